@@ -8,28 +8,38 @@
 //! Defines the AST structure of a graph DSL file
 
 use regex::Regex;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt;
+use std::sync::Arc;
 use tree_sitter::CaptureQuantifier;
 use tree_sitter::Language;
 use tree_sitter::Query;
 
+use crate::functions::Functions;
 use crate::parser::Range;
 use crate::Identifier;
 use crate::Location;
 
-/// A graph DSL file
-#[derive(Debug)]
+/// A graph DSL file.  Its tree-sitter queries are compiled when it is parsed, and are shared
+/// between clones, so cloning a file is a cheap way to reuse it without parsing it again.
+#[derive(Clone, Debug)]
 pub struct File {
     pub language: Language,
     /// The expected global variables used in this file
     pub globals: Vec<Global>,
-    /// The combined query of all stanzas in the file
-    pub query: Option<Query>,
+    /// The combined query of all stanzas in the file, shared between clones.  Use
+    /// [`combined_query`][Self::combined_query] to access it.
+    pub(crate) query: Option<Arc<Query>>,
     /// The list of stanzas in the file
     pub stanzas: Vec<Stanza>,
     /// Attribute shorthands defined in the file
     pub shorthands: AttributeShorthands,
+    /// The stanzas that target other languages than `language`, declared with a `language`
+    /// label, grouped into one file per language and keyed by the name of the language.  These
+    /// files have the same globals and attribute shorthands as this one.
+    pub languages: BTreeMap<Identifier, File>,
 }
 
 impl File {
@@ -40,12 +50,60 @@ impl File {
             query: None,
             stanzas: Vec::new(),
             shorthands: AttributeShorthands::new(),
+            languages: BTreeMap::new(),
         }
     }
+
+    /// Returns the names of the global variables that must be provided when executing this file,
+    /// i.e., the declared globals that do not have a default value.
+    pub fn required_globals(&self) -> impl Iterator<Item = &Identifier> {
+        self.globals
+            .iter()
+            .filter(|global| global.default.is_none())
+            .map(|global| &global.name)
+    }
+
+    /// Returns the names of the declared global variables that are actually read somewhere in
+    /// this file, either in a stanza, in any language, or in an attribute shorthand.
+    pub fn referenced_globals(&self) -> HashSet<Identifier> {
+        let mut reads = HashSet::new();
+        let language_stanzas = self.languages.values().flat_map(|file| &file.stanzas);
+        for stanza in self.stanzas.iter().chain(language_stanzas) {
+            for statement in &stanza.statements {
+                statement.collect_variable_reads(&mut reads);
+            }
+        }
+        for shorthand in self.shorthands.iter() {
+            let mut shorthand_reads = HashSet::new();
+            for attribute in &shorthand.attributes {
+                attribute.value.collect_variable_reads(&mut shorthand_reads);
+            }
+            shorthand_reads.remove(&shorthand.variable.name);
+            reads.extend(shorthand_reads);
+        }
+        self.globals
+            .iter()
+            .filter(|global| reads.contains(&global.name))
+            .map(|global| global.name.clone())
+            .collect()
+    }
+
+    /// Returns the stanzas in this file that target its main language, in the order in which
+    /// they appear.  The stanzas for other languages are found in [`languages`][Self::languages].
+    pub fn stanzas(&self) -> &[Stanza] {
+        &self.stanzas
+    }
+
+    /// Returns the combined query of all stanzas in this file, which has one pattern per
+    /// stanza, in the order in which the stanzas appear.  Returns `None` for a file that was
+    /// created with [`File::new`][] rather than parsed.
+    pub fn combined_query(&self) -> Option<&Query> {
+        self.query.as_deref()
+    }
 }
 
 /// A global variable
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Global {
     /// The name of the global variable
     pub name: Identifier,
@@ -57,10 +115,21 @@ pub struct Global {
 }
 
 /// One stanza within a file
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Stanza {
-    /// The tree-sitter query for this stanza
-    pub query: Query,
+    /// The name of this stanza, if it was given one with a `stanza` label
+    pub name: Option<Identifier>,
+    /// The text of the `;` comment lines directly preceding this stanza, if any, without their
+    /// comment markers
+    pub doc: Option<String>,
+    /// The tree-sitter query for this stanza, shared between clones.  Use
+    /// [`query`][Self::query] to access it.
+    pub(crate) query: Arc<Query>,
+    /// The name of the language that the query of this stanza targets, if it was given one with
+    /// a `language` label, or `None` if it targets the main language of the file
+    pub language: Option<Identifier>,
+    /// The source text of the query pattern of this stanza
+    pub query_source: String,
     /// The list of statements in the stanza
     pub statements: Vec<Statement>,
     /// Capture index of the full match in the stanza query
@@ -68,21 +137,39 @@ pub struct Stanza {
     /// Capture index of the full match in the file query
     pub full_match_file_capture_index: usize,
     pub range: Range,
+    /// The path of the included file that this stanza was read from, or `None` if it was read
+    /// from the main graph DSL file.  The locations in this stanza are relative to that file.
+    pub file: Option<String>,
+}
+
+impl Stanza {
+    /// Returns the location of this stanza in the graph DSL file.
+    pub fn location(&self) -> Location {
+        self.range.start
+    }
+
+    /// Returns the tree-sitter query for this stanza.
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
 }
 
 /// A statement that can appear in a graph DSL stanza
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Statement {
     // Variables
     DeclareImmutable(DeclareImmutable),
+    DestructureImmutable(DestructureImmutable),
     DeclareMutable(DeclareMutable),
     Assign(Assign),
     // Graph nodes
     CreateGraphNode(CreateGraphNode),
     AddGraphNodeAttribute(AddGraphNodeAttribute),
+    DeleteGraphNodeAttribute(DeleteGraphNodeAttribute),
     // Edges
     CreateEdge(CreateEdge),
     AddEdgeAttribute(AddEdgeAttribute),
+    DeleteEdgeAttribute(DeleteEdgeAttribute),
     // Regular expression
     Scan(Scan),
     // Debugging
@@ -97,12 +184,15 @@ impl std::fmt::Display for Statement {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Self::DeclareImmutable(stmt) => stmt.fmt(f),
+            Self::DestructureImmutable(stmt) => stmt.fmt(f),
             Self::DeclareMutable(stmt) => stmt.fmt(f),
             Self::Assign(stmt) => stmt.fmt(f),
             Self::CreateGraphNode(stmt) => stmt.fmt(f),
             Self::AddGraphNodeAttribute(stmt) => stmt.fmt(f),
+            Self::DeleteGraphNodeAttribute(stmt) => stmt.fmt(f),
             Self::CreateEdge(stmt) => stmt.fmt(f),
             Self::AddEdgeAttribute(stmt) => stmt.fmt(f),
+            Self::DeleteEdgeAttribute(stmt) => stmt.fmt(f),
             Self::Scan(stmt) => stmt.fmt(f),
             Self::Print(stmt) => stmt.fmt(f),
             Self::If(stmt) => stmt.fmt(f),
@@ -111,12 +201,19 @@ impl std::fmt::Display for Statement {
     }
 }
 
-/// An `attr` statement that adds an attribute to an edge
-#[derive(Debug, Eq, PartialEq)]
+/// An `attr` or `set-attr` statement, optionally followed by `?`, that adds an attribute to an
+/// edge
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct AddEdgeAttribute {
     pub source: Expression,
     pub sink: Expression,
     pub attributes: Vec<Attribute>,
+    /// Whether existing attributes with the same name are overwritten (`set-attr`), instead of
+    /// causing an error (`attr`)
+    pub overwrite: bool,
+    /// Whether attributes whose value is null are skipped (`attr?` or `set-attr?`), instead of
+    /// being added with a null value
+    pub skip_null: bool,
     pub location: Location,
 }
 
@@ -128,7 +225,13 @@ impl From<AddEdgeAttribute> for Statement {
 
 impl std::fmt::Display for AddEdgeAttribute {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "attr ({} -> {})", self.source, self.sink)?;
+        let keyword = if self.overwrite { "set-attr" } else { "attr" };
+        let optional = if self.skip_null { "?" } else { "" };
+        write!(
+            f,
+            "{}{} ({} -> {})",
+            keyword, optional, self.source, self.sink
+        )?;
         for attr in &self.attributes {
             write!(f, " {}", attr)?;
         }
@@ -136,11 +239,21 @@ impl std::fmt::Display for AddEdgeAttribute {
     }
 }
 
-/// An `attr` statement that adds an attribute to a graph node
-#[derive(Debug, Eq, PartialEq)]
+/// An `attr` or `set-attr` statement, optionally followed by `?`, that adds an attribute to a
+/// graph node
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct AddGraphNodeAttribute {
     pub node: Expression,
     pub attributes: Vec<Attribute>,
+    /// Expressions (`..value`) whose values are lists or sets of name and value pairs, which are
+    /// added as attributes after `attributes`
+    pub spreads: Vec<Expression>,
+    /// Whether existing attributes with the same name are overwritten (`set-attr`), instead of
+    /// causing an error (`attr`)
+    pub overwrite: bool,
+    /// Whether attributes whose value is null are skipped (`attr?` or `set-attr?`), instead of
+    /// being added with a null value
+    pub skip_null: bool,
     pub location: Location,
 }
 
@@ -152,16 +265,70 @@ impl From<AddGraphNodeAttribute> for Statement {
 
 impl std::fmt::Display for AddGraphNodeAttribute {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "attr ({})", self.node)?;
+        let keyword = if self.overwrite { "set-attr" } else { "attr" };
+        let optional = if self.skip_null { "?" } else { "" };
+        write!(f, "{}{} ({})", keyword, optional, self.node)?;
         for attr in &self.attributes {
             write!(f, " {}", attr)?;
         }
+        for spread in &self.spreads {
+            write!(f, " ..{}", spread)?;
+        }
+        write!(f, " at {}", self.location)
+    }
+}
+
+/// A `del-attr` statement that removes attributes from an edge
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeleteEdgeAttribute {
+    pub source: Expression,
+    pub sink: Expression,
+    pub names: Vec<Identifier>,
+    pub location: Location,
+}
+
+impl From<DeleteEdgeAttribute> for Statement {
+    fn from(statement: DeleteEdgeAttribute) -> Statement {
+        Statement::DeleteEdgeAttribute(statement)
+    }
+}
+
+impl std::fmt::Display for DeleteEdgeAttribute {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "del-attr ({} -> {})", self.source, self.sink)?;
+        for name in &self.names {
+            write!(f, " {}", name)?;
+        }
+        write!(f, " at {}", self.location)
+    }
+}
+
+/// A `del-attr` statement that removes attributes from a graph node
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeleteGraphNodeAttribute {
+    pub node: Expression,
+    pub names: Vec<Identifier>,
+    pub location: Location,
+}
+
+impl From<DeleteGraphNodeAttribute> for Statement {
+    fn from(statement: DeleteGraphNodeAttribute) -> Statement {
+        Statement::DeleteGraphNodeAttribute(statement)
+    }
+}
+
+impl std::fmt::Display for DeleteGraphNodeAttribute {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "del-attr ({})", self.node)?;
+        for name in &self.names {
+            write!(f, " {}", name)?;
+        }
         write!(f, " at {}", self.location)
     }
 }
 
 /// A `set` statement that updates the value of a mutable variable
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Assign {
     pub variable: Variable,
     pub value: Expression,
@@ -185,7 +352,7 @@ impl std::fmt::Display for Assign {
 }
 
 /// The name and value of an attribute
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Attribute {
     pub name: Identifier,
     pub value: Expression,
@@ -198,8 +365,9 @@ impl std::fmt::Display for Attribute {
 }
 
 /// An `edge` statement that creates a new edge
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct CreateEdge {
+    pub edge: Option<Variable>,
     pub source: Expression,
     pub sink: Expression,
     pub location: Location,
@@ -213,16 +381,16 @@ impl From<CreateEdge> for Statement {
 
 impl std::fmt::Display for CreateEdge {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "edge {} -> {} at {}",
-            self.source, self.sink, self.location,
-        )
+        write!(f, "edge ")?;
+        if let Some(edge) = &self.edge {
+            write!(f, "{} = ", edge)?;
+        }
+        write!(f, "{} -> {} at {}", self.source, self.sink, self.location)
     }
 }
 
 /// A `node` statement that creates a new graph node
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct CreateGraphNode {
     pub node: Variable,
     pub location: Location,
@@ -241,7 +409,7 @@ impl std::fmt::Display for CreateGraphNode {
 }
 
 /// A `let` statement that declares a new immutable variable
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct DeclareImmutable {
     pub variable: Variable,
     pub value: Expression,
@@ -264,8 +432,38 @@ impl std::fmt::Display for DeclareImmutable {
     }
 }
 
+/// A `let` statement that unpacks the elements of a list into several immutable variables
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DestructureImmutable {
+    pub variables: Vec<Variable>,
+    pub value: Expression,
+    pub location: Location,
+}
+
+impl From<DestructureImmutable> for Statement {
+    fn from(statement: DestructureImmutable) -> Statement {
+        Statement::DestructureImmutable(statement)
+    }
+}
+
+impl std::fmt::Display for DestructureImmutable {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "let [")?;
+        let mut first = true;
+        for variable in &self.variables {
+            if first {
+                first = false;
+            } else {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", variable)?;
+        }
+        write!(f, "] = {} at {}", self.value, self.location)
+    }
+}
+
 /// A `var` statement that declares a new mutable variable
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct DeclareMutable {
     pub variable: Variable,
     pub value: Expression,
@@ -289,7 +487,7 @@ impl std::fmt::Display for DeclareMutable {
 }
 
 /// A `print` statement that prints out some debugging information
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Print {
     pub values: Vec<Expression>,
     pub location: Location,
@@ -312,10 +510,12 @@ impl std::fmt::Display for Print {
 }
 
 /// A `scan` statement that matches regular expressions against a string
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Scan {
     pub value: Expression,
     pub arms: Vec<ScanArm>,
+    /// The `else` arm, which handles any text that none of the regular expressions match
+    pub default_arm: Option<ScanDefaultArm>,
     pub location: Location,
 }
 
@@ -332,7 +532,7 @@ impl std::fmt::Display for Scan {
 }
 
 /// One arm of a `scan` statement
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ScanArm {
     pub regex: Regex,
     pub statements: Vec<Statement>,
@@ -353,8 +553,15 @@ impl std::fmt::Display for ScanArm {
     }
 }
 
+/// The `else` arm of a `scan` statement
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScanDefaultArm {
+    pub statements: Vec<Statement>,
+    pub location: Location,
+}
+
 /// A `cond` conditional statement that selects the first branch with a matching condition
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct If {
     pub arms: Vec<IfArm>,
     pub location: Location,
@@ -386,7 +593,7 @@ impl std::fmt::Display for If {
 }
 
 /// One arm of a `cond` statement
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct IfArm {
     pub conditions: Vec<Condition>,
     pub statements: Vec<Statement>,
@@ -395,7 +602,7 @@ pub struct IfArm {
 
 struct DisplayConditions<'a>(&'a Vec<Condition>);
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Condition {
     Some {
         value: Expression,
@@ -443,7 +650,7 @@ impl std::fmt::Display for Condition {
 }
 
 /// A `for in` statement
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct ForIn {
     pub variable: UnscopedVariable,
     pub value: Expression,
@@ -468,7 +675,7 @@ impl std::fmt::Display for ForIn {
 }
 
 /// A reference to a variable
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Variable {
     Scoped(ScopedVariable),
     Unscoped(UnscopedVariable),
@@ -484,7 +691,7 @@ impl std::fmt::Display for Variable {
 }
 
 /// A reference to a scoped variable
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct ScopedVariable {
     pub scope: Box<Expression>,
     pub name: Identifier,
@@ -504,7 +711,7 @@ impl std::fmt::Display for ScopedVariable {
 }
 
 /// A reference to a global or local variable
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct UnscopedVariable {
     pub name: Identifier,
     pub location: Location,
@@ -523,7 +730,7 @@ impl std::fmt::Display for UnscopedVariable {
 }
 
 /// An expression that can appear in a graph DSL file
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Expression {
     // Literals
     FalseLiteral,
@@ -544,10 +751,42 @@ pub enum Expression {
     Variable(Variable),
     // Functions
     Call(Call),
+    MatchFunction(MatchFunction),
+    // Operators
+    Comparison(Comparison),
+    Logical(Logical),
+    Not(Not),
     // Regular expression
     RegexCapture(RegexCapture),
 }
 
+impl Expression {
+    const OR_PRECEDENCE: u8 = 1;
+    const AND_PRECEDENCE: u8 = 2;
+    const COMPARISON_PRECEDENCE: u8 = 3;
+    const NOT_PRECEDENCE: u8 = 4;
+    const OPERAND_PRECEDENCE: u8 = 5;
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Expression::Logical(expr) => expr.operator.precedence(),
+            Expression::Comparison(_) => Self::COMPARISON_PRECEDENCE,
+            Expression::Not(_) => Self::NOT_PRECEDENCE,
+            _ => Self::OPERAND_PRECEDENCE,
+        }
+    }
+
+    /// Formats this expression as an operand of an operator, adding parentheses if it binds
+    /// less tightly than `precedence`.
+    fn fmt_operand(&self, f: &mut std::fmt::Formatter, precedence: u8) -> std::fmt::Result {
+        if self.precedence() < precedence {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
 impl std::fmt::Display for Expression {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -563,13 +802,17 @@ impl std::fmt::Display for Expression {
             Expression::Capture(expr) => expr.fmt(f),
             Expression::Variable(expr) => expr.fmt(f),
             Expression::Call(expr) => expr.fmt(f),
+            Expression::MatchFunction(expr) => expr.fmt(f),
+            Expression::Comparison(expr) => expr.fmt(f),
+            Expression::Logical(expr) => expr.fmt(f),
+            Expression::Not(expr) => expr.fmt(f),
             Expression::RegexCapture(expr) => expr.fmt(f),
         }
     }
 }
 
 /// A function call
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Call {
     pub function: Identifier,
     pub parameters: Vec<Expression>,
@@ -591,8 +834,172 @@ impl std::fmt::Display for Call {
     }
 }
 
+/// A call to one of the functions that describe the current match.  These functions are built
+/// into the executors instead of the function library, because they need the match itself.  The
+/// parser produces ordinary [`Call`][]s for them, which the checker replaces with this expression.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MatchFunction {
+    /// `(match-index)`
+    MatchIndex,
+    /// `(full-match)`
+    FullMatch,
+    /// `(capture-name @capture)`
+    CaptureName(Capture),
+    /// `(quantifier @capture)`
+    Quantifier(Capture),
+}
+
+impl MatchFunction {
+    pub const MATCH_INDEX: &'static str = "match-index";
+    pub const FULL_MATCH: &'static str = "full-match";
+    pub const CAPTURE_NAME: &'static str = "capture-name";
+    pub const QUANTIFIER: &'static str = "quantifier";
+
+    /// Returns whether `name` is the name of a match function, which cannot be called as an
+    /// ordinary function.
+    pub fn is_reserved(name: &str) -> bool {
+        matches!(
+            name,
+            Self::MATCH_INDEX | Self::FULL_MATCH | Self::CAPTURE_NAME | Self::QUANTIFIER
+        )
+    }
+}
+
+impl From<MatchFunction> for Expression {
+    fn from(expr: MatchFunction) -> Expression {
+        Expression::MatchFunction(expr)
+    }
+}
+
+impl std::fmt::Display for MatchFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::MatchIndex => write!(f, "({})", Self::MATCH_INDEX),
+            Self::FullMatch => write!(f, "({})", Self::FULL_MATCH),
+            Self::CaptureName(capture) => write!(f, "({} {})", Self::CAPTURE_NAME, capture),
+            Self::Quantifier(capture) => write!(f, "({} {})", Self::QUANTIFIER, capture),
+        }
+    }
+}
+
+/// A comparison of two values using an infix operator
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Comparison {
+    pub operator: ComparisonOperator,
+    pub left: Box<Expression>,
+    pub right: Box<Expression>,
+    pub location: Location,
+}
+
+impl From<Comparison> for Expression {
+    fn from(expr: Comparison) -> Expression {
+        Expression::Comparison(expr)
+    }
+}
+
+impl std::fmt::Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.left.fmt_operand(f, Expression::NOT_PRECEDENCE)?;
+        write!(f, " {} ", self.operator)?;
+        self.right.fmt_operand(f, Expression::NOT_PRECEDENCE)
+    }
+}
+
+/// The operator of a comparison expression
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ComparisonOperator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl std::fmt::Display for ComparisonOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Eq => write!(f, "=="),
+            Self::Ne => write!(f, "!="),
+            Self::Lt => write!(f, "<"),
+            Self::Le => write!(f, "<="),
+            Self::Gt => write!(f, ">"),
+            Self::Ge => write!(f, ">="),
+        }
+    }
+}
+
+/// A short-circuiting boolean operation on two values
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Logical {
+    pub operator: LogicalOperator,
+    pub left: Box<Expression>,
+    pub right: Box<Expression>,
+    pub location: Location,
+}
+
+impl From<Logical> for Expression {
+    fn from(expr: Logical) -> Expression {
+        Expression::Logical(expr)
+    }
+}
+
+impl std::fmt::Display for Logical {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let precedence = self.operator.precedence();
+        self.left.fmt_operand(f, precedence)?;
+        write!(f, " {} ", self.operator)?;
+        self.right.fmt_operand(f, precedence + 1)
+    }
+}
+
+/// The operator of a boolean operation
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LogicalOperator {
+    And,
+    Or,
+}
+
+impl LogicalOperator {
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Or => Expression::OR_PRECEDENCE,
+            Self::And => Expression::AND_PRECEDENCE,
+        }
+    }
+}
+
+impl std::fmt::Display for LogicalOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::And => write!(f, "&&"),
+            Self::Or => write!(f, "||"),
+        }
+    }
+}
+
+/// A boolean negation
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Not {
+    pub value: Box<Expression>,
+    pub location: Location,
+}
+
+impl From<Not> for Expression {
+    fn from(expr: Not) -> Expression {
+        Expression::Not(expr)
+    }
+}
+
+impl std::fmt::Display for Not {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "!")?;
+        self.value.fmt_operand(f, Expression::NOT_PRECEDENCE)
+    }
+}
+
 /// A capture expression that references a syntax node
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Capture {
     /// The name of the capture
     pub name: Identifier,
@@ -618,7 +1025,7 @@ impl std::fmt::Display for Capture {
 }
 
 /// An integer constant
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct IntegerConstant {
     pub value: u32,
 }
@@ -636,7 +1043,7 @@ impl std::fmt::Display for IntegerConstant {
 }
 
 /// An ordered list of values
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct ListLiteral {
     pub elements: Vec<Expression>,
 }
@@ -664,7 +1071,7 @@ impl std::fmt::Display for ListLiteral {
 }
 
 /// An list comprehension
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct ListComprehension {
     pub element: Box<Expression>,
     pub variable: UnscopedVariable,
@@ -689,9 +1096,13 @@ impl std::fmt::Display for ListComprehension {
 }
 
 /// A reference to one of the regex captures in a `scan` statement
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct RegexCapture {
+    /// The index of the capture group.  For named references, this is set by the checker.
     pub match_index: usize,
+    /// The name of the capture group, if it is referenced by name instead of by index
+    pub name: Option<Identifier>,
+    pub location: Location,
 }
 
 impl From<RegexCapture> for Expression {
@@ -702,12 +1113,15 @@ impl From<RegexCapture> for Expression {
 
 impl std::fmt::Display for RegexCapture {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "${}", self.match_index)
+        match &self.name {
+            Some(name) => write!(f, "${}", name),
+            None => write!(f, "${}", self.match_index),
+        }
     }
 }
 
 /// An unordered set of values
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct SetLiteral {
     pub elements: Vec<Expression>,
 }
@@ -735,7 +1149,7 @@ impl std::fmt::Display for SetLiteral {
 }
 
 /// An set comprehension
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct SetComprehension {
     pub element: Box<Expression>,
     pub variable: UnscopedVariable,
@@ -760,7 +1174,7 @@ impl std::fmt::Display for SetComprehension {
 }
 
 /// A string constant
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct StringConstant {
     pub value: String,
 }
@@ -796,7 +1210,7 @@ impl From<ScopedVariable> for Expression {
 }
 
 /// Attribute shorthands
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct AttributeShorthands(HashMap<Identifier, AttributeShorthand>);
 
 impl AttributeShorthands {
@@ -822,7 +1236,7 @@ impl AttributeShorthands {
 }
 
 /// An attribute shorthand
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct AttributeShorthand {
     pub name: Identifier,
     pub variable: UnscopedVariable,
@@ -839,3 +1253,312 @@ impl std::fmt::Display for AttributeShorthand {
         write!(f, " at {}", self.location)
     }
 }
+
+// ------------------------------------------------------------------------------------------------
+// Variable reads
+
+impl Statement {
+    /// Adds the names of all unscoped variables read by this statement to `reads`.
+    fn collect_variable_reads(&self, reads: &mut HashSet<Identifier>) {
+        match self {
+            Self::DeclareImmutable(stmt) => {
+                stmt.variable.collect_variable_reads(reads);
+                stmt.value.collect_variable_reads(reads);
+            }
+            Self::DestructureImmutable(stmt) => {
+                for variable in &stmt.variables {
+                    variable.collect_variable_reads(reads);
+                }
+                stmt.value.collect_variable_reads(reads);
+            }
+            Self::DeclareMutable(stmt) => {
+                stmt.variable.collect_variable_reads(reads);
+                stmt.value.collect_variable_reads(reads);
+            }
+            Self::Assign(stmt) => {
+                stmt.variable.collect_variable_reads(reads);
+                stmt.value.collect_variable_reads(reads);
+            }
+            Self::CreateGraphNode(stmt) => stmt.node.collect_variable_reads(reads),
+            Self::AddGraphNodeAttribute(stmt) => {
+                stmt.node.collect_variable_reads(reads);
+                for attribute in &stmt.attributes {
+                    attribute.value.collect_variable_reads(reads);
+                }
+                for spread in &stmt.spreads {
+                    spread.collect_variable_reads(reads);
+                }
+            }
+            Self::DeleteGraphNodeAttribute(stmt) => stmt.node.collect_variable_reads(reads),
+            Self::CreateEdge(stmt) => {
+                stmt.source.collect_variable_reads(reads);
+                stmt.sink.collect_variable_reads(reads);
+            }
+            Self::DeleteEdgeAttribute(stmt) => {
+                stmt.source.collect_variable_reads(reads);
+                stmt.sink.collect_variable_reads(reads);
+            }
+            Self::AddEdgeAttribute(stmt) => {
+                stmt.source.collect_variable_reads(reads);
+                stmt.sink.collect_variable_reads(reads);
+                for attribute in &stmt.attributes {
+                    attribute.value.collect_variable_reads(reads);
+                }
+            }
+            Self::Scan(stmt) => {
+                stmt.value.collect_variable_reads(reads);
+                for arm in &stmt.arms {
+                    for statement in &arm.statements {
+                        statement.collect_variable_reads(reads);
+                    }
+                }
+                if let Some(arm) = &stmt.default_arm {
+                    for statement in &arm.statements {
+                        statement.collect_variable_reads(reads);
+                    }
+                }
+            }
+            Self::Print(stmt) => {
+                for value in &stmt.values {
+                    value.collect_variable_reads(reads);
+                }
+            }
+            Self::If(stmt) => {
+                for arm in &stmt.arms {
+                    for condition in &arm.conditions {
+                        match condition {
+                            Condition::Some { value, .. }
+                            | Condition::None { value, .. }
+                            | Condition::Bool { value, .. } => value.collect_variable_reads(reads),
+                        }
+                    }
+                    for statement in &arm.statements {
+                        statement.collect_variable_reads(reads);
+                    }
+                }
+            }
+            Self::ForIn(stmt) => {
+                stmt.value.collect_variable_reads(reads);
+                for statement in &stmt.statements {
+                    statement.collect_variable_reads(reads);
+                }
+            }
+        }
+    }
+}
+
+impl Variable {
+    /// Adds the names of all unscoped variables read when this variable is the target of a
+    /// statement to `reads`.  Only the scope of a scoped variable is read; an unscoped target
+    /// is defined, not read.
+    fn collect_variable_reads(&self, reads: &mut HashSet<Identifier>) {
+        if let Variable::Scoped(variable) = self {
+            variable.scope.collect_variable_reads(reads);
+        }
+    }
+}
+
+impl Expression {
+    /// Adds the names of all unscoped variables read by this expression to `reads`.
+    fn collect_variable_reads(&self, reads: &mut HashSet<Identifier>) {
+        match self {
+            Self::FalseLiteral
+            | Self::NullLiteral
+            | Self::TrueLiteral
+            | Self::IntegerConstant(_)
+            | Self::StringConstant(_)
+            | Self::Capture(_)
+            | Self::MatchFunction(_)
+            | Self::RegexCapture(_) => {}
+            Self::ListLiteral(expr) => {
+                for element in &expr.elements {
+                    element.collect_variable_reads(reads);
+                }
+            }
+            Self::SetLiteral(expr) => {
+                for element in &expr.elements {
+                    element.collect_variable_reads(reads);
+                }
+            }
+            Self::ListComprehension(expr) => {
+                expr.element.collect_variable_reads(reads);
+                expr.value.collect_variable_reads(reads);
+            }
+            Self::SetComprehension(expr) => {
+                expr.element.collect_variable_reads(reads);
+                expr.value.collect_variable_reads(reads);
+            }
+            Self::Variable(Variable::Scoped(variable)) => {
+                variable.scope.collect_variable_reads(reads)
+            }
+            Self::Variable(Variable::Unscoped(variable)) => {
+                reads.insert(variable.name.clone());
+            }
+            Self::Call(expr) => {
+                for parameter in &expr.parameters {
+                    parameter.collect_variable_reads(reads);
+                }
+            }
+            Self::Comparison(expr) => {
+                expr.left.collect_variable_reads(reads);
+                expr.right.collect_variable_reads(reads);
+            }
+            Self::Logical(expr) => {
+                expr.left.collect_variable_reads(reads);
+                expr.right.collect_variable_reads(reads);
+            }
+            Self::Not(expr) => expr.value.collect_variable_reads(reads),
+        }
+    }
+}
+
+// Stanza locality
+
+impl File {
+    /// Returns whether every stanza in this file only depends on its own matches, so that the
+    /// stanzas can be executed independently of each other.  That is the case if no statement
+    /// or attribute shorthand uses a scoped variable, and every function that is called is
+    /// [stanza-local][crate::functions::Function::is_stanza_local] in `functions`.
+    pub fn is_stanza_local(&self, functions: &Functions) -> bool {
+        let language_stanzas = self.languages.values().flat_map(|file| &file.stanzas);
+        self.stanzas.iter().chain(language_stanzas).all(|stanza| {
+            stanza
+                .statements
+                .iter()
+                .all(|statement| statement.is_stanza_local(functions))
+        }) && self.shorthands.iter().all(|shorthand| {
+            shorthand
+                .attributes
+                .iter()
+                .all(|attribute| attribute.value.is_stanza_local(functions))
+        })
+    }
+}
+
+impl Statement {
+    /// Returns whether this statement only depends on the match that it is executed for.
+    fn is_stanza_local(&self, functions: &Functions) -> bool {
+        let all_local = |statements: &[Statement]| {
+            statements
+                .iter()
+                .all(|statement| statement.is_stanza_local(functions))
+        };
+        match self {
+            Self::DeclareImmutable(stmt) => {
+                stmt.variable.is_stanza_local() && stmt.value.is_stanza_local(functions)
+            }
+            Self::DestructureImmutable(stmt) => {
+                stmt.variables.iter().all(Variable::is_stanza_local)
+                    && stmt.value.is_stanza_local(functions)
+            }
+            Self::DeclareMutable(stmt) => {
+                stmt.variable.is_stanza_local() && stmt.value.is_stanza_local(functions)
+            }
+            Self::Assign(stmt) => {
+                stmt.variable.is_stanza_local() && stmt.value.is_stanza_local(functions)
+            }
+            Self::CreateGraphNode(stmt) => stmt.node.is_stanza_local(),
+            Self::AddGraphNodeAttribute(stmt) => {
+                stmt.node.is_stanza_local(functions)
+                    && stmt
+                        .attributes
+                        .iter()
+                        .all(|attribute| attribute.value.is_stanza_local(functions))
+                    && stmt
+                        .spreads
+                        .iter()
+                        .all(|spread| spread.is_stanza_local(functions))
+            }
+            Self::DeleteGraphNodeAttribute(stmt) => stmt.node.is_stanza_local(functions),
+            Self::CreateEdge(stmt) => {
+                stmt.source.is_stanza_local(functions) && stmt.sink.is_stanza_local(functions)
+            }
+            Self::DeleteEdgeAttribute(stmt) => {
+                stmt.source.is_stanza_local(functions) && stmt.sink.is_stanza_local(functions)
+            }
+            Self::AddEdgeAttribute(stmt) => {
+                stmt.source.is_stanza_local(functions)
+                    && stmt.sink.is_stanza_local(functions)
+                    && stmt
+                        .attributes
+                        .iter()
+                        .all(|attribute| attribute.value.is_stanza_local(functions))
+            }
+            Self::Scan(stmt) => {
+                stmt.value.is_stanza_local(functions)
+                    && stmt.arms.iter().all(|arm| all_local(&arm.statements))
+                    && stmt
+                        .default_arm
+                        .iter()
+                        .all(|arm| all_local(&arm.statements))
+            }
+            Self::Print(stmt) => stmt
+                .values
+                .iter()
+                .all(|value| value.is_stanza_local(functions)),
+            Self::If(stmt) => stmt.arms.iter().all(|arm| {
+                arm.conditions.iter().all(|condition| match condition {
+                    Condition::Some { value, .. }
+                    | Condition::None { value, .. }
+                    | Condition::Bool { value, .. } => value.is_stanza_local(functions),
+                }) && all_local(&arm.statements)
+            }),
+            Self::ForIn(stmt) => {
+                stmt.value.is_stanza_local(functions) && all_local(&stmt.statements)
+            }
+        }
+    }
+}
+
+impl Variable {
+    /// Returns whether this variable is local to the match that defines it.  Scoped variables
+    /// can be shared between the matches of different stanzas.
+    fn is_stanza_local(&self) -> bool {
+        matches!(self, Variable::Unscoped(_))
+    }
+}
+
+impl Expression {
+    /// Returns whether this expression only depends on the match that it is evaluated for.
+    fn is_stanza_local(&self, functions: &Functions) -> bool {
+        match self {
+            Self::FalseLiteral
+            | Self::NullLiteral
+            | Self::TrueLiteral
+            | Self::IntegerConstant(_)
+            | Self::StringConstant(_)
+            | Self::Capture(_)
+            | Self::MatchFunction(_)
+            | Self::RegexCapture(_) => true,
+            Self::ListLiteral(expr) => expr
+                .elements
+                .iter()
+                .all(|element| element.is_stanza_local(functions)),
+            Self::SetLiteral(expr) => expr
+                .elements
+                .iter()
+                .all(|element| element.is_stanza_local(functions)),
+            Self::ListComprehension(expr) => {
+                expr.element.is_stanza_local(functions) && expr.value.is_stanza_local(functions)
+            }
+            Self::SetComprehension(expr) => {
+                expr.element.is_stanza_local(functions) && expr.value.is_stanza_local(functions)
+            }
+            Self::Variable(variable) => variable.is_stanza_local(),
+            Self::Call(expr) => {
+                functions.is_stanza_local(&expr.function).unwrap_or(false)
+                    && expr
+                        .parameters
+                        .iter()
+                        .all(|parameter| parameter.is_stanza_local(functions))
+            }
+            Self::Comparison(expr) => {
+                expr.left.is_stanza_local(functions) && expr.right.is_stanza_local(functions)
+            }
+            Self::Logical(expr) => {
+                expr.left.is_stanza_local(functions) && expr.right.is_stanza_local(functions)
+            }
+            Self::Not(expr) => expr.value.is_stanza_local(functions),
+        }
+    }
+}
@@ -85,7 +85,7 @@ fn main() -> Result<()> {
             .with_context(|| format!("Expected key-value pair separated by '=', got {}.", kv))?;
         globals_.add(
             Identifier::from(kv_.0),
-            graph::Value::String(kv_.1.to_string()),
+            graph::Value::String(kv_.1.to_string().into()),
         )?;
     }
 
@@ -98,7 +98,10 @@ fn main() -> Result<()> {
     let tsg = std::fs::read(tsg_path)
         .with_context(|| format!("Cannot read TSG file {}", tsg_path.display()))?;
     let tsg = String::from_utf8(tsg)?;
-    let file = match File::from_str(language, &tsg) {
+    // Included files are resolved relative to the directory of the TSG file.
+    let tsg_dir = tsg_path.parent().unwrap_or_else(|| Path::new(""));
+    let resolver = |path: &str| std::fs::read_to_string(tsg_dir.join(path));
+    let file = match File::from_str_with_resolver(language, &tsg, resolver) {
         Ok(file) => file,
         Err(err) => {
             eprintln!("{}", err.display_pretty(tsg_path, &tsg));
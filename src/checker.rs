@@ -8,6 +8,7 @@
 use std::collections::HashSet;
 use std::path::Path;
 
+use regex::Regex;
 use thiserror::Error;
 use tree_sitter::CaptureQuantifier;
 use tree_sitter::CaptureQuantifier::One;
@@ -19,6 +20,7 @@ use tree_sitter::Query;
 use crate::ast;
 use crate::parse_error::Excerpt;
 use crate::parser::FULL_MATCH;
+use crate::parser::MAX_EXPRESSION_DEPTH;
 use crate::variables::MutVariables;
 use crate::variables::VariableError;
 use crate::variables::VariableMap;
@@ -40,8 +42,16 @@ pub enum CheckError {
     ExpectedLocalValue(Location),
     #[error("Expected optional value at {0}")]
     ExpectedOptionalValue(Location),
+    #[error("Expression nested more than {0} levels deep at {1}")]
+    ExpressionTooDeep(usize, Location),
+    #[error("In included file \"{0}\": {1}")]
+    InIncludedFile(String, Box<CheckError>),
+    #[error("Invalid parameters in {0} at {1}")]
+    InvalidParameters(String, Location),
     #[error("Nullable regular expression /{0}/ at {1}")]
     NullableRegex(String, Location),
+    #[error("Undefined regex capture ${0} at {1}")]
+    UndefinedRegexCapture(String, Location),
     #[error("Undefined syntax capture @{0} at {1}")]
     UndefinedSyntaxCapture(String, Location),
     #[error("Undefined variable {0} at {1}")]
@@ -81,7 +91,15 @@ impl std::fmt::Display for DisplayCheckErrorPretty<'_> {
             CheckError::ExpectedListValue(location) => *location,
             CheckError::ExpectedLocalValue(location) => *location,
             CheckError::ExpectedOptionalValue(location) => *location,
+            CheckError::ExpressionTooDeep(_, location) => *location,
+            CheckError::InIncludedFile(_, _) => {
+                // The source of the included file is not available to show an excerpt
+                writeln!(f, "{}", self.error)?;
+                return Ok(());
+            }
+            CheckError::InvalidParameters(_, location) => *location,
             CheckError::NullableRegex(_, location) => *location,
+            CheckError::UndefinedRegexCapture(_, location) => *location,
             CheckError::UndefinedSyntaxCapture(_, location) => *location,
             CheckError::UndefinedVariable(_, location) => *location,
             CheckError::UnusedCaptures(_, location) => *location,
@@ -110,6 +128,12 @@ struct CheckContext<'a> {
     stanza_index: usize,
     stanza_query: &'a Query,
     locals: &'a mut dyn MutVariables<VariableResult>,
+    /// The regular expression of the innermost enclosing scan arm, if any
+    regex: Option<&'a Regex>,
+    /// The location of the statement being checked
+    location: Location,
+    /// The number of expressions currently being checked, one inside the other
+    expression_depth: usize,
 }
 
 #[derive(Clone, Debug)]
@@ -143,7 +167,15 @@ impl ast::File {
         }
         let file_query = self.query.as_ref().unwrap();
         for (index, stanza) in self.stanzas.iter_mut().enumerate() {
-            stanza.check(&globals, file_query, index)?;
+            stanza
+                .check(&globals, file_query, index)
+                .map_err(|e| match &stanza.file {
+                    Some(path) => CheckError::InIncludedFile(path.clone(), Box::new(e)),
+                    None => e,
+                })?;
+        }
+        for language_file in self.languages.values_mut() {
+            language_file.check()?;
         }
         Ok(())
     }
@@ -166,6 +198,9 @@ impl ast::Stanza {
             stanza_index,
             stanza_query: &self.query,
             locals: &mut locals,
+            regex: None,
+            location: self.range.start,
+            expression_depth: 0,
         };
         self.full_match_file_capture_index =
             ctx.file_query
@@ -216,14 +251,24 @@ struct StatementResult {
 
 impl ast::Statement {
     fn check(&mut self, ctx: &mut CheckContext) -> Result<StatementResult, CheckError> {
+        let outer_location = std::mem::replace(&mut ctx.location, self.location());
+        let result = self.check_statement(ctx);
+        ctx.location = outer_location;
+        result
+    }
+
+    fn check_statement(&mut self, ctx: &mut CheckContext) -> Result<StatementResult, CheckError> {
         match self {
             Self::DeclareImmutable(stmt) => stmt.check(ctx),
+            Self::DestructureImmutable(stmt) => stmt.check(ctx),
             Self::DeclareMutable(stmt) => stmt.check(ctx),
             Self::Assign(stmt) => stmt.check(ctx),
             Self::CreateGraphNode(stmt) => stmt.check(ctx),
             Self::AddGraphNodeAttribute(stmt) => stmt.check(ctx),
+            Self::DeleteGraphNodeAttribute(stmt) => stmt.check(ctx),
             Self::CreateEdge(stmt) => stmt.check(ctx),
             Self::AddEdgeAttribute(stmt) => stmt.check(ctx),
+            Self::DeleteEdgeAttribute(stmt) => stmt.check(ctx),
             Self::Scan(stmt) => stmt.check(ctx),
             Self::Print(stmt) => stmt.check(ctx),
             Self::If(stmt) => stmt.check(ctx),
@@ -243,6 +288,26 @@ impl ast::DeclareImmutable {
     }
 }
 
+impl ast::DestructureImmutable {
+    fn check(&mut self, ctx: &mut CheckContext) -> Result<StatementResult, CheckError> {
+        let mut used_captures = HashSet::new();
+        let value = self.value.check(ctx)?;
+        used_captures.extend(value.used_captures.iter().cloned());
+        for variable in &mut self.variables {
+            let var_result = variable.check_add(
+                ctx,
+                VariableResult {
+                    is_local: value.is_local,
+                    quantifier: One,
+                },
+                false,
+            )?;
+            used_captures.extend(var_result.used_captures);
+        }
+        Ok(StatementResult { used_captures })
+    }
+}
+
 impl ast::DeclareMutable {
     fn check(&mut self, ctx: &mut CheckContext) -> Result<StatementResult, CheckError> {
         let mut used_captures = HashSet::new();
@@ -290,10 +355,23 @@ impl ast::AddGraphNodeAttribute {
             let attr_result = attribute.check(ctx)?;
             used_captures.extend(attr_result.used_captures);
         }
+        for spread in &mut self.spreads {
+            let spread_result = spread.check(ctx)?;
+            used_captures.extend(spread_result.used_captures);
+        }
         Ok(StatementResult { used_captures })
     }
 }
 
+impl ast::DeleteGraphNodeAttribute {
+    fn check(&mut self, ctx: &mut CheckContext) -> Result<StatementResult, CheckError> {
+        let node_result = self.node.check(ctx)?;
+        Ok(StatementResult {
+            used_captures: node_result.used_captures,
+        })
+    }
+}
+
 impl ast::CreateEdge {
     fn check(&mut self, ctx: &mut CheckContext) -> Result<StatementResult, CheckError> {
         let mut used_captures = HashSet::new();
@@ -301,6 +379,17 @@ impl ast::CreateEdge {
         used_captures.extend(source_result.used_captures);
         let sink_result = self.sink.check(ctx)?;
         used_captures.extend(sink_result.used_captures);
+        if let Some(edge) = &mut self.edge {
+            let edge_result = edge.check_add(
+                ctx,
+                VariableResult {
+                    is_local: true,
+                    quantifier: One,
+                },
+                false,
+            )?;
+            used_captures.extend(edge_result.used_captures);
+        }
         Ok(StatementResult { used_captures })
     }
 }
@@ -320,6 +409,17 @@ impl ast::AddEdgeAttribute {
     }
 }
 
+impl ast::DeleteEdgeAttribute {
+    fn check(&mut self, ctx: &mut CheckContext) -> Result<StatementResult, CheckError> {
+        let mut used_captures = HashSet::new();
+        let source_result = self.source.check(ctx)?;
+        used_captures.extend(source_result.used_captures);
+        let sink_result = self.sink.check(ctx)?;
+        used_captures.extend(sink_result.used_captures);
+        Ok(StatementResult { used_captures })
+    }
+}
+
 impl ast::Scan {
     fn check(&mut self, ctx: &mut CheckContext) -> Result<StatementResult, CheckError> {
         let mut used_captures = HashSet::new();
@@ -350,6 +450,9 @@ impl ast::Scan {
                 stanza_index: ctx.stanza_index,
                 stanza_query: ctx.stanza_query,
                 locals: &mut arm_locals,
+                regex: Some(&arm.regex),
+                location: ctx.location,
+                expression_depth: ctx.expression_depth,
             };
 
             for statement in &mut arm.statements {
@@ -357,6 +460,25 @@ impl ast::Scan {
                 used_captures.extend(stmt_result.used_captures);
             }
         }
+
+        if let Some(default_arm) = &mut self.default_arm {
+            let mut arm_locals = VariableMap::nested(ctx.locals);
+            let mut arm_ctx = CheckContext {
+                globals: ctx.globals,
+                file_query: ctx.file_query,
+                stanza_index: ctx.stanza_index,
+                stanza_query: ctx.stanza_query,
+                locals: &mut arm_locals,
+                regex: None,
+                location: ctx.location,
+                expression_depth: ctx.expression_depth,
+            };
+
+            for statement in &mut default_arm.statements {
+                let stmt_result = statement.check(&mut arm_ctx)?;
+                used_captures.extend(stmt_result.used_captures);
+            }
+        }
         Ok(StatementResult { used_captures })
     }
 }
@@ -389,6 +511,9 @@ impl ast::If {
                 stanza_index: ctx.stanza_index,
                 stanza_query: ctx.stanza_query,
                 locals: &mut arm_locals,
+                regex: ctx.regex,
+                location: ctx.location,
+                expression_depth: ctx.expression_depth,
             };
 
             for statement in &mut arm.statements {
@@ -446,6 +571,9 @@ impl ast::ForIn {
             stanza_index: ctx.stanza_index,
             stanza_query: ctx.stanza_query,
             locals: &mut loop_locals,
+            regex: ctx.regex,
+            location: ctx.location,
+            expression_depth: ctx.expression_depth,
         };
         let var_result = self
             .variable
@@ -474,6 +602,20 @@ struct ExpressionResult {
 
 impl ast::Expression {
     fn check(&mut self, ctx: &mut CheckContext) -> Result<ExpressionResult, CheckError> {
+        // Parsed files cannot nest expressions this deeply, but files can also be built in code.
+        if ctx.expression_depth >= MAX_EXPRESSION_DEPTH {
+            return Err(CheckError::ExpressionTooDeep(
+                MAX_EXPRESSION_DEPTH,
+                ctx.location,
+            ));
+        }
+        ctx.expression_depth += 1;
+        let result = self.check_expression(ctx);
+        ctx.expression_depth -= 1;
+        result
+    }
+
+    fn check_expression(&mut self, ctx: &mut CheckContext) -> Result<ExpressionResult, CheckError> {
         match self {
             Self::FalseLiteral => Ok(ExpressionResult {
                 is_local: true,
@@ -498,7 +640,17 @@ impl ast::Expression {
             Self::SetComprehension(expr) => expr.check(ctx),
             Self::Capture(expr) => expr.check(ctx),
             Self::Variable(expr) => expr.check_get(ctx),
-            Self::Call(expr) => expr.check(ctx),
+            Self::Call(expr) => match expr.resolve_match_function(ctx)? {
+                Some(function) => {
+                    *self = function.into();
+                    self.check_expression(ctx)
+                }
+                None => expr.check(ctx),
+            },
+            Self::MatchFunction(expr) => expr.check(ctx),
+            Self::Comparison(expr) => expr.check(ctx),
+            Self::Logical(expr) => expr.check(ctx),
+            Self::Not(expr) => expr.check(ctx),
             Self::RegexCapture(expr) => expr.check(ctx),
         }
     }
@@ -578,6 +730,9 @@ impl ast::ListComprehension {
             stanza_index: ctx.stanza_index,
             stanza_query: ctx.stanza_query,
             locals: &mut loop_locals,
+            regex: ctx.regex,
+            location: ctx.location,
+            expression_depth: ctx.expression_depth,
         };
         let var_result = self
             .variable
@@ -615,6 +770,9 @@ impl ast::SetComprehension {
             stanza_index: ctx.stanza_index,
             stanza_query: ctx.stanza_query,
             locals: &mut loop_locals,
+            regex: ctx.regex,
+            location: ctx.location,
+            expression_depth: ctx.expression_depth,
         };
         let var_result = self
             .variable
@@ -655,6 +813,34 @@ impl ast::Capture {
 }
 
 impl ast::Call {
+    /// Returns the match function that this call refers to, if any.  Match functions need the
+    /// match itself, so they are resolved here instead of being looked up in the function library
+    /// during execution.
+    fn resolve_match_function(
+        &self,
+        ctx: &CheckContext,
+    ) -> Result<Option<ast::MatchFunction>, CheckError> {
+        use ast::MatchFunction;
+        let function = match (self.function.as_str(), self.parameters.as_slice()) {
+            (MatchFunction::MATCH_INDEX, []) => MatchFunction::MatchIndex,
+            (MatchFunction::FULL_MATCH, []) => MatchFunction::FullMatch,
+            (MatchFunction::CAPTURE_NAME, [ast::Expression::Capture(capture)]) => {
+                MatchFunction::CaptureName(capture.clone())
+            }
+            (MatchFunction::QUANTIFIER, [ast::Expression::Capture(capture)]) => {
+                MatchFunction::Quantifier(capture.clone())
+            }
+            (name, _) if MatchFunction::is_reserved(name) => {
+                return Err(CheckError::InvalidParameters(
+                    self.to_string(),
+                    ctx.location,
+                ))
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(function))
+    }
+
     fn check(&mut self, ctx: &mut CheckContext) -> Result<ExpressionResult, CheckError> {
         let mut is_local = true;
         let mut used_captures = HashSet::new();
@@ -671,8 +857,77 @@ impl ast::Call {
     }
 }
 
+impl ast::MatchFunction {
+    fn check(&mut self, ctx: &mut CheckContext) -> Result<ExpressionResult, CheckError> {
+        let used_captures = match self {
+            Self::MatchIndex | Self::FullMatch => HashSet::default(),
+            Self::CaptureName(capture) | Self::Quantifier(capture) => {
+                capture.check(ctx)?.used_captures
+            }
+        };
+        Ok(ExpressionResult {
+            is_local: true,
+            quantifier: One,
+            used_captures,
+        })
+    }
+}
+
+impl ast::Comparison {
+    fn check(&mut self, ctx: &mut CheckContext) -> Result<ExpressionResult, CheckError> {
+        let left = self.left.check(ctx)?;
+        let right = self.right.check(ctx)?;
+        let mut used_captures = left.used_captures;
+        used_captures.extend(right.used_captures);
+        Ok(ExpressionResult {
+            is_local: left.is_local && right.is_local,
+            quantifier: One,
+            used_captures,
+        })
+    }
+}
+
+impl ast::Logical {
+    fn check(&mut self, ctx: &mut CheckContext) -> Result<ExpressionResult, CheckError> {
+        let left = self.left.check(ctx)?;
+        let right = self.right.check(ctx)?;
+        let mut used_captures = left.used_captures;
+        used_captures.extend(right.used_captures);
+        Ok(ExpressionResult {
+            is_local: left.is_local && right.is_local,
+            quantifier: One,
+            used_captures,
+        })
+    }
+}
+
+impl ast::Not {
+    fn check(&mut self, ctx: &mut CheckContext) -> Result<ExpressionResult, CheckError> {
+        let value = self.value.check(ctx)?;
+        Ok(ExpressionResult {
+            is_local: value.is_local,
+            quantifier: One,
+            used_captures: value.used_captures,
+        })
+    }
+}
+
 impl ast::RegexCapture {
-    fn check(&mut self, _ctx: &mut CheckContext) -> Result<ExpressionResult, CheckError> {
+    fn check(&mut self, ctx: &mut CheckContext) -> Result<ExpressionResult, CheckError> {
+        if let Some(name) = &self.name {
+            self.match_index = ctx
+                .regex
+                .and_then(|regex| regex.capture_names().position(|n| n == Some(name.as_str())))
+                .ok_or_else(|| {
+                    CheckError::UndefinedRegexCapture(name.to_string(), self.location)
+                })?;
+        } else if ctx.regex.is_none() && self.match_index > 0 {
+            // Outside of a regex arm, such as in the default arm of a scan, only $0 is bound.
+            return Err(CheckError::UndefinedRegexCapture(
+                self.match_index.to_string(),
+                self.location,
+            ));
+        }
         Ok(ExpressionResult {
             is_local: true,
             quantifier: One,
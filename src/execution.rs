@@ -5,21 +5,32 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::collections::HashMap;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+use log::warn;
 use thiserror::Error;
 use tree_sitter::CaptureQuantifier;
 use tree_sitter::Node;
+use tree_sitter::QueryCursor;
 use tree_sitter::QueryMatch;
 use tree_sitter::Tree;
 
+use crate::ast::ComparisonOperator;
 use crate::ast::CreateEdge;
 use crate::ast::File;
+use crate::ast::MatchFunction;
 use crate::ast::Stanza;
 use crate::ast::Variable;
 use crate::execution::error::ExecutionError;
 use crate::functions::Functions;
 use crate::graph::Attributes;
 use crate::graph::Graph;
+use crate::graph::GraphEvent;
 use crate::graph::Value;
+use crate::parser::MAX_EXPRESSION_DEPTH;
 use crate::variables::Globals;
 use crate::Identifier;
 use crate::Location;
@@ -32,6 +43,11 @@ impl File {
     /// Executes this graph DSL file against a source file.  You must provide the parsed syntax
     /// tree (`tree`) as well as the source text that it was parsed from (`source`).  You also
     /// provide the set of functions and global variables that are available during execution.
+    ///
+    /// Syntax errors in `tree` do not prevent execution: stanzas are matched against whatever
+    /// the parser recovered.  Use [`ParseError::first`][crate::parse_error::ParseError::first]
+    /// to reject such trees up front, or
+    /// [`ExecutionConfig::skip_matches_with_errors`] to ignore only the affected matches.
     pub fn execute<'a, 'tree>(
         &self,
         tree: &'tree Tree,
@@ -44,11 +60,203 @@ impl File {
         Ok(graph)
     }
 
+    /// Executes this graph DSL file against source files in several languages, which is useful
+    /// when source code embeds code in another language.  The stanzas for the main language of
+    /// the file are executed against `tree`, and the stanzas with a `language NAME` label against
+    /// the syntax tree that `language_trees` maps that name to, along with the source text that
+    /// it was parsed from.  Stanzas for languages that have no tree in `language_trees` are not
+    /// executed, as if they did not match anything.
+    ///
+    /// The stanzas of each language are executed one language after the other, starting with the
+    /// main language, followed by the other languages in order of their names.  Since the syntax
+    /// nodes of different trees are distinct, the executions cannot share scoped variables, so
+    /// any connections between the graph nodes they create must be made via the graph itself.
+    /// For a file with a single language, this behaves like [`execute`][Self::execute].
+    pub fn execute_with_languages<'tree>(
+        &self,
+        tree: &'tree Tree,
+        source: &'tree str,
+        language_trees: &HashMap<&str, (&'tree Tree, &'tree str)>,
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Graph<'tree>, ExecutionError> {
+        let mut graph = Graph::new();
+        self.execute_into(&mut graph, tree, source, config, cancellation_flag)?;
+        for (language, file) in &self.languages {
+            if let Some((tree, source)) = language_trees.get(language.as_str()) {
+                file.execute_into(&mut graph, tree, source, config, cancellation_flag)?;
+            }
+        }
+        Ok(graph)
+    }
+
+    /// Executes only some of the stanzas of this graph DSL file against a source file, which is
+    /// useful to get quick feedback while working on a single stanza.  `stanza` selects either
+    /// the stanza with a particular index, or all stanzas with a particular name (given by a
+    /// `stanza NAME` label in the file).  Fails with [`ExecutionError::UndefinedStanza`] if no
+    /// stanza is selected.  Otherwise, this behaves like [`execute`][Self::execute].
+    pub fn execute_stanza<'s, 'tree>(
+        &self,
+        stanza: impl Into<StanzaSelector<'s>>,
+        tree: &'tree Tree,
+        source: &'tree str,
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Graph<'tree>, ExecutionError> {
+        let selector = stanza.into();
+        let selected_stanzas = self
+            .stanzas
+            .iter()
+            .enumerate()
+            .filter(|(index, stanza)| match selector {
+                StanzaSelector::Index(selected) => *index == selected,
+                StanzaSelector::Name(name) => {
+                    stanza.name.as_ref().map(|n| n.as_str()) == Some(name)
+                }
+            })
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        if selected_stanzas.is_empty() {
+            return Err(ExecutionError::UndefinedStanza(format!("{}", selector)));
+        }
+        let config = config.with_selected_stanzas(selected_stanzas);
+        self.execute(tree, source, &config, cancellation_flag)
+    }
+
+    /// Executes this graph DSL file against a source file, like [`execute`][Self::execute], but
+    /// executes the stanzas of the file in parallel, on up to as many threads as
+    /// [`std::thread::available_parallelism`][] allows.
+    ///
+    /// Each stanza is executed into a graph of its own, and the graphs are then merged in stanza
+    /// order (see [`Graph::merge`][]).  The result is therefore deterministic, however the threads
+    /// are scheduled: in strict mode, it is identical to the result of [`execute`][Self::execute],
+    /// since strict execution creates graph nodes stanza by stanza as well.  In lazy mode, the
+    /// same nodes, edges, and attributes are created, but they might be numbered differently than
+    /// by `execute`.  The output of `print` statements and any
+    /// [unmatched stanzas][ExecutionConfig::report_unmatched_stanzas_to] are passed on in stanza
+    /// order, too, once all stanzas have been executed, and errors are reported as if the stanzas had been executed in
+    /// order, except that any graph nodes that they mention are numbered as in the graph of the
+    /// failing stanza on its own.
+    ///
+    /// Stanzas can only be executed independently if they don't share any state, so this falls
+    /// back to executing the file sequentially if it uses scoped variables, or calls a function
+    /// that is not [stanza-local][crate::functions::Function::is_stanza_local].  See
+    /// [`is_stanza_local`][Self::is_stanza_local].
+    pub fn execute_parallel<'tree>(
+        &self,
+        tree: &'tree Tree,
+        source: &'tree str,
+        config: &ExecutionConfig,
+        cancellation_flag: &(dyn CancellationFlag + Sync),
+    ) -> Result<Graph<'tree>, ExecutionError> {
+        let stanzas = (0..self.stanzas.len())
+            .filter(|index| config.is_stanza_selected(*index))
+            .collect::<Vec<_>>();
+        if stanzas.len() <= 1 || !self.is_stanza_local(config.functions) {
+            return self.execute(tree, source, config, cancellation_flag);
+        }
+        let threads = std::thread::available_parallelism()
+            .map(|threads| threads.get())
+            .unwrap_or(1)
+            .min(stanzas.len());
+
+        let next_stanza = AtomicUsize::new(0);
+        let mut results = std::thread::scope(|scope| {
+            let workers = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut results = Vec::new();
+                        loop {
+                            let next = next_stanza.fetch_add(1, Ordering::Relaxed);
+                            let stanza_index = match stanzas.get(next) {
+                                Some(stanza_index) => *stanza_index,
+                                None => return results,
+                            };
+                            let output = Mutex::new(Vec::new());
+                            let print_to =
+                                |line: &str| output.lock().unwrap().push(line.to_string());
+                            let unmatched = Mutex::new(Vec::new());
+                            let unmatched_stanzas_to =
+                                |location: Location| unmatched.lock().unwrap().push(location);
+                            let mut config = config
+                                .with_selected_stanzas(vec![stanza_index])
+                                .print_to(&print_to);
+                            if config.unmatched_stanzas_to.is_some() {
+                                config = config.report_unmatched_stanzas_to(&unmatched_stanzas_to);
+                            }
+                            let result = self
+                                .execute(tree, source, &config, cancellation_flag)
+                                .map(Graph::detach);
+                            results.push((
+                                next,
+                                result,
+                                output.into_inner().unwrap(),
+                                unmatched.into_inner().unwrap(),
+                            ));
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        results.sort_by_key(|(next, _, _, _)| *next);
+
+        // Combine the results as if the stanzas had been executed one after the other
+        let mut graphs = Vec::with_capacity(results.len());
+        let mut errors = Vec::new();
+        let mut unmatched_stanzas = Vec::new();
+        for (_, result, output, unmatched) in results {
+            for line in output {
+                config.print(&line);
+            }
+            match result {
+                Ok(graph) => graphs.push(graph),
+                Err(ExecutionError::Multiple(stanza_errors)) => errors.extend(stanza_errors),
+                Err(err) => return Err(err),
+            }
+            unmatched_stanzas.extend(unmatched);
+        }
+        if !errors.is_empty() {
+            return Err(ExecutionError::Multiple(errors));
+        }
+        if let Some(unmatched_stanzas_to) = config.unmatched_stanzas_to {
+            unmatched_stanzas.into_iter().for_each(unmatched_stanzas_to);
+        }
+        Ok(Graph::attach_all(tree, graphs))
+    }
+
+    /// Executes this graph DSL file against a source file, reusing an existing query cursor.
+    /// This behaves exactly like [`execute`][Self::execute], but lets you amortize the cost of
+    /// allocating a query cursor when executing the same file against many syntax trees.  Any
+    /// settings of the cursor, such as its byte range, apply to the execution.
+    pub fn execute_reusing<'tree>(
+        &self,
+        cursor: &mut QueryCursor,
+        tree: &'tree Tree,
+        source: &'tree str,
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Graph<'tree>, ExecutionError> {
+        let mut graph = Graph::new();
+        self.execute_into_reusing(cursor, &mut graph, tree, source, config, cancellation_flag)?;
+        Ok(graph)
+    }
+
     /// Executes this graph DSL file against a source file, saving the results into an existing
     /// `Graph` instance.  You must provide the parsed syntax tree (`tree`) as well as the source
     /// text that it was parsed from (`source`).  You also provide the set of functions and global
     /// variables that are available during execution. This variant is useful when you need to
     /// “pre-seed” the graph with some predefined nodes and/or edges before executing the DSL file.
+    ///
+    /// It is also how you build a single graph from source code in several languages using
+    /// separate graph DSL files: execute one file per language, against the syntax tree of that
+    /// language, into the same `Graph`.  The executions do not share variables, so any
+    /// connections between the graph nodes they create must be made via the graph itself.  To
+    /// keep the stanzas for all languages in one file, see
+    /// [`execute_with_languages`][Self::execute_with_languages].
     pub fn execute_into<'a, 'tree>(
         &self,
         graph: &mut Graph<'tree>,
@@ -57,10 +265,210 @@ impl File {
         config: &ExecutionConfig,
         cancellation_flag: &dyn CancellationFlag,
     ) -> Result<(), ExecutionError> {
-        if config.lazy {
-            self.execute_lazy_into(graph, tree, source, config, cancellation_flag)
+        let mut cursor = QueryCursor::new();
+        self.execute_into_reusing(&mut cursor, graph, tree, source, config, cancellation_flag)
+    }
+
+    /// Executes this graph DSL file against a source file, saving the results into an existing
+    /// `Graph` instance, and reusing an existing query cursor.  See
+    /// [`execute_into`][Self::execute_into] and [`execute_reusing`][Self::execute_reusing].
+    pub fn execute_into_reusing<'tree>(
+        &self,
+        cursor: &mut QueryCursor,
+        graph: &mut Graph<'tree>,
+        tree: &'tree Tree,
+        source: &'tree str,
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), ExecutionError> {
+        self.execute_into_reporting(
+            cursor,
+            graph,
+            tree.root_node(),
+            source,
+            config,
+            cancellation_flag,
+        )?;
+        Ok(())
+    }
+
+    /// Executes this graph DSL file against an injected syntax tree, which was parsed from part
+    /// of a parent source file, such as code embedded in a string or a comment.  The results are
+    /// saved into an existing `Graph` instance, typically one that also contains the graph of the
+    /// parent source file.
+    ///
+    /// The injected tree must be parsed from the whole text of the parent source file (`source`),
+    /// restricting the parser to the injected part with [`Parser::set_included_ranges`][].  The
+    /// positions and source text of its syntax nodes are then relative to the parent source file.
+    /// Only syntax nodes within the tree's included ranges are matched.
+    ///
+    /// [`Parser::set_included_ranges`]: tree_sitter::Parser::set_included_ranges
+    pub fn execute_injected_into<'tree>(
+        &self,
+        graph: &mut Graph<'tree>,
+        tree: &'tree Tree,
+        source: &'tree str,
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), ExecutionError> {
+        let mut cursor = QueryCursor::new();
+        let ranges = tree.included_ranges();
+        if let (Some(first), Some(last)) = (ranges.first(), ranges.last()) {
+            cursor.set_byte_range(first.start_byte..last.end_byte.min(source.len()));
+        }
+        self.execute_into_reusing(&mut cursor, graph, tree, source, config, cancellation_flag)
+    }
+
+    /// Executes this graph DSL file against a single subtree of a source file, such as the syntax
+    /// node of one function, instead of against the whole syntax tree.  Queries are matched with
+    /// `node` as their root, so only `node` and its descendants are matched; in particular,
+    /// stanzas whose queries match ancestors of `node` do not match at all.  This is useful to
+    /// re-analyze part of a source file after it has been edited.  `source` is the text of the
+    /// whole source file.
+    pub fn execute_at<'tree>(
+        &self,
+        node: Node<'tree>,
+        source: &'tree str,
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Graph<'tree>, ExecutionError> {
+        let mut graph = Graph::new();
+        let mut cursor = QueryCursor::new();
+        self.execute_into_reporting(
+            &mut cursor,
+            &mut graph,
+            node,
+            source,
+            config,
+            cancellation_flag,
+        )?;
+        Ok(graph)
+    }
+
+    /// Executes this graph DSL file like [`execute_into_reusing`][Self::execute_into_reusing],
+    /// and returns the locations of the stanzas that did not match anything, if `config` asks for
+    /// them to be reported.  Otherwise, the result is always empty.
+    fn execute_into_reporting<'tree>(
+        &self,
+        cursor: &mut QueryCursor,
+        graph: &mut Graph<'tree>,
+        root: Node<'tree>,
+        source: &'tree str,
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Vec<Location>, ExecutionError> {
+        let stanza_matches = if config.lazy {
+            self.execute_lazy_into(cursor, graph, root, source, config, cancellation_flag)?
         } else {
-            self.execute_strict_into(graph, tree, source, config, cancellation_flag)
+            self.execute_strict_into(cursor, graph, root, source, config, cancellation_flag)?
+        };
+        if !config.report_unmatched_stanzas {
+            return Ok(Vec::new());
+        }
+        let unmatched_stanzas = self
+            .stanzas
+            .iter()
+            .zip(stanza_matches)
+            .enumerate()
+            .filter(|(index, (_, matches))| *matches == 0 && config.is_stanza_selected(*index))
+            .map(|(_, (stanza, _))| stanza.range.start)
+            .collect::<Vec<_>>();
+        for location in &unmatched_stanzas {
+            warn!("stanza at {} did not match anything", location);
+            if let Some(unmatched_stanzas_to) = config.unmatched_stanzas_to {
+                unmatched_stanzas_to(*location);
+            }
+        }
+        Ok(unmatched_stanzas)
+    }
+
+    /// Executes this graph DSL file against a source file, one query match at a time.  Each match
+    /// is executed into a new, empty `Graph`, which is passed to `visit` as soon as the match has
+    /// been executed, so that it can be processed and discarded without building the graph of the
+    /// whole source file.  Matches are visited stanza by stanza, in the order of the stanzas in
+    /// the file.
+    ///
+    /// Because the graph of a match is discarded after visiting it, matches are executed in
+    /// isolation: scoped variables are not shared between matches.  This always uses the strict
+    /// executor, since the lazy executor needs all matches before it can evaluate anything, so
+    /// the `lazy` setting of `config` is ignored.  Any error returned by `visit` stops execution.
+    pub fn execute_per_match<'tree, F>(
+        &self,
+        tree: &'tree Tree,
+        source: &'tree str,
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+        visit: F,
+    ) -> Result<(), ExecutionError>
+    where
+        F: FnMut(Graph<'tree>) -> Result<(), ExecutionError>,
+    {
+        let mut cursor = QueryCursor::new();
+        self.execute_strict_per_match(
+            &mut cursor,
+            tree.root_node(),
+            source,
+            config,
+            cancellation_flag,
+            visit,
+        )
+    }
+
+    /// Builds the intermediate graph that lazy execution evaluates, and returns a human-readable
+    /// dump of its statements, one per line, without evaluating them.  This is useful for
+    /// debugging the order in which lazy statements are evaluated.  The `lazy` setting of
+    /// `config` is ignored.
+    pub fn build_lazy_graph<'tree>(
+        &self,
+        tree: &'tree Tree,
+        source: &'tree str,
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<String, ExecutionError> {
+        let mut cursor = QueryCursor::new();
+        self.dump_lazy_graph(
+            &mut cursor,
+            tree.root_node(),
+            source,
+            config,
+            cancellation_flag,
+        )
+    }
+
+    /// Reports what executing this graph DSL file against a source file would do to a pre-seeded
+    /// `graph`, without modifying it.  The file is executed lazily against a copy of `graph`, and
+    /// the graph node, edge, and attribute operations performed on the copy are returned in the
+    /// order they happened.  Since lazy execution creates all graph nodes before evaluating any
+    /// other statement, the [`CreateGraphNode`][GraphEvent::CreateGraphNode] events come first.
+    /// The `lazy` setting of `config` is ignored.
+    pub fn dry_run<'tree>(
+        &self,
+        graph: &Graph<'tree>,
+        tree: &'tree Tree,
+        source: &'tree str,
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Vec<GraphEvent>, ExecutionError> {
+        let mut cursor = QueryCursor::new();
+        self.dry_run_lazy(
+            &mut cursor,
+            graph,
+            tree.root_node(),
+            source,
+            config,
+            cancellation_flag,
+        )
+    }
+
+    /// Creates a reusable handle for executing this graph DSL file against many source files,
+    /// using the functions and settings of `config`.  The global variables of `config` are
+    /// ignored; each run of the handle is given its own.
+    pub fn execution<'a, 'g>(&'a self, config: &ExecutionConfig<'a, 'g>) -> Execution<'a, 'g> {
+        Execution {
+            file: self,
+            config: config.clone(),
+            unmatched_stanzas: Vec::new(),
+            cursor: QueryCursor::new(),
         }
     }
 
@@ -97,7 +505,7 @@ impl File {
             }
         }
 
-        Ok(())
+        globals.check_declared_types()
     }
 
     pub fn try_visit_matches<'tree, E, F>(
@@ -110,9 +518,10 @@ impl File {
     where
         F: FnMut(Match<'_, 'tree>) -> Result<(), E>,
     {
+        let mut cursor = QueryCursor::new();
         if lazy {
             let file_query = self.query.as_ref().expect("missing file query");
-            self.try_visit_matches_lazy(tree, source, |stanza, mat| {
+            self.try_visit_matches_lazy(&mut cursor, tree.root_node(), source, |stanza, mat| {
                 let named_captures = stanza
                     .query
                     .capture_names()
@@ -135,7 +544,7 @@ impl File {
                 })
             })
         } else {
-            self.try_visit_matches_strict(tree, source, |stanza, mat| {
+            self.try_visit_matches_strict(&mut cursor, tree.root_node(), source, |stanza, mat| {
                 let named_captures = stanza
                     .query
                     .capture_names()
@@ -171,7 +580,8 @@ impl Stanza {
     where
         F: FnMut(Match<'_, 'tree>) -> Result<(), E>,
     {
-        self.try_visit_matches_strict(tree, source, |mat| {
+        let mut cursor = QueryCursor::new();
+        self.try_visit_matches_strict(&mut cursor, tree.root_node(), source, |mat| {
             let named_captures = self
                 .query
                 .capture_names()
@@ -249,16 +659,60 @@ impl<'a, 'tree> Match<'a, 'tree> {
     }
 }
 
+/// Selects the stanzas to execute with [`File::execute_stanza`][].  A stanza can be selected by
+/// its index in the file, or by the name it was given with a `stanza NAME` label.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StanzaSelector<'a> {
+    Index(usize),
+    Name(&'a str),
+}
+
+impl From<usize> for StanzaSelector<'_> {
+    fn from(index: usize) -> Self {
+        Self::Index(index)
+    }
+}
+
+impl<'a> From<&'a str> for StanzaSelector<'a> {
+    fn from(name: &'a str) -> Self {
+        Self::Name(name)
+    }
+}
+
+impl std::fmt::Display for StanzaSelector<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "with index {}", index),
+            Self::Name(name) => write!(f, "named {}", name),
+        }
+    }
+}
+
 /// Configuration for the execution of a File
+#[derive(Clone)]
 pub struct ExecutionConfig<'a, 'g> {
     pub(crate) functions: &'a Functions,
     pub(crate) globals: &'a Globals<'g>,
     pub(crate) lazy: bool,
     pub(crate) location_attr: Option<Identifier>,
     pub(crate) variable_name_attr: Option<Identifier>,
+    pub(crate) record_locations: bool,
+    pub(crate) match_limit: Option<usize>,
+    pub(crate) report_unmatched_stanzas: bool,
+    pub(crate) overwrite_attributes: bool,
+    pub(crate) collect_errors: bool,
+    pub(crate) max_expression_depth: usize,
+    pub(crate) reject_self_edges: bool,
+    pub(crate) skip_matches_with_errors: bool,
+    pub(crate) selected_stanzas: Option<Vec<usize>>,
+    pub(crate) print_to: Option<&'a (dyn Fn(&str) + Sync)>,
+    pub(crate) unmatched_stanzas_to: Option<&'a (dyn Fn(Location) + Sync)>,
 }
 
 impl<'a, 'g> ExecutionConfig<'a, 'g> {
+    /// Creates a new execution configuration.  The function registry and global variables are
+    /// only borrowed immutably, so a single registry can be shared by any number of
+    /// configurations and executions.
     pub fn new(functions: &'a Functions, globals: &'a Globals<'g>) -> Self {
         Self {
             functions,
@@ -266,6 +720,17 @@ impl<'a, 'g> ExecutionConfig<'a, 'g> {
             lazy: false,
             location_attr: None,
             variable_name_attr: None,
+            record_locations: false,
+            match_limit: None,
+            report_unmatched_stanzas: false,
+            overwrite_attributes: false,
+            collect_errors: false,
+            max_expression_depth: MAX_EXPRESSION_DEPTH,
+            reject_self_edges: false,
+            skip_matches_with_errors: false,
+            selected_stanzas: None,
+            print_to: None,
+            unmatched_stanzas_to: None,
         }
     }
 
@@ -280,6 +745,17 @@ impl<'a, 'g> ExecutionConfig<'a, 'g> {
             lazy: self.lazy,
             location_attr: location_attr.into(),
             variable_name_attr: variable_name_attr.into(),
+            record_locations: self.record_locations,
+            match_limit: self.match_limit,
+            report_unmatched_stanzas: self.report_unmatched_stanzas,
+            overwrite_attributes: self.overwrite_attributes,
+            collect_errors: self.collect_errors,
+            max_expression_depth: self.max_expression_depth,
+            reject_self_edges: self.reject_self_edges,
+            skip_matches_with_errors: self.skip_matches_with_errors,
+            selected_stanzas: self.selected_stanzas,
+            print_to: self.print_to,
+            unmatched_stanzas_to: self.unmatched_stanzas_to,
         }
     }
 
@@ -290,8 +766,400 @@ impl<'a, 'g> ExecutionConfig<'a, 'g> {
             lazy,
             location_attr: self.location_attr,
             variable_name_attr: self.variable_name_attr,
+            record_locations: self.record_locations,
+            match_limit: self.match_limit,
+            report_unmatched_stanzas: self.report_unmatched_stanzas,
+            overwrite_attributes: self.overwrite_attributes,
+            collect_errors: self.collect_errors,
+            max_expression_depth: self.max_expression_depth,
+            reject_self_edges: self.reject_self_edges,
+            skip_matches_with_errors: self.skip_matches_with_errors,
+            selected_stanzas: self.selected_stanzas,
+            print_to: self.print_to,
+            unmatched_stanzas_to: self.unmatched_stanzas_to,
+        }
+    }
+
+    /// Records the location of the DSL statement that created each graph node and edge, which
+    /// is then available via [`GraphNode::created_at`][crate::graph::GraphNode::created_at] and
+    /// [`Edge::created_at`][crate::graph::Edge::created_at].
+    pub fn record_locations(self, record_locations: bool) -> Self {
+        Self {
+            functions: self.functions,
+            globals: self.globals,
+            lazy: self.lazy,
+            location_attr: self.location_attr,
+            variable_name_attr: self.variable_name_attr,
+            record_locations,
+            match_limit: self.match_limit,
+            report_unmatched_stanzas: self.report_unmatched_stanzas,
+            overwrite_attributes: self.overwrite_attributes,
+            collect_errors: self.collect_errors,
+            max_expression_depth: self.max_expression_depth,
+            reject_self_edges: self.reject_self_edges,
+            skip_matches_with_errors: self.skip_matches_with_errors,
+            selected_stanzas: self.selected_stanzas,
+            print_to: self.print_to,
+            unmatched_stanzas_to: self.unmatched_stanzas_to,
         }
     }
+
+    /// Limits the number of matches that each stanza can process.  Execution fails with
+    /// [`ExecutionError::ExceededMatchLimit`] if any stanza matches more often.  By default, the
+    /// number of matches is unlimited.
+    pub fn match_limit(self, match_limit: usize) -> Self {
+        Self {
+            functions: self.functions,
+            globals: self.globals,
+            lazy: self.lazy,
+            location_attr: self.location_attr,
+            variable_name_attr: self.variable_name_attr,
+            record_locations: self.record_locations,
+            match_limit: Some(match_limit),
+            report_unmatched_stanzas: self.report_unmatched_stanzas,
+            overwrite_attributes: self.overwrite_attributes,
+            collect_errors: self.collect_errors,
+            max_expression_depth: self.max_expression_depth,
+            reject_self_edges: self.reject_self_edges,
+            skip_matches_with_errors: self.skip_matches_with_errors,
+            selected_stanzas: self.selected_stanzas,
+            print_to: self.print_to,
+            unmatched_stanzas_to: self.unmatched_stanzas_to,
+        }
+    }
+
+    /// Reports the stanzas that do not match anything during an execution, which usually means
+    /// that their query refers to syntax that the grammar no longer produces.  Each such stanza
+    /// is logged as a warning, and an [`Execution`] handle makes them available via
+    /// [`Execution::unmatched_stanzas`].  Use
+    /// [`report_unmatched_stanzas_to`][Self::report_unmatched_stanzas_to] to receive them from
+    /// the other ways of executing a file.
+    pub fn report_unmatched_stanzas(self, report_unmatched_stanzas: bool) -> Self {
+        Self {
+            functions: self.functions,
+            globals: self.globals,
+            lazy: self.lazy,
+            location_attr: self.location_attr,
+            variable_name_attr: self.variable_name_attr,
+            record_locations: self.record_locations,
+            match_limit: self.match_limit,
+            report_unmatched_stanzas,
+            overwrite_attributes: self.overwrite_attributes,
+            collect_errors: self.collect_errors,
+            max_expression_depth: self.max_expression_depth,
+            reject_self_edges: self.reject_self_edges,
+            skip_matches_with_errors: self.skip_matches_with_errors,
+            selected_stanzas: self.selected_stanzas,
+            print_to: self.print_to,
+            unmatched_stanzas_to: self.unmatched_stanzas_to,
+        }
+    }
+
+    /// Reports the stanzas that do not match anything during an execution, like
+    /// [`report_unmatched_stanzas`][Self::report_unmatched_stanzas], and also passes the location
+    /// of each such stanza to `unmatched_stanzas_to`, in the order they appear in the file.  This
+    /// makes them available when executing with [`File::execute`][] or any of its variants.
+    pub fn report_unmatched_stanzas_to(
+        self,
+        unmatched_stanzas_to: &'a (dyn Fn(Location) + Sync),
+    ) -> Self {
+        Self {
+            functions: self.functions,
+            globals: self.globals,
+            lazy: self.lazy,
+            location_attr: self.location_attr,
+            variable_name_attr: self.variable_name_attr,
+            record_locations: self.record_locations,
+            match_limit: self.match_limit,
+            report_unmatched_stanzas: true,
+            overwrite_attributes: self.overwrite_attributes,
+            collect_errors: self.collect_errors,
+            max_expression_depth: self.max_expression_depth,
+            reject_self_edges: self.reject_self_edges,
+            skip_matches_with_errors: self.skip_matches_with_errors,
+            selected_stanzas: self.selected_stanzas,
+            print_to: self.print_to,
+            unmatched_stanzas_to: Some(unmatched_stanzas_to),
+        }
+    }
+
+    /// Lets `attr` statements overwrite existing attributes, like `set-attr` statements do, so
+    /// that the last value written wins.  By default, adding an attribute that already exists
+    /// fails with [`ExecutionError::DuplicateAttribute`].
+    pub fn overwrite_attributes(self, overwrite_attributes: bool) -> Self {
+        Self {
+            functions: self.functions,
+            globals: self.globals,
+            lazy: self.lazy,
+            location_attr: self.location_attr,
+            variable_name_attr: self.variable_name_attr,
+            record_locations: self.record_locations,
+            match_limit: self.match_limit,
+            report_unmatched_stanzas: self.report_unmatched_stanzas,
+            overwrite_attributes,
+            collect_errors: self.collect_errors,
+            max_expression_depth: self.max_expression_depth,
+            reject_self_edges: self.reject_self_edges,
+            skip_matches_with_errors: self.skip_matches_with_errors,
+            selected_stanzas: self.selected_stanzas,
+            print_to: self.print_to,
+            unmatched_stanzas_to: self.unmatched_stanzas_to,
+        }
+    }
+
+    /// Keeps executing after a statement fails, instead of stopping at the first error.  The
+    /// failing statement is skipped, and execution fails at the end with an
+    /// [`ExecutionError::Multiple`] error that contains the errors of all failed statements, in
+    /// the order they occurred.  Note that skipping a statement can cause later statements that
+    /// depend on it to fail as well.  This only affects strict execution; lazy execution always
+    /// stops at the first error.
+    pub fn collect_errors(self, collect_errors: bool) -> Self {
+        Self {
+            functions: self.functions,
+            globals: self.globals,
+            lazy: self.lazy,
+            location_attr: self.location_attr,
+            variable_name_attr: self.variable_name_attr,
+            record_locations: self.record_locations,
+            match_limit: self.match_limit,
+            report_unmatched_stanzas: self.report_unmatched_stanzas,
+            overwrite_attributes: self.overwrite_attributes,
+            collect_errors,
+            max_expression_depth: self.max_expression_depth,
+            reject_self_edges: self.reject_self_edges,
+            skip_matches_with_errors: self.skip_matches_with_errors,
+            selected_stanzas: self.selected_stanzas,
+            print_to: self.print_to,
+            unmatched_stanzas_to: self.unmatched_stanzas_to,
+        }
+    }
+
+    /// Limits how deeply expressions can be nested inside each other, such as function calls
+    /// whose parameters are function calls themselves.  Execution fails with
+    /// [`ExecutionError::ExceededRecursionDepth`] when evaluating an expression that is nested
+    /// more deeply, instead of overflowing the stack.
+    ///
+    /// The default limit is 64, the same as the limit that the parser enforces, which rejects
+    /// more deeply nested expressions with
+    /// [`ParseError::ExpressionTooDeep`][crate::ParseError::ExpressionTooDeep].
+    /// Files that were parsed therefore never exceed the default limit; lowering it restricts
+    /// them further, and raising it only affects files whose AST was built in code.  Chains of
+    /// values that refer to each other, such as scoped variables defined in terms of other
+    /// scoped variables, are not nested expressions, and are not limited by this setting.
+    pub fn max_expression_depth(self, max_expression_depth: usize) -> Self {
+        Self {
+            functions: self.functions,
+            globals: self.globals,
+            lazy: self.lazy,
+            location_attr: self.location_attr,
+            variable_name_attr: self.variable_name_attr,
+            record_locations: self.record_locations,
+            match_limit: self.match_limit,
+            report_unmatched_stanzas: self.report_unmatched_stanzas,
+            overwrite_attributes: self.overwrite_attributes,
+            collect_errors: self.collect_errors,
+            max_expression_depth,
+            reject_self_edges: self.reject_self_edges,
+            skip_matches_with_errors: self.skip_matches_with_errors,
+            selected_stanzas: self.selected_stanzas,
+            print_to: self.print_to,
+            unmatched_stanzas_to: self.unmatched_stanzas_to,
+        }
+    }
+
+    /// Rejects edges from a graph node to itself, for graph models that do not allow them.
+    /// Creating such an edge fails with [`ExecutionError::SelfEdge`].  By default, self edges
+    /// are allowed.
+    pub fn reject_self_edges(self, reject_self_edges: bool) -> Self {
+        Self {
+            functions: self.functions,
+            globals: self.globals,
+            lazy: self.lazy,
+            location_attr: self.location_attr,
+            variable_name_attr: self.variable_name_attr,
+            record_locations: self.record_locations,
+            match_limit: self.match_limit,
+            report_unmatched_stanzas: self.report_unmatched_stanzas,
+            overwrite_attributes: self.overwrite_attributes,
+            collect_errors: self.collect_errors,
+            max_expression_depth: self.max_expression_depth,
+            reject_self_edges,
+            skip_matches_with_errors: self.skip_matches_with_errors,
+            selected_stanzas: self.selected_stanzas,
+            print_to: self.print_to,
+            unmatched_stanzas_to: self.unmatched_stanzas_to,
+        }
+    }
+
+    /// Skips query matches that contain a syntax error, so that the parts of a source file that
+    /// could be parsed still produce a graph, while the broken parts are ignored.  A match is
+    /// skipped if any of its matched syntax nodes is, or contains, an `ERROR` or `MISSING` node.
+    /// By default, all matches are executed.
+    pub fn skip_matches_with_errors(self, skip_matches_with_errors: bool) -> Self {
+        Self {
+            functions: self.functions,
+            globals: self.globals,
+            lazy: self.lazy,
+            location_attr: self.location_attr,
+            variable_name_attr: self.variable_name_attr,
+            record_locations: self.record_locations,
+            match_limit: self.match_limit,
+            report_unmatched_stanzas: self.report_unmatched_stanzas,
+            overwrite_attributes: self.overwrite_attributes,
+            collect_errors: self.collect_errors,
+            max_expression_depth: self.max_expression_depth,
+            reject_self_edges: self.reject_self_edges,
+            skip_matches_with_errors,
+            selected_stanzas: self.selected_stanzas,
+            print_to: self.print_to,
+            unmatched_stanzas_to: self.unmatched_stanzas_to,
+        }
+    }
+
+    /// Sends the output of `print` statements to `print_to`, one line at a time and without the
+    /// trailing newline, instead of writing it to standard error.  This lets you collect the
+    /// output, for instance to show it elsewhere or to test it.
+    pub fn print_to(self, print_to: &'a (dyn Fn(&str) + Sync)) -> Self {
+        Self {
+            functions: self.functions,
+            globals: self.globals,
+            lazy: self.lazy,
+            location_attr: self.location_attr,
+            variable_name_attr: self.variable_name_attr,
+            record_locations: self.record_locations,
+            match_limit: self.match_limit,
+            report_unmatched_stanzas: self.report_unmatched_stanzas,
+            overwrite_attributes: self.overwrite_attributes,
+            collect_errors: self.collect_errors,
+            max_expression_depth: self.max_expression_depth,
+            reject_self_edges: self.reject_self_edges,
+            skip_matches_with_errors: self.skip_matches_with_errors,
+            selected_stanzas: self.selected_stanzas,
+            print_to: Some(print_to),
+            unmatched_stanzas_to: self.unmatched_stanzas_to,
+        }
+    }
+
+    /// Outputs a line printed by a `print` statement.
+    pub(crate) fn print(&self, line: &str) {
+        print_line(self.print_to, line)
+    }
+
+    /// Returns whether a query match should be executed.
+    pub(crate) fn is_match_selected(&self, mat: &QueryMatch) -> bool {
+        !self.skip_matches_with_errors || !mat.captures.iter().any(|c| c.node.has_error())
+    }
+
+    /// Returns a copy of this configuration that only executes the stanzas with the given indices.
+    pub(crate) fn with_selected_stanzas(&self, selected_stanzas: Vec<usize>) -> Self {
+        Self {
+            functions: self.functions,
+            globals: self.globals,
+            lazy: self.lazy,
+            location_attr: self.location_attr.clone(),
+            variable_name_attr: self.variable_name_attr.clone(),
+            record_locations: self.record_locations,
+            match_limit: self.match_limit,
+            report_unmatched_stanzas: self.report_unmatched_stanzas,
+            overwrite_attributes: self.overwrite_attributes,
+            collect_errors: self.collect_errors,
+            max_expression_depth: self.max_expression_depth,
+            reject_self_edges: self.reject_self_edges,
+            skip_matches_with_errors: self.skip_matches_with_errors,
+            selected_stanzas: Some(selected_stanzas),
+            print_to: self.print_to,
+            unmatched_stanzas_to: self.unmatched_stanzas_to,
+        }
+    }
+
+    /// Returns whether the stanza with the given index should be executed.
+    pub(crate) fn is_stanza_selected(&self, stanza_index: usize) -> bool {
+        match &self.selected_stanzas {
+            Some(selected_stanzas) => selected_stanzas.contains(&stanza_index),
+            None => true,
+        }
+    }
+
+    /// Checks that an expression at the given nesting depth can be evaluated.
+    pub(crate) fn check_expression_depth(&self, depth: usize) -> Result<(), ExecutionError> {
+        if depth > self.max_expression_depth {
+            return Err(ExecutionError::ExceededRecursionDepth(format!(
+                "of {}",
+                self.max_expression_depth
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns a copy of this configuration that uses different global variables.
+    pub(crate) fn with_globals<'b, 'h>(&self, globals: &'b Globals<'h>) -> ExecutionConfig<'b, 'h>
+    where
+        'a: 'b,
+    {
+        ExecutionConfig {
+            functions: self.functions,
+            globals,
+            lazy: self.lazy,
+            location_attr: self.location_attr.clone(),
+            variable_name_attr: self.variable_name_attr.clone(),
+            record_locations: self.record_locations,
+            match_limit: self.match_limit,
+            report_unmatched_stanzas: self.report_unmatched_stanzas,
+            overwrite_attributes: self.overwrite_attributes,
+            collect_errors: self.collect_errors,
+            max_expression_depth: self.max_expression_depth,
+            reject_self_edges: self.reject_self_edges,
+            skip_matches_with_errors: self.skip_matches_with_errors,
+            selected_stanzas: self.selected_stanzas.clone(),
+            print_to: self.print_to,
+            unmatched_stanzas_to: self.unmatched_stanzas_to,
+        }
+    }
+}
+
+/// A reusable handle for executing a graph DSL file against many source files.  The handle
+/// keeps the functions and settings of the configuration it was created from, and owns the
+/// query cursor used to find matches, so that it is allocated only once.  Each
+/// [`run`][Self::run] starts from a clean state, and is given its own global variables.
+pub struct Execution<'a, 'g> {
+    file: &'a File,
+    config: ExecutionConfig<'a, 'g>,
+    unmatched_stanzas: Vec<Location>,
+    cursor: QueryCursor,
+}
+
+impl<'a, 'g> Execution<'a, 'g> {
+    /// Executes the graph DSL file against a source file, saving the results into an existing
+    /// `Graph` instance.  This behaves exactly like [`File::execute_into`][], using the
+    /// configuration this handle was created from, but with the given global variables.
+    pub fn run<'tree>(
+        &mut self,
+        tree: &'tree Tree,
+        source: &'tree str,
+        graph: &mut Graph<'tree>,
+        globals: &Globals,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<(), ExecutionError> {
+        let config = self.config.with_globals(globals);
+        self.unmatched_stanzas.clear();
+        self.unmatched_stanzas = self.file.execute_into_reporting(
+            &mut self.cursor,
+            graph,
+            tree.root_node(),
+            source,
+            &config,
+            cancellation_flag,
+        )?;
+        Ok(())
+    }
+
+    /// Returns the locations of the stanzas that did not match anything during the most recent
+    /// [`run`][Self::run], in the order they appear in the file.  This is always empty unless the
+    /// handle was created from a configuration with
+    /// [`report_unmatched_stanzas`][ExecutionConfig::report_unmatched_stanzas] enabled.
+    pub fn unmatched_stanzas(&self) -> &[Location] {
+        &self.unmatched_stanzas
+    }
 }
 
 /// Trait to signal that the execution is cancelled
@@ -311,6 +1179,8 @@ impl CancellationFlag for NoCancellation {
 pub struct CancellationError(pub &'static str);
 
 impl Value {
+    /// Creates a value from the nodes of a query capture, according to the capture's quantifier.
+    /// A capture that can never match any nodes, with the `Zero` quantifier, results in `#null`.
     pub fn from_nodes<'tree, NI: IntoIterator<Item = Node<'tree>>>(
         graph: &mut Graph<'tree>,
         nodes: NI,
@@ -318,15 +1188,17 @@ impl Value {
     ) -> Value {
         let mut nodes = nodes.into_iter();
         match quantifier {
-            CaptureQuantifier::Zero => unreachable!(),
+            CaptureQuantifier::Zero => Value::Null,
             CaptureQuantifier::One => {
                 let syntax_node = graph.add_syntax_node(nodes.next().expect("missing capture"));
                 syntax_node.into()
             }
             CaptureQuantifier::ZeroOrMore | CaptureQuantifier::OneOrMore => {
-                let syntax_nodes = nodes
-                    .map(|n| graph.add_syntax_node(n.clone()).into())
-                    .collect::<Vec<Value>>();
+                // The nodes of a capture are typically filtered from the captures of a match, so
+                // the upper bound of the size hint is a close estimate of the number of nodes.
+                let (lower, upper) = nodes.size_hint();
+                let mut syntax_nodes = Vec::with_capacity(upper.unwrap_or(lower));
+                syntax_nodes.extend(nodes.map(|n| Value::from(graph.add_syntax_node(n))));
                 syntax_nodes.into()
             }
             CaptureQuantifier::ZeroOrOne => match nodes.next() {
@@ -340,6 +1212,85 @@ impl Value {
     }
 }
 
+impl Stanza {
+    /// Checks that this stanza has not processed more than the configured number of matches.
+    pub(crate) fn check_match_limit(
+        &self,
+        config: &ExecutionConfig,
+        matches: usize,
+    ) -> Result<(), ExecutionError> {
+        match config.match_limit {
+            Some(limit) if matches > limit => Err(ExecutionError::ExceededMatchLimit(format!(
+                "of {} matches for stanza at {}",
+                limit, self.range.start
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Outputs a line printed by a `print` statement to `print_to`, or to standard error if it is not
+/// given.
+pub(crate) fn print_line(print_to: Option<&(dyn Fn(&str) + Sync)>, line: &str) {
+    match print_to {
+        Some(print_to) => print_to(line),
+        None => eprintln!("{}", line),
+    }
+}
+
+impl MatchFunction {
+    /// Evaluates this function for the match of the stanza with the given index, whose whole
+    /// query matched `full_match`.
+    pub(crate) fn evaluate<'tree>(
+        &self,
+        stanza_index: usize,
+        full_match: Node<'tree>,
+        graph: &mut Graph<'tree>,
+    ) -> Value {
+        match self {
+            Self::MatchIndex => Value::Integer(stanza_index as u32),
+            Self::FullMatch => graph.add_syntax_node(full_match).into(),
+            Self::CaptureName(capture) => capture.name.as_str().into(),
+            Self::Quantifier(capture) => match capture.quantifier {
+                CaptureQuantifier::Zero => "zero",
+                CaptureQuantifier::ZeroOrOne => "zero-or-one",
+                CaptureQuantifier::ZeroOrMore => "zero-or-more",
+                CaptureQuantifier::One => "one",
+                CaptureQuantifier::OneOrMore => "one-or-more",
+            }
+            .into(),
+        }
+    }
+}
+
+impl ComparisonOperator {
+    /// Compares two values using this operator.  `==` and `!=` behave like the `eq` function:
+    /// null can be compared with any value, and other values only with values of the same type.
+    /// The ordering operators can only compare integers with integers, and strings with strings.
+    pub(crate) fn compare(&self, left: &Value, right: &Value) -> Result<bool, ExecutionError> {
+        let is_equality = matches!(self, Self::Eq | Self::Ne);
+        let comparable = match (left, right) {
+            (Value::Integer(_), Value::Integer(_)) | (Value::String(_), Value::String(_)) => true,
+            (Value::Null, _) | (_, Value::Null) => is_equality,
+            _ => is_equality && std::mem::discriminant(left) == std::mem::discriminant(right),
+        };
+        if !comparable {
+            return Err(ExecutionError::CannotCompareValues(format!(
+                "{} {} {}",
+                left, self, right
+            )));
+        }
+        Ok(match self {
+            Self::Eq => left == right,
+            Self::Ne => left != right,
+            Self::Lt => left < right,
+            Self::Le => left <= right,
+            Self::Gt => left > right,
+            Self::Ge => left >= right,
+        })
+    }
+}
+
 impl CreateEdge {
     pub(crate) fn add_debug_attrs(
         &self,
@@ -11,6 +11,7 @@ use thiserror::Error;
 use crate::ast::Stanza;
 use crate::ast::Statement;
 use crate::execution::CancellationError;
+use crate::graph::ExpectedType;
 use crate::parse_error::Excerpt;
 use crate::Location;
 
@@ -23,6 +24,8 @@ pub enum ExecutionError {
     CannotAssignImmutableVariable(String),
     #[error("Cannot assign scoped variable {0}")]
     CannotAssignScopedVariable(String),
+    #[error("Cannot compare values {0}")]
+    CannotCompareValues(String),
     #[error("Cannot define mutable scoped variable {0}")]
     CannotDefineMutableScopedVariable(String),
     #[error("Duplicate attribute {0}")]
@@ -31,34 +34,56 @@ pub enum ExecutionError {
     DuplicateEdge(String),
     #[error("Duplicate variable {0}")]
     DuplicateVariable(String),
+    #[error("Exceeded match limit {0}")]
+    ExceededMatchLimit(String),
+    #[error("Exceeded maximum expression depth {0}")]
+    ExceededRecursionDepth(String),
+    #[error("Expected an attribute name and value pair {0}")]
+    ExpectedAttributePair(String),
+    #[error("Expected an edge {0}")]
+    ExpectedEdge(String),
     #[error("Expected a graph node reference {0}")]
     ExpectedGraphNode(String),
     #[error("Expected a list {0}")]
     ExpectedList(String),
+    #[error("Expected a set {0}")]
+    ExpectedSet(String),
     #[error("Expected a boolean {0}")]
     ExpectedBoolean(String),
+    #[error("Expected a local value {0}")]
+    ExpectedLocalValue(String),
     #[error("Expected an integer {0}")]
     ExpectedInteger(String),
     #[error("Expected a string {0}")]
     ExpectedString(String),
     #[error("Expected a syntax node {0}")]
     ExpectedSyntaxNode(String),
+    #[error("Global variable {0} expected to be {1}, but got {2}")]
+    GlobalTypeMismatch(String, ExpectedType, ExpectedType),
+    #[error("Integer overflow {0}")]
+    IntegerOverflow(String),
     #[error("Invalid parameters {0}")]
     InvalidParameters(String),
     #[error("Scoped variables can only be attached to syntax nodes {0}")]
     InvalidVariableScope(String),
+    #[error("List length mismatch {0}")]
+    ListLengthMismatch(String),
     #[error("Missing global variable {0}")]
     MissingGlobalVariable(String),
     #[error("Recursively defined scoped variable {0}")]
     RecursivelyDefinedScopedVariable(String),
     #[error("Recursively defined variable {0}")]
     RecursivelyDefinedVariable(String),
+    #[error("Self edge {0}")]
+    SelfEdge(String),
     #[error("Undefined capture {0}")]
     UndefinedCapture(String),
     #[error("Undefined function {0}")]
     UndefinedFunction(String),
     #[error("Undefined regex capture {0}")]
     UndefinedRegexCapture(String),
+    #[error("Undefined stanza {0}")]
+    UndefinedStanza(String),
     #[error("Undefined scoped variable {0}")]
     UndefinedScopedVariable(String),
     #[error("Empty regex capture {0}")]
@@ -73,6 +98,19 @@ pub enum ExecutionError {
     FunctionFailed(String, String),
     #[error("{0}. Caused by: {1}")]
     InContext(Context, Box<ExecutionError>),
+    #[error("{}", display_multiple(_0))]
+    Multiple(Vec<ExecutionError>),
+}
+
+fn display_multiple(errors: &[ExecutionError]) -> String {
+    match errors.first() {
+        Some(first) => format!(
+            "{} errors during execution, the first being: {}",
+            errors.len(),
+            first
+        ),
+        None => "No errors during execution".to_string(),
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -86,6 +124,9 @@ pub struct StatementContext {
     pub statement: String,
     pub statement_location: Location,
     pub stanza_location: Location,
+    /// The path of the included file that the stanza was read from, if any.  The statement and
+    /// stanza locations are relative to that file.
+    pub stanza_file: Option<String>,
     pub source_location: Location,
     pub node_kind: String,
 }
@@ -96,6 +137,7 @@ impl StatementContext {
             statement: format!("{}", stmt),
             statement_location: stmt.location(),
             stanza_location: stanza.range.start,
+            stanza_file: stanza.file.clone(),
             source_location: Location::from(source_node.range().start_point),
             node_kind: source_node.kind().to_string(),
         }
@@ -150,8 +192,16 @@ impl StatementContext {
         }
         write!(
             f,
-            " {} in stanza at {} matching ({}) node at {}",
-            self.statement, self.stanza_location, self.node_kind, self.source_location
+            " {} in stanza at {}",
+            self.statement, self.stanza_location
+        )?;
+        if let Some(path) = &self.stanza_file {
+            write!(f, " in included file \"{}\"", path)?;
+        }
+        write!(
+            f,
+            " matching ({}) node at {}",
+            self.node_kind, self.source_location
         )?;
         Ok(())
     }
@@ -241,6 +291,12 @@ impl DisplayExecutionErrorPretty<'_> {
                 self.fmt_entry(f, index + 1, cause)?;
                 Ok(())
             }
+            ExecutionError::Multiple(errors) => {
+                for error in errors {
+                    self.fmt_entry(f, index, error)?;
+                }
+                Ok(())
+            }
             other => writeln!(f, "{:>5}: {}", index, other),
         }
     }
@@ -266,29 +322,41 @@ impl StatementContext {
         } else {
             writeln!(f, "     > and executing statement {}", self.statement)?;
         }
-        write!(
-            f,
-            "{}",
-            Excerpt::from_source(
-                tsg_path,
-                tsg,
-                self.statement_location.row,
-                self.statement_location.to_column_range(),
-                7
-            )
-        )?;
-        writeln!(f, "{}in stanza", " ".repeat(7))?;
-        write!(
-            f,
-            "{}",
-            Excerpt::from_source(
-                tsg_path,
-                tsg,
-                self.stanza_location.row,
-                self.stanza_location.to_column_range(),
-                7
-            )
-        )?;
+        if let Some(path) = &self.stanza_file {
+            // The source of the included file is not available to show excerpts
+            writeln!(
+                f,
+                "{}at {} in stanza at {} in included file \"{}\"",
+                " ".repeat(7),
+                self.statement_location,
+                self.stanza_location,
+                path
+            )?;
+        } else {
+            write!(
+                f,
+                "{}",
+                Excerpt::from_source(
+                    tsg_path,
+                    tsg,
+                    self.statement_location.row,
+                    self.statement_location.to_column_range(),
+                    7
+                )
+            )?;
+            writeln!(f, "{}in stanza", " ".repeat(7))?;
+            write!(
+                f,
+                "{}",
+                Excerpt::from_source(
+                    tsg_path,
+                    tsg,
+                    self.stanza_location.row,
+                    self.stanza_location.to_column_range(),
+                    7
+                )
+            )?;
+        }
         writeln!(f, "{}matching ({}) node", " ".repeat(7), self.node_kind)?;
         write!(
             f,
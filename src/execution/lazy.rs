@@ -13,9 +13,9 @@ use log::{debug, trace};
 
 use std::collections::HashMap;
 
+use tree_sitter::Node;
 use tree_sitter::QueryCursor;
 use tree_sitter::QueryMatch;
-use tree_sitter::Tree;
 
 use crate::ast;
 use crate::execution::error::ExecutionError;
@@ -26,6 +26,7 @@ use crate::functions::Functions;
 use crate::graph;
 use crate::graph::Attributes;
 use crate::graph::Graph;
+use crate::graph::GraphEvent;
 use crate::graph::Value;
 use crate::variables::Globals;
 use crate::variables::MutVariables;
@@ -39,42 +40,131 @@ use values::*;
 
 impl ast::File {
     /// Executes this graph DSL file against a source file, saving the results into an existing
-    /// `Graph` instance.  You must provide the parsed syntax tree (`tree`) as well as the source
-    /// text that it was parsed from (`source`).  You also provide the set of functions and global
-    /// variables that are available during execution. This variant is useful when you need to
-    /// “pre-seed” the graph with some predefined nodes and/or edges before executing the DSL file.
+    /// `Graph` instance.  You must provide the root of the parsed syntax tree, or of the subtree
+    /// to match against (`root`), as well as the source text that it was parsed from (`source`).
+    /// You also provide the set of functions and global variables that are available during
+    /// execution. This variant is useful when you need to “pre-seed” the graph with some
+    /// predefined nodes and/or edges before executing the DSL file.
     pub(super) fn execute_lazy_into<'a, 'tree>(
         &self,
+        cursor: &mut QueryCursor,
         graph: &mut Graph<'tree>,
-        tree: &'tree Tree,
+        root: Node<'tree>,
         source: &'tree str,
         config: &ExecutionConfig,
         cancellation_flag: &dyn CancellationFlag,
-    ) -> Result<(), ExecutionError> {
+    ) -> Result<Vec<usize>, ExecutionError> {
         let mut globals = Globals::nested(config.globals);
         self.check_globals(&mut globals)?;
-        let mut config = ExecutionConfig {
-            functions: config.functions,
-            globals: &globals,
-            lazy: config.lazy,
-            location_attr: config.location_attr.clone(),
-            variable_name_attr: config.variable_name_attr.clone(),
-        };
+        let config = config.with_globals(&globals);
+
+        let mut lazy_graph =
+            self.build_lazy_graph_into(cursor, graph, root, source, &config, cancellation_flag)?;
+        lazy_graph.evaluate_into(graph, source, &config, cancellation_flag, None)?;
+
+        Ok(lazy_graph.stanza_matches)
+    }
+
+    /// Executes this graph DSL file against a clone of `graph`, and returns the operations that
+    /// execution performed on the clone, leaving `graph` itself untouched.  All graph nodes are
+    /// created before any other statement is evaluated, so the node creation events come first.
+    pub(super) fn dry_run_lazy<'tree>(
+        &self,
+        cursor: &mut QueryCursor,
+        graph: &Graph<'tree>,
+        root: Node<'tree>,
+        source: &'tree str,
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<Vec<GraphEvent>, ExecutionError> {
+        let mut globals = Globals::nested(config.globals);
+        self.check_globals(&mut globals)?;
+        let config = config.with_globals(&globals);
+
+        let mut scratch = graph.clone();
+        let mut lazy_graph = self.build_lazy_graph_into(
+            cursor,
+            &mut scratch,
+            root,
+            source,
+            &config,
+            cancellation_flag,
+        )?;
+        let mut events = scratch
+            .nodes_added_since(graph)
+            .map(GraphEvent::CreateGraphNode)
+            .collect::<Vec<_>>();
+        lazy_graph.evaluate_into(
+            &mut scratch,
+            source,
+            &config,
+            cancellation_flag,
+            Some(&mut events),
+        )?;
+        Ok(events)
+    }
 
+    /// Executes the stanzas of this graph DSL file to build the lazy graph, without evaluating
+    /// it, and returns a dump of the resulting lazy statements, one per line.
+    pub(super) fn dump_lazy_graph<'tree>(
+        &self,
+        cursor: &mut QueryCursor,
+        root: Node<'tree>,
+        source: &'tree str,
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<String, ExecutionError> {
+        let mut globals = Globals::nested(config.globals);
+        self.check_globals(&mut globals)?;
+        let config = config.with_globals(&globals);
+
+        let mut graph = Graph::new();
+        let lazy_graph = self.build_lazy_graph_into(
+            cursor,
+            &mut graph,
+            root,
+            source,
+            &config,
+            cancellation_flag,
+        )?;
+        let mut dump = String::new();
+        for graph_stmt in &lazy_graph.statements {
+            dump += &format!("{}\n", graph_stmt);
+        }
+        Ok(dump)
+    }
+
+    /// Executes the stanzas of this graph DSL file to build the lazy graph.  Graph nodes are
+    /// created in `graph` immediately; all other statements are deferred until evaluation.
+    fn build_lazy_graph_into<'tree>(
+        &self,
+        cursor: &mut QueryCursor,
+        graph: &mut Graph<'tree>,
+        root: Node<'tree>,
+        source: &'tree str,
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+    ) -> Result<LazyGraph, ExecutionError> {
         let mut locals = VariableMap::new();
         let mut store = LazyStore::new();
         let mut scoped_store = LazyScopedVariables::new();
         let mut lazy_graph = Vec::new();
         let mut function_parameters = Vec::new();
         let mut prev_element_debug_info = HashMap::new();
+        let mut stanza_matches = vec![0; self.stanzas.len()];
 
-        self.try_visit_matches_lazy(tree, source, |stanza, mat| {
+        self.try_visit_matches_lazy(cursor, root, source, |stanza, mat| {
             cancellation_flag.check("processing matches")?;
+            if !config.is_stanza_selected(mat.pattern_index) || !config.is_match_selected(&mat) {
+                return Ok(());
+            }
+            stanza_matches[mat.pattern_index] += 1;
+            stanza.check_match_limit(config, stanza_matches[mat.pattern_index])?;
             stanza.execute_lazy(
                 source,
                 &mat,
                 graph,
-                &mut config,
+                config,
                 &mut locals,
                 &mut store,
                 &mut scoped_store,
@@ -86,39 +176,28 @@ impl ast::File {
             )
         })?;
 
-        let mut exec = EvaluationContext {
-            source,
-            graph,
-            functions: config.functions,
-            store: &store,
-            scoped_store: &scoped_store,
-            function_parameters: &mut function_parameters,
-            prev_element_debug_info: &mut prev_element_debug_info,
-            cancellation_flag,
-        };
-        for graph_stmt in &lazy_graph {
-            graph_stmt.evaluate(&mut exec)?;
-        }
-        // make sure any unforced values are now forced, to surface any problems
-        // hidden by the fact that the values were unused
-        store.evaluate_all(&mut exec)?;
-        scoped_store.evaluate_all(&mut exec)?;
-
-        Ok(())
+        Ok(LazyGraph {
+            statements: lazy_graph,
+            store,
+            scoped_store,
+            function_parameters,
+            prev_element_debug_info,
+            stanza_matches,
+        })
     }
 
     pub(super) fn try_visit_matches_lazy<'tree, E, F>(
         &self,
-        tree: &'tree Tree,
+        cursor: &mut QueryCursor,
+        root: Node<'tree>,
         source: &'tree str,
         mut visit: F,
     ) -> Result<(), E>
     where
         F: FnMut(&ast::Stanza, QueryMatch<'_, 'tree>) -> Result<(), E>,
     {
-        let mut cursor = QueryCursor::new();
         let query = self.query.as_ref().unwrap();
-        let matches = cursor.matches(query, tree.root_node(), source.as_bytes());
+        let matches = cursor.matches(query, root, source.as_bytes());
         for mat in matches {
             let stanza = &self.stanzas[mat.pattern_index];
             visit(stanza, mat)?;
@@ -127,6 +206,53 @@ impl ast::File {
     }
 }
 
+/// The result of executing stanzas, which is evaluated to build the actual graph
+struct LazyGraph {
+    statements: Vec<LazyStatement>,
+    store: LazyStore,
+    scoped_store: LazyScopedVariables,
+    function_parameters: Vec<graph::Value>,
+    prev_element_debug_info: HashMap<GraphElementKey, DebugInfo>,
+    /// The number of matches of each stanza, in the order of the stanzas in the file
+    stanza_matches: Vec<usize>,
+}
+
+impl LazyGraph {
+    /// Evaluates the lazy graph to build the actual graph, recording the operations performed on
+    /// the graph in `events`, if given.
+    fn evaluate_into<'tree>(
+        &mut self,
+        graph: &mut Graph<'tree>,
+        source: &'tree str,
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+        events: Option<&mut Vec<GraphEvent>>,
+    ) -> Result<(), ExecutionError> {
+        let mut exec = EvaluationContext {
+            source,
+            graph,
+            functions: config.functions,
+            store: &self.store,
+            scoped_store: &self.scoped_store,
+            function_parameters: &mut self.function_parameters,
+            prev_element_debug_info: &mut self.prev_element_debug_info,
+            cancellation_flag,
+            events,
+            value_depth: 0,
+            eager: false,
+            print_to: config.print_to,
+        };
+        for graph_stmt in &self.statements {
+            graph_stmt.evaluate(&mut exec)?;
+        }
+        // make sure any unforced values are now forced, to surface any problems
+        // hidden by the fact that the values were unused
+        self.store.evaluate_all(&mut exec)?;
+        self.scoped_store.evaluate_all(&mut exec)?;
+        Ok(())
+    }
+}
+
 /// Context for execution, which executes stanzas to build the lazy graph
 struct ExecutionContext<'a, 'c, 'g, 'tree> {
     source: &'tree str,
@@ -143,6 +269,10 @@ struct ExecutionContext<'a, 'c, 'g, 'tree> {
     error_context: StatementContext,
     shorthands: &'a ast::AttributeShorthands,
     cancellation_flag: &'a dyn CancellationFlag,
+    /// The number of expressions that are currently being evaluated, one inside the other
+    expression_depth: usize,
+    /// The root node of the current match
+    full_match: Node<'tree>,
 }
 
 /// Context for evaluation, which evalautes the lazy graph to build the actual graph
@@ -155,6 +285,25 @@ pub(self) struct EvaluationContext<'a, 'tree> {
     pub function_parameters: &'a mut Vec<graph::Value>, // re-usable buffer to reduce memory allocations
     pub prev_element_debug_info: &'a mut HashMap<GraphElementKey, DebugInfo>,
     pub cancellation_flag: &'a dyn CancellationFlag,
+    /// The operations performed on the graph, if they are being recorded
+    pub events: Option<&'a mut Vec<GraphEvent>>,
+    /// The number of values that are currently being forced, one inside the other, as when a
+    /// scoped variable refers to another scoped variable
+    pub value_depth: usize,
+    /// Whether the value is needed before the graph is built, in which case functions that read
+    /// the graph cannot be called
+    pub eager: bool,
+    /// Where the output of `print` statements goes, if not to standard error
+    pub print_to: Option<&'a (dyn Fn(&str) + Sync)>,
+}
+
+impl EvaluationContext<'_, '_> {
+    /// Records an operation performed on the graph, if operations are being recorded.
+    fn record(&mut self, event: impl FnOnce() -> GraphEvent) {
+        if let Some(events) = self.events.as_mut() {
+            events.push(event());
+        }
+    }
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
@@ -205,6 +354,8 @@ impl ast::Stanza {
                 error_context,
                 shorthands,
                 cancellation_flag,
+                expression_depth: 0,
+                full_match: node,
             };
             statement
                 .execute_lazy(&mut exec)
@@ -220,12 +371,15 @@ impl ast::Statement {
         exec.cancellation_flag.check("executing statement")?;
         match self {
             Self::DeclareImmutable(statement) => statement.execute_lazy(exec),
+            Self::DestructureImmutable(statement) => statement.execute_lazy(exec),
             Self::DeclareMutable(statement) => statement.execute_lazy(exec),
             Self::Assign(statement) => statement.execute_lazy(exec),
             Self::CreateGraphNode(statement) => statement.execute_lazy(exec),
             Self::AddGraphNodeAttribute(statement) => statement.execute_lazy(exec),
+            Self::DeleteGraphNodeAttribute(statement) => statement.execute_lazy(exec),
             Self::CreateEdge(statement) => statement.execute_lazy(exec),
             Self::AddEdgeAttribute(statement) => statement.execute_lazy(exec),
+            Self::DeleteEdgeAttribute(statement) => statement.execute_lazy(exec),
             Self::Scan(statement) => statement.execute_lazy(exec),
             Self::Print(statement) => statement.execute_lazy(exec),
             Self::If(statement) => statement.execute_lazy(exec),
@@ -241,6 +395,19 @@ impl ast::DeclareImmutable {
     }
 }
 
+impl ast::DestructureImmutable {
+    fn execute_lazy(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        let value = self.value.evaluate_lazy(exec)?;
+        // Store the list once, so that it is only evaluated once for all of the variables.
+        let list = exec.store.add(value, exec.error_context.clone().into());
+        for (index, variable) in self.variables.iter().enumerate() {
+            let element = LazyListElement::new(list.clone().into(), index, self.variables.len());
+            variable.add_lazy(exec, element.into(), false)?;
+        }
+        Ok(())
+    }
+}
+
 impl ast::DeclareMutable {
     fn execute_lazy(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
         let value = self.value.evaluate_lazy(exec)?;
@@ -258,6 +425,9 @@ impl ast::Assign {
 impl ast::CreateGraphNode {
     fn execute_lazy(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
         let graph_node = exec.graph.add_graph_node();
+        if exec.config.record_locations {
+            exec.graph[graph_node].created_at = Some(self.location);
+        }
         self.node
             .add_debug_attrs(&mut exec.graph[graph_node].attributes, exec.config)?;
         self.node.add_lazy(exec, graph_node.into(), false)
@@ -272,8 +442,31 @@ impl ast::AddGraphNodeAttribute {
         for attribute in &self.attributes {
             attribute.execute_lazy(exec, &mut add_attribute)?;
         }
-        let stmt =
-            LazyAddGraphNodeAttribute::new(node, attributes, exec.error_context.clone().into());
+        let mut spreads = Vec::new();
+        for spread in &self.spreads {
+            spreads.push(spread.evaluate_lazy(exec)?);
+        }
+        let stmt = LazyAddGraphNodeAttribute::new(
+            node,
+            attributes,
+            spreads,
+            self.overwrite || exec.config.overwrite_attributes,
+            self.skip_null,
+            exec.error_context.clone().into(),
+        );
+        exec.lazy_graph.push(stmt.into());
+        Ok(())
+    }
+}
+
+impl ast::DeleteGraphNodeAttribute {
+    fn execute_lazy(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        let node = self.node.evaluate_lazy(exec)?;
+        let stmt = LazyDeleteGraphNodeAttribute::new(
+            node,
+            self.names.clone(),
+            exec.error_context.clone().into(),
+        );
         exec.lazy_graph.push(stmt.into());
         Ok(())
     }
@@ -285,7 +478,23 @@ impl ast::CreateEdge {
         let sink = self.sink.evaluate_lazy(exec)?;
         let mut attributes = Attributes::new();
         self.add_debug_attrs(&mut attributes, exec.config)?;
-        let stmt = LazyCreateEdge::new(source, sink, attributes, exec.error_context.clone().into());
+        let created_at = if exec.config.record_locations {
+            Some(self.location)
+        } else {
+            None
+        };
+        if let Some(variable) = &self.edge {
+            let edge = LazyEdge::new(source.clone(), sink.clone());
+            variable.add_lazy(exec, edge.into(), false)?;
+        }
+        let stmt = LazyCreateEdge::new(
+            source,
+            sink,
+            attributes,
+            created_at,
+            exec.config.reject_self_edges,
+            exec.error_context.clone().into(),
+        );
         exec.lazy_graph.push(stmt.into());
         Ok(())
     }
@@ -300,8 +509,29 @@ impl ast::AddEdgeAttribute {
         for attribute in &self.attributes {
             attribute.execute_lazy(exec, &mut add_attribute)?;
         }
-        let stmt =
-            LazyAddEdgeAttribute::new(source, sink, attributes, exec.error_context.clone().into());
+        let stmt = LazyAddEdgeAttribute::new(
+            source,
+            sink,
+            attributes,
+            self.overwrite || exec.config.overwrite_attributes,
+            self.skip_null,
+            exec.error_context.clone().into(),
+        );
+        exec.lazy_graph.push(stmt.into());
+        Ok(())
+    }
+}
+
+impl ast::DeleteEdgeAttribute {
+    fn execute_lazy(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        let source = self.source.evaluate_lazy(exec)?;
+        let sink = self.sink.evaluate_lazy(exec)?;
+        let stmt = LazyDeleteEdgeAttribute::new(
+            source,
+            sink,
+            self.names.clone(),
+            exec.error_context.clone().into(),
+        );
         exec.lazy_graph.push(stmt.into());
         Ok(())
     }
@@ -334,23 +564,41 @@ impl ast::Scan {
                 }
             }
 
-            if matches.is_empty() {
-                return Ok(());
-            }
-
             matches.sort_by_key(|(captures, index)| {
                 let range = captures.get(0).expect("missing regex capture").range();
                 (range.start, *index)
             });
 
-            let (regex_captures, block_index) = &matches[0];
-            let arm = &self.arms[*block_index];
+            // Any text before the earliest match (or all of the remaining text, if nothing
+            // matches) is handled by the default arm, if there is one.  Note that `i` always
+            // lands on a character boundary, since the regex crate only reports match offsets
+            // on UTF-8 character boundaries.
+            let unmatched_end = matches.first().map_or(match_string.len(), |(captures, _)| {
+                i + captures.get(0).expect("missing regex capture").start()
+            });
 
             let mut current_regex_captures = Vec::new();
-            for regex_capture in regex_captures.iter() {
-                current_regex_captures
-                    .push(regex_capture.map(|m| m.as_str()).unwrap_or("").to_string());
-            }
+            let (arm, statements) = match (&self.default_arm, matches.first()) {
+                (Some(default_arm), _) if unmatched_end > i => {
+                    current_regex_captures.push(match_string[i..unmatched_end].to_string());
+                    i = unmatched_end;
+                    (None, &default_arm.statements)
+                }
+                (_, Some((regex_captures, block_index))) => {
+                    let arm = &self.arms[*block_index];
+                    for regex_capture in regex_captures.iter() {
+                        current_regex_captures
+                            .push(regex_capture.map(|m| m.as_str()).unwrap_or("").to_string());
+                    }
+                    i += regex_captures
+                        .get(0)
+                        .expect("missing regex capture")
+                        .range()
+                        .end;
+                    (Some(arm), &arm.statements)
+                }
+                (_, None) => return Ok(()),
+            };
 
             let mut arm_locals = VariableMap::nested(exec.locals);
             let mut arm_exec = ExecutionContext {
@@ -368,24 +616,23 @@ impl ast::Scan {
                 error_context: exec.error_context.clone(),
                 shorthands: exec.shorthands,
                 cancellation_flag: exec.cancellation_flag,
+                expression_depth: exec.expression_depth,
+                full_match: exec.full_match,
             };
 
-            for statement in &arm.statements {
+            for statement in statements {
                 arm_exec.error_context.statement = format!("{}", statement);
                 arm_exec.error_context.statement_location = statement.location();
                 statement
                     .execute_lazy(&mut arm_exec)
-                    .with_context(|| {
-                        format!("matching {} with arm \"{}\"", match_string, arm.regex,).into()
+                    .with_context(|| match arm {
+                        Some(arm) => {
+                            format!("matching {} with arm \"{}\"", match_string, arm.regex,).into()
+                        }
+                        None => format!("matching {} with default arm", match_string).into(),
                     })
                     .with_context(|| arm_exec.error_context.clone().into())?;
             }
-
-            i += regex_captures
-                .get(0)
-                .expect("missing regex capture")
-                .range()
-                .end;
         }
 
         Ok(())
@@ -433,6 +680,8 @@ impl ast::If {
                     error_context: exec.error_context.clone(),
                     shorthands: exec.shorthands,
                     cancellation_flag: exec.cancellation_flag,
+                    expression_depth: exec.expression_depth,
+                    full_match: exec.full_match,
                 };
                 for stmt in &arm.statements {
                     arm_exec.error_context.statement = format!("{}", stmt);
@@ -479,6 +728,8 @@ impl ast::ForIn {
                 error_context: exec.error_context.clone(),
                 shorthands: exec.shorthands,
                 cancellation_flag: exec.cancellation_flag,
+                expression_depth: exec.expression_depth,
+                full_match: exec.full_match,
             };
             self.variable
                 .add_lazy(&mut loop_exec, value.into(), false)?;
@@ -494,6 +745,19 @@ impl ast::ForIn {
 
 impl ast::Expression {
     fn evaluate_lazy(&self, exec: &mut ExecutionContext) -> Result<LazyValue, ExecutionError> {
+        exec.expression_depth += 1;
+        let result = match exec.config.check_expression_depth(exec.expression_depth) {
+            Ok(()) => self.evaluate_lazy_nested(exec),
+            Err(e) => Err(e),
+        };
+        exec.expression_depth -= 1;
+        result
+    }
+
+    fn evaluate_lazy_nested(
+        &self,
+        exec: &mut ExecutionContext,
+    ) -> Result<LazyValue, ExecutionError> {
         match self {
             Self::FalseLiteral => Ok(false.into()),
             Self::NullLiteral => Ok(graph::Value::Null.into()),
@@ -507,6 +771,12 @@ impl ast::Expression {
             Self::Capture(expr) => expr.evaluate_lazy(exec),
             Self::Variable(expr) => expr.evaluate_lazy(exec),
             Self::Call(expr) => expr.evaluate_lazy(exec),
+            Self::MatchFunction(expr) => Ok(expr
+                .evaluate(exec.mat.pattern_index, exec.full_match, exec.graph)
+                .into()),
+            Self::Comparison(expr) => expr.evaluate_lazy(exec),
+            Self::Logical(expr) => expr.evaluate_lazy(exec),
+            Self::Not(expr) => expr.evaluate_lazy(exec),
             Self::RegexCapture(expr) => expr.evaluate_lazy(exec),
         }
     }
@@ -514,7 +784,13 @@ impl ast::Expression {
     // Eagerly evaluate the expression to a `Value`, instead of a `LazyValue`. This method should
     // only be called on expressions that are local (i.e., `is_local = true` in the checker).
     fn evaluate_eager(&self, exec: &mut ExecutionContext) -> Result<graph::Value, ExecutionError> {
-        self.evaluate_lazy(exec)?.evaluate(&mut EvaluationContext {
+        // Values that are already known (such as globals) are returned as is, to avoid copying
+        // them a second time, which can be expensive for large lists and sets.
+        let value = match self.evaluate_lazy(exec)? {
+            LazyValue::Value(value) => return Ok(value),
+            value => value,
+        };
+        value.evaluate(&mut EvaluationContext {
             source: exec.source,
             graph: exec.graph,
             functions: exec.config.functions,
@@ -523,6 +799,10 @@ impl ast::Expression {
             function_parameters: exec.function_parameters,
             prev_element_debug_info: exec.prev_element_debug_info,
             cancellation_flag: exec.cancellation_flag,
+            events: None,
+            value_depth: 0,
+            eager: true,
+            print_to: exec.config.print_to,
         })
     }
 }
@@ -571,6 +851,8 @@ impl ast::ListComprehension {
                 error_context: exec.error_context.clone(),
                 shorthands: exec.shorthands,
                 cancellation_flag: exec.cancellation_flag,
+                expression_depth: exec.expression_depth,
+                full_match: exec.full_match,
             };
             self.variable
                 .add_lazy(&mut loop_exec, value.into(), false)?;
@@ -613,6 +895,8 @@ impl ast::SetComprehension {
                 error_context: exec.error_context.clone(),
                 shorthands: exec.shorthands,
                 cancellation_flag: exec.cancellation_flag,
+                expression_depth: exec.expression_depth,
+                full_match: exec.full_match,
             };
             self.variable
                 .add_lazy(&mut loop_exec, value.into(), false)?;
@@ -645,9 +929,36 @@ impl ast::Call {
     }
 }
 
+impl ast::Comparison {
+    fn evaluate_lazy(&self, exec: &mut ExecutionContext) -> Result<LazyValue, ExecutionError> {
+        let left = self.left.evaluate_lazy(exec)?;
+        let right = self.right.evaluate_lazy(exec)?;
+        Ok(LazyComparison::new(self.operator, left, right).into())
+    }
+}
+
+impl ast::Logical {
+    fn evaluate_lazy(&self, exec: &mut ExecutionContext) -> Result<LazyValue, ExecutionError> {
+        let left = self.left.evaluate_lazy(exec)?;
+        let right = self.right.evaluate_lazy(exec)?;
+        Ok(LazyLogical::new(self.operator, left, right).into())
+    }
+}
+
+impl ast::Not {
+    fn evaluate_lazy(&self, exec: &mut ExecutionContext) -> Result<LazyValue, ExecutionError> {
+        let value = self.value.evaluate_lazy(exec)?;
+        Ok(LazyNot::new(value).into())
+    }
+}
+
 impl ast::RegexCapture {
     fn evaluate_lazy(&self, exec: &mut ExecutionContext) -> Result<LazyValue, ExecutionError> {
-        let value = exec.current_regex_captures[self.match_index].clone();
+        let value = exec
+            .current_regex_captures
+            .get(self.match_index)
+            .ok_or(ExecutionError::UndefinedRegexCapture(format!("{}", self)))?
+            .clone();
         Ok(value.into())
     }
 }
@@ -706,7 +1017,11 @@ impl ast::ScopedVariable {
             )));
         }
         let scope = self.scope.evaluate_lazy(exec)?;
-        let variable = exec.store.add(value, exec.error_context.clone().into());
+        let variable = exec.store.add_scoped(
+            value,
+            format!("{}", self),
+            exec.error_context.clone().into(),
+        );
         exec.scoped_store.add(
             scope,
             self.name.clone(),
@@ -827,6 +1142,8 @@ impl ast::AttributeShorthand {
             error_context: exec.error_context.clone(),
             shorthands: exec.shorthands,
             cancellation_flag: exec.cancellation_flag,
+            expression_depth: exec.expression_depth,
+            full_match: exec.full_match,
         };
         self.variable.add_lazy(&mut shorthand_exec, value, false)?;
         for attr in &self.attributes {
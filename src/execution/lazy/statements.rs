@@ -14,8 +14,12 @@ use std::fmt;
 
 use crate::execution::error::ExecutionError;
 use crate::execution::error::ResultWithExecutionError;
+use crate::execution::print_line;
 use crate::graph::Attributes;
+use crate::graph::GraphEvent;
+use crate::graph::Value;
 use crate::Identifier;
+use crate::Location;
 
 use super::store::DebugInfo;
 use super::values::*;
@@ -26,8 +30,10 @@ use super::GraphElementKey;
 #[derive(Debug)]
 pub(super) enum LazyStatement {
     AddGraphNodeAttribute(LazyAddGraphNodeAttribute),
+    DeleteGraphNodeAttribute(LazyDeleteGraphNodeAttribute),
     CreateEdge(LazyCreateEdge),
     AddEdgeAttribute(LazyAddEdgeAttribute),
+    DeleteEdgeAttribute(LazyDeleteEdgeAttribute),
     Print(LazyPrint),
 }
 
@@ -40,12 +46,18 @@ impl LazyStatement {
             Self::AddGraphNodeAttribute(stmt) => stmt
                 .evaluate(exec)
                 .with_context(|| stmt.debug_info.clone().into()),
+            Self::DeleteGraphNodeAttribute(stmt) => stmt
+                .evaluate(exec)
+                .with_context(|| stmt.debug_info.clone().into()),
             Self::CreateEdge(stmt) => stmt
                 .evaluate(exec)
                 .with_context(|| stmt.debug_info.clone().into()),
             Self::AddEdgeAttribute(stmt) => stmt
                 .evaluate(exec)
                 .with_context(|| stmt.debug_info.clone().into()),
+            Self::DeleteEdgeAttribute(stmt) => stmt
+                .evaluate(exec)
+                .with_context(|| stmt.debug_info.clone().into()),
             Self::Print(stmt) => stmt
                 .evaluate(exec)
                 .with_context(|| stmt.debug_info.clone().into()),
@@ -67,6 +79,18 @@ impl From<LazyAddGraphNodeAttribute> for LazyStatement {
     }
 }
 
+impl From<LazyDeleteEdgeAttribute> for LazyStatement {
+    fn from(stmt: LazyDeleteEdgeAttribute) -> Self {
+        Self::DeleteEdgeAttribute(stmt)
+    }
+}
+
+impl From<LazyDeleteGraphNodeAttribute> for LazyStatement {
+    fn from(stmt: LazyDeleteGraphNodeAttribute) -> Self {
+        Self::DeleteGraphNodeAttribute(stmt)
+    }
+}
+
 impl From<LazyCreateEdge> for LazyStatement {
     fn from(stmt: LazyCreateEdge) -> Self {
         Self::CreateEdge(stmt)
@@ -83,8 +107,10 @@ impl fmt::Display for LazyStatement {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::AddGraphNodeAttribute(stmt) => stmt.fmt(f),
+            Self::DeleteGraphNodeAttribute(stmt) => stmt.fmt(f),
             Self::CreateEdge(stmt) => stmt.fmt(f),
             Self::AddEdgeAttribute(stmt) => stmt.fmt(f),
+            Self::DeleteEdgeAttribute(stmt) => stmt.fmt(f),
             Self::Print(stmt) => stmt.fmt(f),
         }
     }
@@ -95,6 +121,9 @@ impl fmt::Display for LazyStatement {
 pub(super) struct LazyAddGraphNodeAttribute {
     node: LazyValue,
     attributes: Vec<LazyAttribute>,
+    spreads: Vec<LazyValue>,
+    overwrite: bool,
+    skip_null: bool,
     debug_info: DebugInfo,
 }
 
@@ -102,35 +131,57 @@ impl LazyAddGraphNodeAttribute {
     pub(super) fn new(
         node: LazyValue,
         attributes: Vec<LazyAttribute>,
+        spreads: Vec<LazyValue>,
+        overwrite: bool,
+        skip_null: bool,
         debug_info: DebugInfo,
     ) -> Self {
         Self {
             node,
             attributes,
+            spreads,
+            overwrite,
+            skip_null,
             debug_info,
         }
     }
 
     pub(super) fn evaluate(&self, exec: &mut EvaluationContext) -> Result<(), ExecutionError> {
         let node = self.node.evaluate_as_graph_node(exec)?;
+        let mut attributes = Vec::new();
         for attribute in &self.attributes {
-            let value = attribute.value.evaluate(exec)?;
+            attributes.push((attribute.name.clone(), attribute.value.evaluate(exec)?));
+        }
+        for spread in &self.spreads {
+            attributes.extend(spread.evaluate(exec)?.into_attribute_pairs()?);
+        }
+        for (name, value) in attributes {
+            if self.skip_null && value.is_null() {
+                continue;
+            }
             let prev_debug_info = exec.prev_element_debug_info.insert(
-                GraphElementKey::NodeAttribute(node, attribute.name.clone()),
+                GraphElementKey::NodeAttribute(node, name.clone()),
                 self.debug_info.clone(),
             );
-            exec.graph[node]
-                .attributes
-                .add(attribute.name.clone(), value)
-                .map_err(|_| {
-                    ExecutionError::DuplicateAttribute(format!(
-                        "{} on {} at {} and {}",
-                        attribute.name,
-                        node,
-                        prev_debug_info.unwrap(),
-                        self.debug_info,
-                    ))
-                })?;
+            let event_value = exec.events.as_ref().map(|_| value.clone());
+            if self.overwrite {
+                exec.graph[node].attributes.set(name.clone(), value);
+            } else {
+                exec.graph[node]
+                    .attributes
+                    .add(name.clone(), value)
+                    .map_err(|_| {
+                        ExecutionError::DuplicateAttribute(format!(
+                            "{} on {} at {}",
+                            name,
+                            node,
+                            duplicate_locations(prev_debug_info, &self.debug_info),
+                        ))
+                    })?;
+            }
+            if let Some(value) = event_value {
+                exec.record(|| GraphEvent::AddGraphNodeAttribute(node, name, value));
+            }
         }
         Ok(())
     }
@@ -138,10 +189,52 @@ impl LazyAddGraphNodeAttribute {
 
 impl fmt::Display for LazyAddGraphNodeAttribute {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "attr ({})", self.node)?;
+        let keyword = if self.overwrite { "set-attr" } else { "attr" };
+        let optional = if self.skip_null { "?" } else { "" };
+        write!(f, "{}{} ({})", keyword, optional, self.node)?;
         for attr in &self.attributes {
             write!(f, " {}", attr)?;
         }
+        for spread in &self.spreads {
+            write!(f, " ..{}", spread)?;
+        }
+        write!(f, " at {}", self.debug_info)
+    }
+}
+
+/// Lazy statement to delete graph node attributes
+#[derive(Debug)]
+pub(super) struct LazyDeleteGraphNodeAttribute {
+    node: LazyValue,
+    names: Vec<Identifier>,
+    debug_info: DebugInfo,
+}
+
+impl LazyDeleteGraphNodeAttribute {
+    pub(super) fn new(node: LazyValue, names: Vec<Identifier>, debug_info: DebugInfo) -> Self {
+        Self {
+            node,
+            names,
+            debug_info,
+        }
+    }
+
+    pub(super) fn evaluate(&self, exec: &mut EvaluationContext) -> Result<(), ExecutionError> {
+        let node = self.node.evaluate_as_graph_node(exec)?;
+        for name in &self.names {
+            exec.graph[node].attributes.remove(name);
+            exec.record(|| GraphEvent::DeleteGraphNodeAttribute(node, name.clone()));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for LazyDeleteGraphNodeAttribute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "del-attr ({})", self.node)?;
+        for name in &self.names {
+            write!(f, " {}", name)?;
+        }
         write!(f, " at {}", self.debug_info)
     }
 }
@@ -152,6 +245,8 @@ pub(super) struct LazyCreateEdge {
     source: LazyValue,
     sink: LazyValue,
     attributes: Attributes,
+    created_at: Option<Location>,
+    reject_self_edge: bool,
     debug_info: DebugInfo,
 }
 
@@ -160,12 +255,16 @@ impl LazyCreateEdge {
         source: LazyValue,
         sink: LazyValue,
         attributes: Attributes,
+        created_at: Option<Location>,
+        reject_self_edge: bool,
         debug_info: DebugInfo,
     ) -> Self {
         Self {
             source,
             sink,
             attributes,
+            created_at,
+            reject_self_edge,
             debug_info,
         }
     }
@@ -173,6 +272,12 @@ impl LazyCreateEdge {
     pub(super) fn evaluate(&self, exec: &mut EvaluationContext) -> Result<(), ExecutionError> {
         let source = self.source.evaluate_as_graph_node(exec)?;
         let sink = self.sink.evaluate_as_graph_node(exec)?;
+        if self.reject_self_edge && source == sink {
+            return Err(ExecutionError::SelfEdge(format!(
+                "({} -> {}) at {}",
+                source, sink, self.debug_info,
+            )));
+        }
         let prev_debug_info = exec
             .prev_element_debug_info
             .insert(GraphElementKey::Edge(source, sink), self.debug_info.clone());
@@ -180,15 +285,25 @@ impl LazyCreateEdge {
             Ok(edge) => edge,
             Err(_) => {
                 return Err(ExecutionError::DuplicateEdge(format!(
-                    "({} -> {}) at {} and {}",
+                    "({} -> {}) at {}",
                     source,
                     sink,
-                    prev_debug_info.unwrap(),
-                    self.debug_info,
+                    duplicate_locations(prev_debug_info, &self.debug_info),
                 )))?
             }
         };
         edge.attributes = self.attributes.clone();
+        edge.created_at = self.created_at;
+        exec.record(|| GraphEvent::CreateEdge(source, sink));
+        if exec.events.is_some() {
+            let mut attributes = self.attributes.iter().collect::<Vec<_>>();
+            attributes.sort_by_key(|(name, _)| *name);
+            for (name, value) in attributes {
+                exec.record(|| {
+                    GraphEvent::AddEdgeAttribute(source, sink, name.clone(), value.clone())
+                });
+            }
+        }
         Ok(())
     }
 }
@@ -209,6 +324,8 @@ pub(super) struct LazyAddEdgeAttribute {
     source: LazyValue,
     sink: LazyValue,
     attributes: Vec<LazyAttribute>,
+    overwrite: bool,
+    skip_null: bool,
     debug_info: DebugInfo,
 }
 
@@ -217,12 +334,16 @@ impl LazyAddEdgeAttribute {
         source: LazyValue,
         sink: LazyValue,
         attributes: Vec<LazyAttribute>,
+        overwrite: bool,
+        skip_null: bool,
         debug_info: DebugInfo,
     ) -> Self {
         Self {
             source,
             sink,
             attributes,
+            overwrite,
+            skip_null,
             debug_info,
         }
     }
@@ -232,6 +353,10 @@ impl LazyAddEdgeAttribute {
         let sink = self.sink.evaluate_as_graph_node(exec)?;
         for attribute in &self.attributes {
             let value = attribute.value.evaluate(exec)?;
+            if self.skip_null && value.is_null() {
+                continue;
+            }
+            let event_value = exec.events.as_ref().map(|_| value.clone());
             let edge = match exec.graph[source].get_edge_mut(sink) {
                 Some(edge) => Ok(edge),
                 None => Err(ExecutionError::UndefinedEdge(format!(
@@ -243,18 +368,26 @@ impl LazyAddEdgeAttribute {
                 GraphElementKey::EdgeAttribute(source, sink, attribute.name.clone()),
                 self.debug_info.clone(),
             );
-            edge.attributes
-                .add(attribute.name.clone(), value)
-                .map_err(|_| {
-                    ExecutionError::DuplicateAttribute(format!(
-                        "{} on edge ({} -> {}) at {} and {}",
-                        attribute.name,
-                        source,
-                        sink,
-                        prev_debug_info.unwrap(),
-                        self.debug_info,
-                    ))
-                })?;
+            if self.overwrite {
+                edge.attributes.set(attribute.name.clone(), value);
+            } else {
+                edge.attributes
+                    .add(attribute.name.clone(), value)
+                    .map_err(|_| {
+                        ExecutionError::DuplicateAttribute(format!(
+                            "{} on edge ({} -> {}) at {}",
+                            attribute.name,
+                            source,
+                            sink,
+                            duplicate_locations(prev_debug_info, &self.debug_info),
+                        ))
+                    })?;
+            }
+            if let Some(value) = event_value {
+                exec.record(|| {
+                    GraphEvent::AddEdgeAttribute(source, sink, attribute.name.clone(), value)
+                });
+            }
         }
         Ok(())
     }
@@ -262,7 +395,13 @@ impl LazyAddEdgeAttribute {
 
 impl fmt::Display for LazyAddEdgeAttribute {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "attr ({} -> {})", self.source, self.sink,)?;
+        let keyword = if self.overwrite { "set-attr" } else { "attr" };
+        let optional = if self.skip_null { "?" } else { "" };
+        write!(
+            f,
+            "{}{} ({} -> {})",
+            keyword, optional, self.source, self.sink,
+        )?;
         for attr in &self.attributes {
             write!(f, " {}", attr,)?;
         }
@@ -270,6 +409,60 @@ impl fmt::Display for LazyAddEdgeAttribute {
     }
 }
 
+/// Lazy statement to delete graph edge attributes
+#[derive(Debug)]
+pub(super) struct LazyDeleteEdgeAttribute {
+    source: LazyValue,
+    sink: LazyValue,
+    names: Vec<Identifier>,
+    debug_info: DebugInfo,
+}
+
+impl LazyDeleteEdgeAttribute {
+    pub(super) fn new(
+        source: LazyValue,
+        sink: LazyValue,
+        names: Vec<Identifier>,
+        debug_info: DebugInfo,
+    ) -> Self {
+        Self {
+            source,
+            sink,
+            names,
+            debug_info,
+        }
+    }
+
+    pub(super) fn evaluate(&self, exec: &mut EvaluationContext) -> Result<(), ExecutionError> {
+        let source = self.source.evaluate_as_graph_node(exec)?;
+        let sink = self.sink.evaluate_as_graph_node(exec)?;
+        let edge = match exec.graph[source].get_edge_mut(sink) {
+            Some(edge) => Ok(edge),
+            None => Err(ExecutionError::UndefinedEdge(format!(
+                "({} -> {}) at {}",
+                source, sink, self.debug_info,
+            ))),
+        }?;
+        for name in &self.names {
+            edge.attributes.remove(name);
+        }
+        for name in &self.names {
+            exec.record(|| GraphEvent::DeleteEdgeAttribute(source, sink, name.clone()));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for LazyDeleteEdgeAttribute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "del-attr ({} -> {})", self.source, self.sink)?;
+        for name in &self.names {
+            write!(f, " {}", name)?;
+        }
+        write!(f, " at {}", self.debug_info)
+    }
+}
+
 /// Lazy statement to print values
 #[derive(Debug)]
 pub(super) struct LazyPrint {
@@ -292,16 +485,17 @@ impl LazyPrint {
     }
 
     pub(super) fn evaluate(&self, exec: &mut EvaluationContext) -> Result<(), ExecutionError> {
+        let mut line = String::new();
         for argument in &self.arguments {
             match argument {
-                LazyPrintArgument::Text(string) => eprint!("{}", string),
-                LazyPrintArgument::Value(value) => {
-                    let value = value.evaluate(exec)?;
-                    eprint!("{:?}", value);
-                }
+                LazyPrintArgument::Text(string) => line.push_str(string),
+                LazyPrintArgument::Value(value) => match value.evaluate(exec)? {
+                    Value::String(string) => line.push_str(&string),
+                    value => line.push_str(&format!("{:?}", value)),
+                },
             }
         }
-        eprintln!("");
+        print_line(exec.print_to, &line);
         Ok(())
     }
 }
@@ -312,6 +506,7 @@ impl fmt::Display for LazyPrint {
         let mut first = true;
         for argument in &self.arguments {
             if first {
+                write!(f, " ")?;
                 first = false;
             } else {
                 write!(f, ", ")?;
@@ -325,6 +520,15 @@ impl fmt::Display for LazyPrint {
     }
 }
 
+/// Describes where a duplicate graph element was created.  The previous element might not have
+/// been created by any statement, if it was already in the graph before execution started.
+fn duplicate_locations(prev: Option<DebugInfo>, current: &DebugInfo) -> String {
+    match prev {
+        Some(prev) => format!("{} and {}", prev, current),
+        None => format!("{}", current),
+    }
+}
+
 /// Lazy attribute
 #[derive(Debug)]
 pub(super) struct LazyAttribute {
@@ -68,7 +68,23 @@ impl LazyStore {
         let store_location = self.elements.len();
         let variable = LazyVariable::new(store_location);
         trace!("store {} = {}", store_location, value);
-        self.elements.push(Thunk::new(value, debug_info));
+        self.elements.push(Thunk::new(value, None, debug_info));
+        variable
+    }
+
+    /// Adds a thunk holding the value of the given scoped variable.  Recursive definitions of the
+    /// variable are reported as recursively defined scoped variables.
+    pub(super) fn add_scoped(
+        &mut self,
+        value: LazyValue,
+        scoped_variable: String,
+        debug_info: DebugInfo,
+    ) -> LazyVariable {
+        let store_location = self.elements.len();
+        let variable = LazyVariable::new(store_location);
+        trace!("store {} = {}", store_location, value);
+        self.elements
+            .push(Thunk::new(value, Some(scoped_variable), debug_info));
         variable
     }
 
@@ -223,6 +239,7 @@ impl ScopedValues {
 /// Thunk holding a lazy value or a forced graph value
 struct Thunk {
     state: Rc<RefCell<ThunkState>>,
+    scoped_variable: Option<String>,
     debug_info: DebugInfo,
 }
 
@@ -243,9 +260,10 @@ impl fmt::Display for ThunkState {
 }
 
 impl Thunk {
-    fn new(value: LazyValue, debug_info: DebugInfo) -> Thunk {
+    fn new(value: LazyValue, scoped_variable: Option<String>, debug_info: DebugInfo) -> Thunk {
         Thunk {
             state: Rc::new(RefCell::new(ThunkState::Unforced(value))),
+            scoped_variable,
             debug_info,
         }
     }
@@ -260,10 +278,16 @@ impl Thunk {
                 Ok(value)
             }
             ThunkState::Forced(value) => Ok(value),
-            ThunkState::Forcing => Err(ExecutionError::RecursivelyDefinedVariable(format!(
-                "{}",
-                self.debug_info
-            ))),
+            ThunkState::Forcing => match &self.scoped_variable {
+                Some(name) => Err(ExecutionError::RecursivelyDefinedScopedVariable(format!(
+                    "{} at {}",
+                    name, self.debug_info
+                ))),
+                None => Err(ExecutionError::RecursivelyDefinedVariable(format!(
+                    "{}",
+                    self.debug_info
+                ))),
+            },
         }?;
         *self.state.borrow_mut() = ThunkState::Forced(value.clone());
         Ok(value)
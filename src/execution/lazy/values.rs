@@ -12,6 +12,8 @@ use log::trace;
 use std::convert::From;
 use std::fmt;
 
+use crate::ast::ComparisonOperator;
+use crate::ast::LogicalOperator;
 use crate::execution::error::ExecutionError;
 use crate::graph::GraphNodeRef;
 use crate::graph::SyntaxNodeRef;
@@ -21,6 +23,12 @@ use crate::Identifier;
 use super::store::*;
 use super::EvaluationContext;
 
+/// The maximum number of values that can be forced one inside the other, such as the links of a
+/// chain of scoped variables that each refer to the previous one.  Forcing a link takes about
+/// 2 KiB of stack in release builds (and several times that in debug builds), so this keeps the
+/// longest chains within about half of the 8 MiB stack that the main thread usually has.
+const MAX_VALUE_DEPTH: usize = 2048;
+
 /// Lazy values
 #[derive(Clone, Debug)]
 pub(super) enum LazyValue {
@@ -30,6 +38,11 @@ pub(super) enum LazyValue {
     Variable(LazyVariable),
     ScopedVariable(LazyScopedVariable),
     Call(LazyCall),
+    Comparison(LazyComparison),
+    Logical(LazyLogical),
+    Not(LazyNot),
+    Edge(LazyEdge),
+    ListElement(LazyListElement),
 }
 
 impl From<Value> for LazyValue {
@@ -116,9 +129,55 @@ impl From<LazyCall> for LazyValue {
     }
 }
 
+impl From<LazyComparison> for LazyValue {
+    fn from(value: LazyComparison) -> Self {
+        LazyValue::Comparison(value)
+    }
+}
+
+impl From<LazyLogical> for LazyValue {
+    fn from(value: LazyLogical) -> Self {
+        LazyValue::Logical(value)
+    }
+}
+
+impl From<LazyNot> for LazyValue {
+    fn from(value: LazyNot) -> Self {
+        LazyValue::Not(value)
+    }
+}
+
+impl From<LazyEdge> for LazyValue {
+    fn from(value: LazyEdge) -> Self {
+        LazyValue::Edge(value)
+    }
+}
+
+impl From<LazyListElement> for LazyValue {
+    fn from(value: LazyListElement) -> Self {
+        LazyValue::ListElement(value)
+    }
+}
+
 impl LazyValue {
     pub(super) fn evaluate(&self, exec: &mut EvaluationContext) -> Result<Value, ExecutionError> {
         exec.cancellation_flag.check("evaluating value")?;
+        // Values can refer to variables whose values refer to other variables, and so on, and
+        // forcing such a chain recurses once per link.  That is not expression nesting, which
+        // was already limited when the values were created, so it has a limit of its own.
+        if exec.value_depth >= MAX_VALUE_DEPTH {
+            return Err(ExecutionError::ExceededRecursionDepth(format!(
+                "of {} in a chain of values",
+                MAX_VALUE_DEPTH
+            )));
+        }
+        exec.value_depth += 1;
+        let result = self.evaluate_value(exec);
+        exec.value_depth -= 1;
+        result
+    }
+
+    fn evaluate_value(&self, exec: &mut EvaluationContext) -> Result<Value, ExecutionError> {
         trace!("eval {} {{", self);
         let ret = match self {
             Self::Value(value) => Ok(value.clone()),
@@ -127,6 +186,11 @@ impl LazyValue {
             Self::Variable(expr) => expr.evaluate(exec),
             Self::ScopedVariable(expr) => expr.evaluate(exec),
             Self::Call(expr) => expr.evaluate(exec),
+            Self::Comparison(expr) => expr.evaluate(exec),
+            Self::Logical(expr) => expr.evaluate(exec),
+            Self::Not(expr) => expr.evaluate(exec),
+            Self::Edge(expr) => expr.evaluate(exec),
+            Self::ListElement(expr) => expr.evaluate(exec),
         }?;
         trace!("}} = {}", ret);
         Ok(ret)
@@ -164,6 +228,11 @@ impl fmt::Display for LazyValue {
             Self::Variable(expr) => expr.fmt(f),
             Self::ScopedVariable(expr) => expr.fmt(f),
             Self::Call(expr) => expr.fmt(f),
+            Self::Comparison(expr) => expr.fmt(f),
+            Self::Logical(expr) => expr.fmt(f),
+            Self::Not(expr) => expr.fmt(f),
+            Self::Edge(expr) => expr.fmt(f),
+            Self::ListElement(expr) => expr.fmt(f),
         }
     }
 }
@@ -291,6 +360,12 @@ impl LazyCall {
     }
 
     pub(super) fn evaluate(&self, exec: &mut EvaluationContext) -> Result<Value, ExecutionError> {
+        if exec.eager && exec.functions.reads_graph(&self.function)? {
+            return Err(ExecutionError::ExpectedLocalValue(format!(
+                "but function {} reads the graph",
+                self.function
+            )));
+        }
         for argument in &self.arguments {
             let argument = argument.evaluate(exec)?;
             exec.function_parameters.push(argument);
@@ -316,3 +391,165 @@ impl fmt::Display for LazyCall {
         write!(f, ")")
     }
 }
+
+/// Lazy comparison
+#[derive(Clone, Debug)]
+pub(super) struct LazyComparison {
+    operator: ComparisonOperator,
+    left: Box<LazyValue>,
+    right: Box<LazyValue>,
+}
+
+impl LazyComparison {
+    pub(super) fn new(operator: ComparisonOperator, left: LazyValue, right: LazyValue) -> Self {
+        Self {
+            operator,
+            left: left.into(),
+            right: right.into(),
+        }
+    }
+
+    pub(super) fn evaluate(&self, exec: &mut EvaluationContext) -> Result<Value, ExecutionError> {
+        let left = self.left.evaluate(exec)?;
+        let right = self.right.evaluate(exec)?;
+        Ok(self.operator.compare(&left, &right)?.into())
+    }
+}
+
+impl fmt::Display for LazyComparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "(compare '{} {} {})",
+            self.operator, self.left, self.right
+        )
+    }
+}
+
+/// Lazy short-circuiting boolean operation
+#[derive(Clone, Debug)]
+pub(super) struct LazyLogical {
+    operator: LogicalOperator,
+    left: Box<LazyValue>,
+    right: Box<LazyValue>,
+}
+
+impl LazyLogical {
+    pub(super) fn new(operator: LogicalOperator, left: LazyValue, right: LazyValue) -> Self {
+        Self {
+            operator,
+            left: left.into(),
+            right: right.into(),
+        }
+    }
+
+    pub(super) fn evaluate(&self, exec: &mut EvaluationContext) -> Result<Value, ExecutionError> {
+        let left = self.left.evaluate(exec)?.into_boolean()?;
+        let result = match (self.operator, left) {
+            (LogicalOperator::And, false) => false,
+            (LogicalOperator::Or, true) => true,
+            _ => self.right.evaluate(exec)?.into_boolean()?,
+        };
+        Ok(result.into())
+    }
+}
+
+impl fmt::Display for LazyLogical {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "(logical '{} {} {})",
+            self.operator, self.left, self.right
+        )
+    }
+}
+
+/// Lazy boolean negation
+#[derive(Clone, Debug)]
+pub(super) struct LazyNot {
+    value: Box<LazyValue>,
+}
+
+impl LazyNot {
+    pub(super) fn new(value: LazyValue) -> Self {
+        Self {
+            value: value.into(),
+        }
+    }
+
+    pub(super) fn evaluate(&self, exec: &mut EvaluationContext) -> Result<Value, ExecutionError> {
+        let value = self.value.evaluate(exec)?.into_boolean()?;
+        Ok((!value).into())
+    }
+}
+
+impl fmt::Display for LazyNot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(not {})", self.value)
+    }
+}
+
+/// Lazy reference to an edge
+#[derive(Clone, Debug)]
+pub(super) struct LazyEdge {
+    source: Box<LazyValue>,
+    sink: Box<LazyValue>,
+}
+
+impl LazyEdge {
+    pub(super) fn new(source: LazyValue, sink: LazyValue) -> Self {
+        Self {
+            source: source.into(),
+            sink: sink.into(),
+        }
+    }
+
+    pub(super) fn evaluate(&self, exec: &mut EvaluationContext) -> Result<Value, ExecutionError> {
+        let source = self.source.evaluate_as_graph_node(exec)?;
+        let sink = self.sink.evaluate_as_graph_node(exec)?;
+        Ok(Value::Edge(source, sink))
+    }
+}
+
+impl fmt::Display for LazyEdge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({} -> {})", self.source, self.sink)
+    }
+}
+
+/// Lazy element of a list that is unpacked into several variables
+#[derive(Clone, Debug)]
+pub(super) struct LazyListElement {
+    list: Box<LazyValue>,
+    index: usize,
+    length: usize,
+}
+
+impl LazyListElement {
+    pub(super) fn new(list: LazyValue, index: usize, length: usize) -> Self {
+        Self {
+            list: list.into(),
+            index,
+            length,
+        }
+    }
+
+    pub(super) fn evaluate(&self, exec: &mut EvaluationContext) -> Result<Value, ExecutionError> {
+        let mut values = self.list.evaluate(exec)?.into_list()?;
+        if values.len() != self.length {
+            return Err(ExecutionError::ListLengthMismatch(format!(
+                "expected {} elements, got {} in {}",
+                self.length,
+                values.len(),
+                self.list,
+            )));
+        }
+        Ok(values.swap_remove(self.index))
+    }
+}
+
+impl fmt::Display for LazyListElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(element {} of {})", self.index, self.list)
+    }
+}
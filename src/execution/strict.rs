@@ -7,9 +7,9 @@
 
 use std::collections::BTreeSet;
 use std::collections::HashMap;
+use tree_sitter::Node;
 use tree_sitter::QueryCursor;
 use tree_sitter::QueryMatch;
-use tree_sitter::Tree;
 
 use crate::ast::AddEdgeAttribute;
 use crate::ast::AddGraphNodeAttribute;
@@ -19,11 +19,15 @@ use crate::ast::AttributeShorthand;
 use crate::ast::AttributeShorthands;
 use crate::ast::Call;
 use crate::ast::Capture;
+use crate::ast::Comparison;
 use crate::ast::Condition;
 use crate::ast::CreateEdge;
 use crate::ast::CreateGraphNode;
 use crate::ast::DeclareImmutable;
 use crate::ast::DeclareMutable;
+use crate::ast::DeleteEdgeAttribute;
+use crate::ast::DeleteGraphNodeAttribute;
+use crate::ast::DestructureImmutable;
 use crate::ast::Expression;
 use crate::ast::File;
 use crate::ast::ForIn;
@@ -31,6 +35,9 @@ use crate::ast::If;
 use crate::ast::IntegerConstant;
 use crate::ast::ListComprehension;
 use crate::ast::ListLiteral;
+use crate::ast::Logical;
+use crate::ast::LogicalOperator;
+use crate::ast::Not;
 use crate::ast::Print;
 use crate::ast::RegexCapture;
 use crate::ast::Scan;
@@ -59,54 +66,131 @@ use crate::Location;
 
 impl File {
     /// Executes this graph DSL file against a source file, saving the results into an existing
-    /// `Graph` instance.  You must provide the parsed syntax tree (`tree`) as well as the source
-    /// text that it was parsed from (`source`).  You also provide the set of functions and global
-    /// variables that are available during execution. This variant is useful when you need to
-    /// “pre-seed” the graph with some predefined nodes and/or edges before executing the DSL file.
+    /// `Graph` instance.  You must provide the root of the parsed syntax tree, or of the subtree
+    /// to match against (`root`), as well as the source text that it was parsed from (`source`).
+    /// You also provide the set of functions and global variables that are available during
+    /// execution. This variant is useful when you need to “pre-seed” the graph with some
+    /// predefined nodes and/or edges before executing the DSL file.
     pub(super) fn execute_strict_into<'a, 'tree>(
         &self,
+        cursor: &mut QueryCursor,
         graph: &mut Graph<'tree>,
-        tree: &'tree Tree,
+        root: Node<'tree>,
         source: &'tree str,
         config: &ExecutionConfig,
         cancellation_flag: &dyn CancellationFlag,
-    ) -> Result<(), ExecutionError> {
+    ) -> Result<Vec<usize>, ExecutionError> {
         let mut globals = Globals::nested(config.globals);
         self.check_globals(&mut globals)?;
-        let mut config = ExecutionConfig {
-            functions: config.functions,
-            globals: &globals,
-            lazy: config.lazy,
-            location_attr: config.location_attr.clone(),
-            variable_name_attr: config.variable_name_attr.clone(),
-        };
+        let mut config = config.with_globals(&globals);
 
         let mut locals = VariableMap::new();
         let mut scoped = ScopedVariables::new();
         let current_regex_captures = Vec::new();
         let mut function_parameters = Vec::new();
+        let mut stanza_matches = Vec::with_capacity(self.stanzas.len());
+        let mut errors = Vec::new();
 
-        self.try_visit_matches_strict(tree, source, |stanza, mat| {
-            stanza.execute(
-                source,
-                &mat,
-                graph,
-                &mut config,
-                &mut locals,
-                &mut scoped,
-                &current_regex_captures,
-                &mut function_parameters,
-                &self.shorthands,
-                cancellation_flag,
-            )
-        })?;
+        for (stanza_index, stanza) in self.stanzas.iter().enumerate() {
+            if !config.is_stanza_selected(stanza_index) {
+                stanza_matches.push(0);
+                continue;
+            }
+            let mut matches = 0;
+            stanza.try_visit_matches_strict(cursor, root, source, |mat| {
+                if !config.is_match_selected(&mat) {
+                    return Ok(());
+                }
+                matches += 1;
+                stanza.check_match_limit(&config, matches)?;
+                stanza.execute(
+                    source,
+                    &mat,
+                    stanza_index,
+                    graph,
+                    &mut config,
+                    &mut locals,
+                    &mut scoped,
+                    &current_regex_captures,
+                    &mut function_parameters,
+                    &self.shorthands,
+                    &mut errors,
+                    cancellation_flag,
+                )
+            })?;
+            stanza_matches.push(matches);
+        }
+
+        if !errors.is_empty() {
+            return Err(ExecutionError::Multiple(errors));
+        }
+        Ok(stanza_matches)
+    }
+
+    /// Executes this graph DSL file against a source file, building a separate graph for each
+    /// query match, and passing it to `visit` as soon as the match has been executed.
+    pub(super) fn execute_strict_per_match<'tree, F>(
+        &self,
+        cursor: &mut QueryCursor,
+        root: Node<'tree>,
+        source: &'tree str,
+        config: &ExecutionConfig,
+        cancellation_flag: &dyn CancellationFlag,
+        mut visit: F,
+    ) -> Result<(), ExecutionError>
+    where
+        F: FnMut(Graph<'tree>) -> Result<(), ExecutionError>,
+    {
+        let mut globals = Globals::nested(config.globals);
+        self.check_globals(&mut globals)?;
+        let config = config.with_globals(&globals);
+
+        let mut locals = VariableMap::new();
+        let current_regex_captures = Vec::new();
+        let mut function_parameters = Vec::new();
+        let mut errors = Vec::new();
+
+        for (stanza_index, stanza) in self.stanzas.iter().enumerate() {
+            if !config.is_stanza_selected(stanza_index) {
+                continue;
+            }
+            let mut matches = 0;
+            stanza.try_visit_matches_strict(cursor, root, source, |mat| {
+                if !config.is_match_selected(&mat) {
+                    return Ok(());
+                }
+                matches += 1;
+                stanza.check_match_limit(&config, matches)?;
+                let mut graph = Graph::new();
+                let mut scoped = ScopedVariables::new();
+                stanza.execute(
+                    source,
+                    &mat,
+                    stanza_index,
+                    &mut graph,
+                    &config,
+                    &mut locals,
+                    &mut scoped,
+                    &current_regex_captures,
+                    &mut function_parameters,
+                    &self.shorthands,
+                    &mut errors,
+                    cancellation_flag,
+                )?;
+                visit(graph)
+            })?;
+        }
 
+        if !errors.is_empty() {
+            return Err(ExecutionError::Multiple(errors));
+        }
         Ok(())
     }
 
     pub(super) fn try_visit_matches_strict<'tree, E, F>(
         &self,
-        tree: &'tree Tree,
+        cursor: &mut QueryCursor,
+        root: Node<'tree>,
         source: &'tree str,
         mut visit: F,
     ) -> Result<(), E>
@@ -114,7 +198,7 @@ impl File {
         F: FnMut(&Stanza, QueryMatch<'_, 'tree>) -> Result<(), E>,
     {
         for stanza in &self.stanzas {
-            stanza.try_visit_matches_strict(tree, source, |mat| visit(stanza, mat))?;
+            stanza.try_visit_matches_strict(cursor, root, source, |mat| visit(stanza, mat))?;
         }
         Ok(())
     }
@@ -130,9 +214,14 @@ struct ExecutionContext<'a, 'c, 'g, 's, 'tree> {
     current_regex_captures: &'a Vec<String>,
     function_parameters: &'a mut Vec<Value>,
     mat: &'a QueryMatch<'a, 'tree>,
+    stanza_index: usize,
     error_context: StatementContext,
     shorthands: &'a AttributeShorthands,
     cancellation_flag: &'a dyn CancellationFlag,
+    /// The number of expressions that are currently being evaluated, one inside the other
+    expression_depth: usize,
+    /// The root node of the current match
+    full_match: Node<'tree>,
 }
 
 struct ScopedVariables<'a> {
@@ -156,6 +245,7 @@ impl Stanza {
         &self,
         source: &'tree str,
         mat: &QueryMatch<'_, 'tree>,
+        stanza_index: usize,
         graph: &mut Graph<'tree>,
         config: &ExecutionConfig<'_, 'g>,
         locals: &mut VariableMap<'l, Value>,
@@ -163,17 +253,16 @@ impl Stanza {
         current_regex_captures: &Vec<String>,
         function_parameters: &mut Vec<Value>,
         shorthands: &AttributeShorthands,
+        errors: &mut Vec<ExecutionError>,
         cancellation_flag: &dyn CancellationFlag,
     ) -> Result<(), ExecutionError> {
         locals.clear();
+        let node = mat
+            .nodes_for_capture_index(self.full_match_stanza_capture_index as u32)
+            .next()
+            .expect("missing full capture");
         for statement in &self.statements {
-            let error_context = {
-                let node = mat
-                    .nodes_for_capture_index(self.full_match_stanza_capture_index as u32)
-                    .next()
-                    .expect("missing full capture");
-                StatementContext::new(&statement, &self, &node)
-            };
+            let error_context = StatementContext::new(&statement, &self, &node);
             let mut exec = ExecutionContext {
                 source,
                 graph,
@@ -183,28 +272,39 @@ impl Stanza {
                 current_regex_captures,
                 function_parameters,
                 mat: &mat,
+                stanza_index,
                 error_context,
                 shorthands,
                 cancellation_flag,
+                expression_depth: 0,
+                full_match: node,
             };
-            statement
+            let result = statement
                 .execute(&mut exec)
-                .with_context(|| exec.error_context.into())?;
+                .with_context(|| exec.error_context.into());
+            match result {
+                Err(error)
+                    if config.collect_errors && !matches!(error, ExecutionError::Cancelled(_)) =>
+                {
+                    errors.push(error)
+                }
+                result => result?,
+            }
         }
         Ok(())
     }
 
     pub(super) fn try_visit_matches_strict<'tree, E, F>(
         &self,
-        tree: &'tree Tree,
+        cursor: &mut QueryCursor,
+        root: Node<'tree>,
         source: &'tree str,
         mut visit: F,
     ) -> Result<(), E>
     where
         F: FnMut(QueryMatch<'_, 'tree>) -> Result<(), E>,
     {
-        let mut cursor = QueryCursor::new();
-        let matches = cursor.matches(&self.query, tree.root_node(), source.as_bytes());
+        let matches = cursor.matches(&self.query, root, source.as_bytes());
         for mat in matches {
             visit(mat)?;
         }
@@ -216,12 +316,15 @@ impl Statement {
     pub fn location(&self) -> Location {
         match self {
             Statement::DeclareImmutable(s) => s.location,
+            Statement::DestructureImmutable(s) => s.location,
             Statement::DeclareMutable(s) => s.location,
             Statement::Assign(s) => s.location,
             Statement::CreateGraphNode(s) => s.location,
             Statement::AddGraphNodeAttribute(s) => s.location,
+            Statement::DeleteGraphNodeAttribute(s) => s.location,
             Statement::CreateEdge(s) => s.location,
             Statement::AddEdgeAttribute(s) => s.location,
+            Statement::DeleteEdgeAttribute(s) => s.location,
             Statement::Scan(s) => s.location,
             Statement::Print(s) => s.location,
             Statement::If(s) => s.location,
@@ -233,12 +336,15 @@ impl Statement {
         exec.cancellation_flag.check("executing statement")?;
         match self {
             Statement::DeclareImmutable(statement) => statement.execute(exec),
+            Statement::DestructureImmutable(statement) => statement.execute(exec),
             Statement::DeclareMutable(statement) => statement.execute(exec),
             Statement::Assign(statement) => statement.execute(exec),
             Statement::CreateGraphNode(statement) => statement.execute(exec),
             Statement::AddGraphNodeAttribute(statement) => statement.execute(exec),
+            Statement::DeleteGraphNodeAttribute(statement) => statement.execute(exec),
             Statement::CreateEdge(statement) => statement.execute(exec),
             Statement::AddEdgeAttribute(statement) => statement.execute(exec),
+            Statement::DeleteEdgeAttribute(statement) => statement.execute(exec),
             Statement::Scan(statement) => statement.execute(exec),
             Statement::Print(statement) => statement.execute(exec),
             Statement::If(statement) => statement.execute(exec),
@@ -254,6 +360,24 @@ impl DeclareImmutable {
     }
 }
 
+impl DestructureImmutable {
+    fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        let values = self.value.evaluate(exec)?.into_list()?;
+        if values.len() != self.variables.len() {
+            return Err(ExecutionError::ListLengthMismatch(format!(
+                "expected {} elements, got {} in {}",
+                self.variables.len(),
+                values.len(),
+                self,
+            )));
+        }
+        for (variable, value) in self.variables.iter().zip(values) {
+            variable.add(exec, value, false)?;
+        }
+        Ok(())
+    }
+}
+
 impl DeclareMutable {
     fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
         let value = self.value.evaluate(exec)?;
@@ -271,6 +395,9 @@ impl Assign {
 impl CreateGraphNode {
     fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
         let graph_node = exec.graph.add_graph_node();
+        if exec.config.record_locations {
+            exec.graph[graph_node].created_at = Some(self.location);
+        }
         self.node
             .add_debug_attrs(&mut exec.graph[graph_node].attributes, exec.config)?;
         let value = Value::GraphNode(graph_node);
@@ -282,19 +409,39 @@ impl AddGraphNodeAttribute {
     fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
         let node = self.node.evaluate(exec)?.into_graph_node_ref()?;
         let add_attribute = |exec: &mut ExecutionContext, name: Identifier, value: Value| {
-            exec.graph[node]
-                .attributes
-                .add(name.clone(), value)
-                .map_err(|_| {
-                    ExecutionError::DuplicateAttribute(format!(
-                        " {} on graph node ({}) in {}",
-                        name, node, self,
-                    ))
-                })
+            if self.skip_null && value.is_null() {
+                return Ok(());
+            }
+            let attributes = &mut exec.graph[node].attributes;
+            if self.overwrite || exec.config.overwrite_attributes {
+                attributes.set(name, value);
+                return Ok(());
+            }
+            attributes.add(name.clone(), value).map_err(|_| {
+                ExecutionError::DuplicateAttribute(format!(
+                    " {} on graph node ({}) in {}",
+                    name, node, self,
+                ))
+            })
         };
         for attribute in &self.attributes {
             attribute.execute(exec, &add_attribute)?;
         }
+        for spread in &self.spreads {
+            for (name, value) in spread.evaluate(exec)?.into_attribute_pairs()? {
+                add_attribute(exec, name, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DeleteGraphNodeAttribute {
+    fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        let node = self.node.evaluate(exec)?.into_graph_node_ref()?;
+        for name in &self.names {
+            exec.graph[node].attributes.remove(name);
+        }
         Ok(())
     }
 }
@@ -303,6 +450,12 @@ impl CreateEdge {
     fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
         let source = self.source.evaluate(exec)?.into_graph_node_ref()?;
         let sink = self.sink.evaluate(exec)?.into_graph_node_ref()?;
+        if exec.config.reject_self_edges && source == sink {
+            return Err(ExecutionError::SelfEdge(format!(
+                "({} -> {}) in {}",
+                source, sink, self,
+            )));
+        }
         let edge = match exec.graph[source].add_edge(sink) {
             Ok(edge) => edge,
             Err(_) => {
@@ -313,6 +466,12 @@ impl CreateEdge {
             }
         };
         self.add_debug_attrs(&mut edge.attributes, exec.config)?;
+        if exec.config.record_locations {
+            edge.created_at = Some(self.location);
+        }
+        if let Some(variable) = &self.edge {
+            variable.add(exec, Value::Edge(source, sink), false)?;
+        }
         Ok(())
     }
 }
@@ -322,6 +481,9 @@ impl AddEdgeAttribute {
         let source = self.source.evaluate(exec)?.into_graph_node_ref()?;
         let sink = self.sink.evaluate(exec)?.into_graph_node_ref()?;
         let add_attribute = |exec: &mut ExecutionContext, name: Identifier, value: Value| {
+            if self.skip_null && value.is_null() {
+                return Ok(());
+            }
             let edge = match exec.graph[source].get_edge_mut(sink) {
                 Some(edge) => Ok(edge),
                 None => Err(ExecutionError::UndefinedEdge(format!(
@@ -329,6 +491,10 @@ impl AddEdgeAttribute {
                     source, sink, self,
                 ))),
             }?;
+            if self.overwrite || exec.config.overwrite_attributes {
+                edge.attributes.set(name, value);
+                return Ok(());
+            }
             edge.attributes.add(name.clone(), value).map_err(|_| {
                 ExecutionError::DuplicateAttribute(format!(
                     " {} on edge ({} -> {}) in {}",
@@ -343,6 +509,24 @@ impl AddEdgeAttribute {
     }
 }
 
+impl DeleteEdgeAttribute {
+    fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        let source = self.source.evaluate(exec)?.into_graph_node_ref()?;
+        let sink = self.sink.evaluate(exec)?.into_graph_node_ref()?;
+        let edge = match exec.graph[source].get_edge_mut(sink) {
+            Some(edge) => Ok(edge),
+            None => Err(ExecutionError::UndefinedEdge(format!(
+                "({} -> {}) in {}",
+                source, sink, self,
+            ))),
+        }?;
+        for name in &self.names {
+            edge.attributes.remove(name);
+        }
+        Ok(())
+    }
+}
+
 impl Scan {
     fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
         let match_string = self.value.evaluate(exec)?.into_string()?;
@@ -370,23 +554,41 @@ impl Scan {
                 }
             }
 
-            if matches.is_empty() {
-                return Ok(());
-            }
-
             matches.sort_by_key(|(captures, index)| {
                 let range = captures.get(0).expect("missing regex capture").range();
                 (range.start, *index)
             });
 
-            let (regex_captures, block_index) = &matches[0];
-            let arm = &self.arms[*block_index];
+            // Any text before the earliest match (or all of the remaining text, if nothing
+            // matches) is handled by the default arm, if there is one.  Note that `i` always
+            // lands on a character boundary, since the regex crate only reports match offsets
+            // on UTF-8 character boundaries.
+            let unmatched_end = matches.first().map_or(match_string.len(), |(captures, _)| {
+                i + captures.get(0).expect("missing regex capture").start()
+            });
 
             let mut current_regex_captures = Vec::new();
-            for regex_capture in regex_captures.iter() {
-                current_regex_captures
-                    .push(regex_capture.map(|m| m.as_str()).unwrap_or("").to_string());
-            }
+            let (arm, statements) = match (&self.default_arm, matches.first()) {
+                (Some(default_arm), _) if unmatched_end > i => {
+                    current_regex_captures.push(match_string[i..unmatched_end].to_string());
+                    i = unmatched_end;
+                    (None, &default_arm.statements)
+                }
+                (_, Some((regex_captures, block_index))) => {
+                    let arm = &self.arms[*block_index];
+                    for regex_capture in regex_captures.iter() {
+                        current_regex_captures
+                            .push(regex_capture.map(|m| m.as_str()).unwrap_or("").to_string());
+                    }
+                    i += regex_captures
+                        .get(0)
+                        .expect("missing regex capture")
+                        .range()
+                        .end;
+                    (Some(arm), &arm.statements)
+                }
+                (_, None) => return Ok(()),
+            };
 
             let mut arm_locals = VariableMap::nested(exec.locals);
             let mut arm_exec = ExecutionContext {
@@ -398,26 +600,26 @@ impl Scan {
                 current_regex_captures: &current_regex_captures,
                 function_parameters: exec.function_parameters,
                 mat: exec.mat,
+                stanza_index: exec.stanza_index,
                 error_context: exec.error_context.clone(),
                 shorthands: exec.shorthands,
                 cancellation_flag: exec.cancellation_flag,
+                expression_depth: exec.expression_depth,
+                full_match: exec.full_match,
             };
 
-            for statement in &arm.statements {
+            for statement in statements {
                 arm_exec.error_context.update_statement(statement);
                 statement
                     .execute(&mut arm_exec)
-                    .with_context(|| {
-                        format!("matching {} with arm \"{}\"", match_string, arm.regex,).into()
+                    .with_context(|| match arm {
+                        Some(arm) => {
+                            format!("matching {} with arm \"{}\"", match_string, arm.regex,).into()
+                        }
+                        None => format!("matching {} with default arm", match_string).into(),
                     })
                     .with_context(|| arm_exec.error_context.clone().into())?;
             }
-
-            i += regex_captures
-                .get(0)
-                .expect("missing regex capture")
-                .range()
-                .end;
         }
 
         Ok(())
@@ -426,15 +628,14 @@ impl Scan {
 
 impl Print {
     fn execute(&self, exec: &mut ExecutionContext) -> Result<(), ExecutionError> {
+        let mut line = String::new();
         for value in &self.values {
-            if let Expression::StringConstant(expr) = value {
-                eprint!("{}", expr.value);
-            } else {
-                let value = value.evaluate(exec)?;
-                eprint!("{:?}", value);
+            match value.evaluate(exec)? {
+                Value::String(string) => line.push_str(&string),
+                value => line.push_str(&format!("{:?}", value)),
             }
         }
-        eprintln!();
+        exec.config.print(&line);
         Ok(())
     }
 }
@@ -457,9 +658,12 @@ impl If {
                     current_regex_captures: exec.current_regex_captures,
                     function_parameters: exec.function_parameters,
                     mat: exec.mat,
+                    stanza_index: exec.stanza_index,
                     error_context: exec.error_context.clone(),
                     shorthands: exec.shorthands,
                     cancellation_flag: exec.cancellation_flag,
+                    expression_depth: exec.expression_depth,
+                    full_match: exec.full_match,
                 };
                 for stmt in &arm.statements {
                     arm_exec.error_context.update_statement(stmt);
@@ -498,9 +702,12 @@ impl ForIn {
                 current_regex_captures: exec.current_regex_captures,
                 function_parameters: exec.function_parameters,
                 mat: exec.mat,
+                stanza_index: exec.stanza_index,
                 error_context: exec.error_context.clone(),
                 shorthands: exec.shorthands,
                 cancellation_flag: exec.cancellation_flag,
+                expression_depth: exec.expression_depth,
+                full_match: exec.full_match,
             };
             self.variable.add(&mut loop_exec, value, false)?;
             for stmt in &self.statements {
@@ -515,6 +722,16 @@ impl ForIn {
 
 impl Expression {
     fn evaluate(&self, exec: &mut ExecutionContext) -> Result<Value, ExecutionError> {
+        exec.expression_depth += 1;
+        let result = match exec.config.check_expression_depth(exec.expression_depth) {
+            Ok(()) => self.evaluate_nested(exec),
+            Err(e) => Err(e),
+        };
+        exec.expression_depth -= 1;
+        result
+    }
+
+    fn evaluate_nested(&self, exec: &mut ExecutionContext) -> Result<Value, ExecutionError> {
         match self {
             Expression::FalseLiteral => Ok(Value::Boolean(false)),
             Expression::NullLiteral => Ok(Value::Null),
@@ -528,6 +745,12 @@ impl Expression {
             Expression::Capture(expr) => expr.evaluate(exec),
             Expression::Variable(expr) => expr.evaluate(exec),
             Expression::Call(expr) => expr.evaluate(exec),
+            Expression::MatchFunction(expr) => {
+                Ok(expr.evaluate(exec.stanza_index, exec.full_match, exec.graph))
+            }
+            Expression::Comparison(expr) => expr.evaluate(exec),
+            Expression::Logical(expr) => expr.evaluate(exec),
+            Expression::Not(expr) => expr.evaluate(exec),
             Expression::RegexCapture(expr) => expr.evaluate(exec),
         }
     }
@@ -541,7 +764,7 @@ impl IntegerConstant {
 
 impl StringConstant {
     fn evaluate(&self, _exec: &mut ExecutionContext) -> Result<Value, ExecutionError> {
-        Ok(Value::String(self.value.clone()))
+        Ok(Value::String(self.value.clone().into()))
     }
 }
 
@@ -572,9 +795,12 @@ impl ListComprehension {
                 current_regex_captures: exec.current_regex_captures,
                 function_parameters: exec.function_parameters,
                 mat: exec.mat,
+                stanza_index: exec.stanza_index,
                 error_context: exec.error_context.clone(),
                 shorthands: exec.shorthands,
                 cancellation_flag: exec.cancellation_flag,
+                expression_depth: exec.expression_depth,
+                full_match: exec.full_match,
             };
             self.variable.add(&mut loop_exec, value, false)?;
             let element = self.element.evaluate(&mut loop_exec)?;
@@ -611,9 +837,12 @@ impl SetComprehension {
                 current_regex_captures: exec.current_regex_captures,
                 function_parameters: exec.function_parameters,
                 mat: exec.mat,
+                stanza_index: exec.stanza_index,
                 error_context: exec.error_context.clone(),
                 shorthands: exec.shorthands,
                 cancellation_flag: exec.cancellation_flag,
+                expression_depth: exec.expression_depth,
+                full_match: exec.full_match,
             };
             self.variable.add(&mut loop_exec, value, false)?;
             let element = self.element.evaluate(&mut loop_exec)?;
@@ -652,13 +881,40 @@ impl Call {
     }
 }
 
+impl Comparison {
+    fn evaluate(&self, exec: &mut ExecutionContext) -> Result<Value, ExecutionError> {
+        let left = self.left.evaluate(exec)?;
+        let right = self.right.evaluate(exec)?;
+        Ok(self.operator.compare(&left, &right)?.into())
+    }
+}
+
+impl Logical {
+    fn evaluate(&self, exec: &mut ExecutionContext) -> Result<Value, ExecutionError> {
+        let left = self.left.evaluate(exec)?.into_boolean()?;
+        let result = match (self.operator, left) {
+            (LogicalOperator::And, false) => false,
+            (LogicalOperator::Or, true) => true,
+            _ => self.right.evaluate(exec)?.into_boolean()?,
+        };
+        Ok(result.into())
+    }
+}
+
+impl Not {
+    fn evaluate(&self, exec: &mut ExecutionContext) -> Result<Value, ExecutionError> {
+        let value = self.value.evaluate(exec)?.into_boolean()?;
+        Ok((!value).into())
+    }
+}
+
 impl RegexCapture {
     fn evaluate(&self, exec: &mut ExecutionContext) -> Result<Value, ExecutionError> {
         let capture = exec
             .current_regex_captures
             .get(self.match_index)
             .ok_or(ExecutionError::UndefinedRegexCapture(format!("{}", self)))?;
-        Ok(Value::String(capture.clone()))
+        Ok(Value::String(capture.clone().into()))
     }
 }
 
@@ -713,8 +969,10 @@ impl ScopedVariable {
         if let Some(value) = variables.get(&self.name) {
             Ok(value)
         } else {
-            Err(ExecutionError::UndefinedVariable(format!(
-                "{} on node {}",
+            Err(ExecutionError::UndefinedScopedVariable(format!(
+                "{} on node {} (in strict execution, a scoped variable must be defined by an \
+                 earlier statement or stanza before it is used; lazy execution allows it to be \
+                 defined later)",
                 self, scope
             )))
         }
@@ -843,9 +1101,12 @@ impl AttributeShorthand {
             current_regex_captures: exec.current_regex_captures,
             function_parameters: exec.function_parameters,
             mat: exec.mat,
+            stanza_index: exec.stanza_index,
             error_context: exec.error_context.clone(),
             shorthands: exec.shorthands,
             cancellation_flag: exec.cancellation_flag,
+            expression_depth: exec.expression_depth,
+            full_match: exec.full_match,
         };
         self.variable.add(&mut shorthand_exec, value, false)?;
         for attr in &self.attributes {
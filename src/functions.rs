@@ -9,6 +9,7 @@
 
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::Mutex;
 
 use crate::execution::error::ExecutionError;
 use crate::graph::Graph;
@@ -23,6 +24,10 @@ use crate::Identifier;
 /// Any other data that you need must be passed in as a parameter to the function.  You can use the
 /// [`Parameters`][] trait to consume those parameters and verify that you received the correct
 /// number and type of them.
+///
+/// Functions are called through a shared reference, since a [`Functions`][] registry is shared by
+/// all executions that use it.  A function that needs to keep state between calls, such as a
+/// cache, must use interior mutability.
 pub trait Function {
     fn call(
         &self,
@@ -30,6 +35,37 @@ pub trait Function {
         source: &str,
         parameters: &mut dyn Parameters,
     ) -> Result<Value, ExecutionError>;
+
+    /// Calls this function as part of the library `functions`, which is how the graph DSL calls
+    /// it.  Functions that call other functions of the library, such as
+    /// [`sort-by`][`crate::reference::functions#sort-by`], override this method.  The default
+    /// implementation ignores the library and calls [`call`][Self::call].
+    fn call_in(
+        &self,
+        _functions: &Functions,
+        graph: &mut Graph,
+        source: &str,
+        parameters: &mut dyn Parameters,
+    ) -> Result<Value, ExecutionError> {
+        self.call(graph, source, parameters)
+    }
+
+    /// Returns whether this function reads the contents of the graph, such as the attributes of
+    /// graph nodes.  Lazy execution only builds the graph after all stanzas have been executed, so
+    /// such functions cannot be called where a local value is required, such as in conditions.
+    /// The default implementation returns `false`.
+    fn reads_graph(&self) -> bool {
+        false
+    }
+
+    /// Returns whether the result of this function only depends on the stanza match that it is
+    /// called from, and not on graph nodes or attributes created for other matches.  Only files
+    /// whose functions are all stanza-local can be executed in parallel by
+    /// [`File::execute_parallel`][crate::ast::File::execute_parallel].  The default
+    /// implementation returns `true` unless the function [reads the graph][Self::reads_graph].
+    fn is_stanza_local(&self) -> bool {
+        !self.reads_graph()
+    }
 }
 
 /// A helper trait for consuming the parameters of a function.  You will typically use it as
@@ -41,7 +77,7 @@ pub trait Function {
 /// # use tree_sitter_graph::graph::Value;
 /// # use tree_sitter_graph::ExecutionError;
 /// # fn main() -> Result<(), ExecutionError> {
-/// # let param_vec = vec![Value::String("test".to_string()), Value::Integer(42)];
+/// # let param_vec = vec![Value::from("test"), Value::Integer(42)];
 /// # let mut params = param_vec.into_iter();
 /// let first_param = params.param()?.into_string()?;
 /// let second_param = params.param()?.as_integer()?;
@@ -55,6 +91,11 @@ pub trait Parameters {
     /// that were passed in.
     fn param(&mut self) -> Result<Value, ExecutionError>;
 
+    /// Returns the next parameter, or `None` if you have exhausted all of the parameters that were
+    /// passed in.  Use this for optional trailing parameters, so that other errors are not
+    /// mistaken for a missing parameter.
+    fn optional_param(&mut self) -> Result<Option<Value>, ExecutionError>;
+
     /// Ensures that there are no more parameters to consume.
     fn finish(&mut self) -> Result<(), ExecutionError>;
 }
@@ -72,6 +113,10 @@ where
         Ok(value)
     }
 
+    fn optional_param(&mut self) -> Result<Option<Value>, ExecutionError> {
+        Ok(self.next())
+    }
+
     fn finish(&mut self) -> Result<(), ExecutionError> {
         let value = self.next();
         if value.is_some() {
@@ -83,10 +128,16 @@ where
     }
 }
 
+/// A callback that resolves functions that are not registered in a [`Functions`][] library.
+pub type FunctionResolver =
+    dyn FnMut(&Identifier) -> Option<Box<dyn Function + Send + Sync>> + Send;
+
 /// A library of named functions.
 #[derive(Default)]
 pub struct Functions {
     functions: HashMap<Identifier, Arc<dyn Function + Send + Sync>>,
+    fallback: Option<Mutex<Box<FunctionResolver>>>,
+    resolved: Mutex<HashMap<Identifier, Arc<dyn Function + Send + Sync>>>,
 }
 
 impl Functions {
@@ -102,12 +153,21 @@ impl Functions {
         // general functions
         functions.add(Identifier::from("eq"), stdlib::Eq);
         functions.add(Identifier::from("is-null"), stdlib::IsNull);
+        functions.add(Identifier::from("hash"), stdlib::Hash);
         // tree functions
+        functions.add(
+            Identifier::from("ancestor-of-kind"),
+            stdlib::syntax::AncestorOfKind,
+        );
         functions.add(
             Identifier::from("named-child-index"),
             stdlib::syntax::NamedChildIndex,
         );
         functions.add(Identifier::from("source-text"), stdlib::syntax::SourceText);
+        functions.add(
+            Identifier::from("source-text-dedent"),
+            stdlib::syntax::SourceTextDedent,
+        );
         functions.add(Identifier::from("start-row"), stdlib::syntax::StartRow);
         functions.add(
             Identifier::from("start-column"),
@@ -116,26 +176,73 @@ impl Functions {
         functions.add(Identifier::from("end-row"), stdlib::syntax::EndRow);
         functions.add(Identifier::from("end-column"), stdlib::syntax::EndColumn);
         functions.add(Identifier::from("node-type"), stdlib::syntax::NodeType);
+        functions.add(
+            Identifier::from("next-named-sibling"),
+            stdlib::syntax::NextNamedSibling,
+        );
+        functions.add(
+            Identifier::from("prev-named-sibling"),
+            stdlib::syntax::PrevNamedSibling,
+        );
         functions.add(
             Identifier::from("named-child-count"),
             stdlib::syntax::NamedChildCount,
         );
+        functions.add(Identifier::from("field-name"), stdlib::syntax::FieldName);
         // graph functions
         functions.add(Identifier::from("node"), stdlib::graph::Node);
+        functions.add(
+            Identifier::from("node-for-syntax"),
+            stdlib::graph::NodeForSyntax,
+        );
+        functions.add(Identifier::from("edge-source"), stdlib::graph::EdgeSource);
+        functions.add(Identifier::from("edge-sink"), stdlib::graph::EdgeSink);
+        functions.add(Identifier::from("get-attr"), stdlib::graph::GetAttr);
         // boolean functions
         functions.add(Identifier::from("not"), stdlib::bool::Not);
         functions.add(Identifier::from("and"), stdlib::bool::And);
         functions.add(Identifier::from("or"), stdlib::bool::Or);
         // math functions
         functions.add(Identifier::from("plus"), stdlib::math::Plus);
+        functions.add(Identifier::from("times"), stdlib::math::Times);
+        functions.add(Identifier::from("min"), stdlib::math::Min);
+        functions.add(Identifier::from("max"), stdlib::math::Max);
         // string functions
         functions.add(Identifier::from("format"), stdlib::string::Format);
+        functions.add(
+            Identifier::from("matches"),
+            stdlib::string::Matches::default(),
+        );
+        functions.add(Identifier::from("parse-int"), stdlib::string::ParseInt);
+        functions.add(Identifier::from("repeat"), stdlib::string::Repeat);
         functions.add(Identifier::from("replace"), stdlib::string::Replace);
+        functions.add(
+            Identifier::from("regex-replace"),
+            stdlib::string::RegexReplace::default(),
+        );
+        functions.add(Identifier::from("to-string"), stdlib::string::ToStr);
+        functions.add(
+            Identifier::from("string-length"),
+            stdlib::string::StringLength,
+        );
+        functions.add(Identifier::from("starts-with"), stdlib::string::StartsWith);
+        functions.add(Identifier::from("ends-with"), stdlib::string::EndsWith);
+        functions.add(Identifier::from("index-of"), stdlib::string::IndexOf);
+        functions.add(Identifier::from("trim"), stdlib::string::Trim);
+        functions.add(Identifier::from("trim-start"), stdlib::string::TrimStart);
+        functions.add(Identifier::from("trim-end"), stdlib::string::TrimEnd);
         // list functions
         functions.add(Identifier::from("concat"), stdlib::list::Concat);
         functions.add(Identifier::from("is-empty"), stdlib::list::IsEmpty);
         functions.add(Identifier::from("join"), stdlib::list::Join);
         functions.add(Identifier::from("length"), stdlib::list::Length);
+        functions.add(Identifier::from("reverse"), stdlib::list::Reverse);
+        functions.add(Identifier::from("sort"), stdlib::list::Sort);
+        functions.add(Identifier::from("sort-by"), stdlib::list::SortBy);
+        // set functions
+        functions.add(Identifier::from("union"), stdlib::set::Union);
+        functions.add(Identifier::from("intersect"), stdlib::set::Intersect);
+        functions.add(Identifier::from("difference"), stdlib::set::Difference);
         functions
     }
 
@@ -147,6 +254,19 @@ impl Functions {
         self.functions.insert(name, Arc::new(function));
     }
 
+    /// Sets a callback that resolves functions that have not been added to this library, which
+    /// lets you back the graph DSL with functions that are only known at call time.  The callback
+    /// is invoked the first time an unknown function is called.  Functions that it resolves are
+    /// cached, so it is called at most once for each of them; if it returns `None`, the call
+    /// fails with [`ExecutionError::UndefinedFunction`].
+    pub fn set_fallback<F>(&mut self, fallback: F)
+    where
+        F: FnMut(&Identifier) -> Option<Box<dyn Function + Send + Sync>> + Send + 'static,
+    {
+        self.fallback = Some(Mutex::new(Box::new(fallback)));
+        self.resolved.lock().unwrap().clear();
+    }
+
     /// Calls a named function, returning an error if there is no function with that name.
     pub fn call(
         &self,
@@ -155,11 +275,43 @@ impl Functions {
         source: &str,
         parameters: &mut dyn Parameters,
     ) -> Result<Value, ExecutionError> {
-        let function = self
-            .functions
-            .get(name)
-            .ok_or(ExecutionError::UndefinedFunction(format!("{}", name)))?;
-        function.call(graph, source, parameters)
+        self.get(name)?.call_in(self, graph, source, parameters)
+    }
+
+    /// Returns whether a named function reads the contents of the graph, returning an error if
+    /// there is no function with that name.  See [`Function::reads_graph`][].
+    pub fn reads_graph(&self, name: &Identifier) -> Result<bool, ExecutionError> {
+        Ok(self.get(name)?.reads_graph())
+    }
+
+    /// Returns whether a named function is stanza-local, returning an error if there is no
+    /// function with that name.  See [`Function::is_stanza_local`][].
+    pub fn is_stanza_local(&self, name: &Identifier) -> Result<bool, ExecutionError> {
+        Ok(self.get(name)?.is_stanza_local())
+    }
+
+    /// Returns a named function, resolving it with the fallback callback if it has not been added
+    /// to this library.
+    fn get(&self, name: &Identifier) -> Result<Arc<dyn Function + Send + Sync>, ExecutionError> {
+        if let Some(function) = self.functions.get(name) {
+            return Ok(function.clone());
+        }
+        self.resolve(name)
+            .ok_or(ExecutionError::UndefinedFunction(format!("{}", name)))
+    }
+
+    /// Resolves a function that has not been added to this library, using the fallback callback.
+    /// The resolved functions stay locked while the callback runs, so that concurrent executions
+    /// cannot resolve the same name twice.
+    fn resolve(&self, name: &Identifier) -> Option<Arc<dyn Function + Send + Sync>> {
+        let fallback = self.fallback.as_ref()?;
+        let mut resolved = self.resolved.lock().unwrap();
+        if let Some(function) = resolved.get(name) {
+            return Some(function.clone());
+        }
+        let function: Arc<dyn Function + Send + Sync> = (fallback.lock().unwrap())(name)?.into();
+        resolved.insert(name.clone(), function.clone());
+        Some(function)
     }
 }
 
@@ -170,8 +322,10 @@ pub mod stdlib {
     use crate::execution::error::ExecutionError;
     use crate::graph::Graph;
     use crate::graph::Value;
+    use crate::Identifier;
 
     use super::Function;
+    use super::Functions;
     use super::Parameters;
 
     /// The implementation of the standard [`eq`][`crate::reference::functions#eq`] function.
@@ -228,6 +382,13 @@ pub mod stdlib {
                     Value::GraphNode(right) => return Ok((left == right).into()),
                     _ => {}
                 },
+                Value::Edge(left_source, left_sink) => match &right {
+                    Value::Null => return Ok(false.into()),
+                    Value::Edge(right_source, right_sink) => {
+                        return Ok((left_source == right_source && left_sink == right_sink).into())
+                    }
+                    _ => {}
+                },
             };
             Err(ExecutionError::FunctionFailed(
                 "eq".into(),
@@ -239,6 +400,38 @@ pub mod stdlib {
         }
     }
 
+    /// The implementation of the standard [`hash`][`crate::reference::functions#hash`] function.
+    pub struct Hash;
+
+    impl Hash {
+        /// Computes the 32-bit FNV-1a hash of a byte string.
+        fn fnv1a(bytes: &[u8]) -> u32 {
+            let mut hash: u32 = 0x811c9dc5;
+            for byte in bytes {
+                hash ^= *byte as u32;
+                hash = hash.wrapping_mul(0x01000193);
+            }
+            hash
+        }
+    }
+
+    impl Function for Hash {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            let parameter = parameters.param()?;
+            parameters.finish()?;
+            let hash = match &parameter {
+                Value::String(text) => Self::fnv1a(text.as_bytes()),
+                _ => Self::fnv1a(parameter.to_string().as_bytes()),
+            };
+            Ok(Value::Integer(hash))
+        }
+    }
+
     /// The implementation of the standard [`is-null`][`crate::reference::functions#is-null`] function.
     pub struct IsNull;
 
@@ -302,6 +495,35 @@ pub mod stdlib {
         pub struct SourceText;
 
         impl Function for SourceText {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = parameters.param()?;
+                parameters.finish()?;
+                let source_text = |node: Value| -> Result<Value, ExecutionError> {
+                    let node = graph[node.into_syntax_node_ref()?];
+                    Ok(Value::String(source[node.byte_range()].to_string().into()))
+                };
+                match node {
+                    Value::List(nodes) => Ok(Value::List(
+                        nodes
+                            .into_iter()
+                            .map(source_text)
+                            .collect::<Result<_, _>>()?,
+                    )),
+                    node => source_text(node),
+                }
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`source-text-dedent`][`crate::reference::functions#source-text-dedent`] function.
+        pub struct SourceTextDedent;
+
+        impl Function for SourceTextDedent {
             fn call(
                 &self,
                 graph: &mut Graph,
@@ -310,7 +532,39 @@ pub mod stdlib {
             ) -> Result<Value, ExecutionError> {
                 let node = graph[parameters.param()?.into_syntax_node_ref()?];
                 parameters.finish()?;
-                Ok(Value::String(source[node.byte_range()].to_string()))
+                let range = node.byte_range();
+                // If the node is only preceded by whitespace on its first line, that whitespace is
+                // the indentation of the first line.  Otherwise, the first line is left as is.
+                let line_start = source[..range.start].rfind('\n').map_or(0, |i| i + 1);
+                let first_line_indented = source[line_start..range.start]
+                    .chars()
+                    .all(char::is_whitespace);
+                let (text, skip) = if first_line_indented {
+                    (&source[line_start..range.end], 0)
+                } else {
+                    (&source[range], 1)
+                };
+                let indentation = |line: &str| line.len() - line.trim_start().len();
+                let lines = text.split('\n').collect::<Vec<_>>();
+                let indent = lines
+                    .iter()
+                    .skip(skip)
+                    .filter(|line| !line.trim().is_empty())
+                    .map(|line| indentation(line))
+                    .min()
+                    .unwrap_or(0);
+                let dedented = lines
+                    .iter()
+                    .enumerate()
+                    .map(|(index, line)| {
+                        if index < skip {
+                            return *line;
+                        }
+                        let strip = indent.min(indentation(line));
+                        line.get(strip..).unwrap_or_else(|| line.trim_start())
+                    })
+                    .collect::<Vec<_>>();
+                Ok(dedented.join("\n").into())
             }
         }
 
@@ -396,7 +650,7 @@ pub mod stdlib {
             ) -> Result<Value, ExecutionError> {
                 let node = graph[parameters.param()?.into_syntax_node_ref()?];
                 parameters.finish()?;
-                Ok(Value::String(node.kind().to_string()))
+                Ok(Value::String(node.kind().to_string().into()))
             }
         }
 
@@ -417,6 +671,106 @@ pub mod stdlib {
                 Ok(Value::Integer(node.named_child_count() as u32))
             }
         }
+
+        /// The implementation of the standard
+        /// [`field-name`][`crate::reference::functions#field-name`] function.
+        pub struct FieldName;
+
+        impl Function for FieldName {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                parameters.finish()?;
+                let parent = match node.parent() {
+                    Some(parent) => parent,
+                    None => return Ok(Value::Null),
+                };
+                let mut cursor = parent.walk();
+                if cursor.goto_first_child() {
+                    loop {
+                        if cursor.node() == node {
+                            return Ok(match cursor.field_name() {
+                                Some(name) => name.into(),
+                                None => Value::Null,
+                            });
+                        }
+                        if !cursor.goto_next_sibling() {
+                            break;
+                        }
+                    }
+                }
+                Ok(Value::Null)
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`ancestor-of-kind`][`crate::reference::functions#ancestor-of-kind`] function.
+        pub struct AncestorOfKind;
+
+        impl Function for AncestorOfKind {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                let kind = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                let mut current = node.parent();
+                while let Some(ancestor) = current {
+                    if ancestor.kind() == kind {
+                        return Ok(graph.add_syntax_node(ancestor).into());
+                    }
+                    current = ancestor.parent();
+                }
+                Ok(Value::Null)
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`next-named-sibling`][`crate::reference::functions#next-named-sibling`] function.
+        pub struct NextNamedSibling;
+
+        impl Function for NextNamedSibling {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                parameters.finish()?;
+                Ok(match node.next_named_sibling() {
+                    Some(sibling) => graph.add_syntax_node(sibling).into(),
+                    None => Value::Null,
+                })
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`prev-named-sibling`][`crate::reference::functions#prev-named-sibling`] function.
+        pub struct PrevNamedSibling;
+
+        impl Function for PrevNamedSibling {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = graph[parameters.param()?.into_syntax_node_ref()?];
+                parameters.finish()?;
+                Ok(match node.prev_named_sibling() {
+                    Some(sibling) => graph.add_syntax_node(sibling).into(),
+                    None => Value::Null,
+                })
+            }
+        }
     }
 
     pub mod graph {
@@ -437,6 +791,89 @@ pub mod stdlib {
                 Ok(Value::GraphNode(node))
             }
         }
+
+        /// The implementation of the standard
+        /// [`node-for-syntax`][`crate::reference::functions#node-for-syntax`] function.
+        pub struct NodeForSyntax;
+
+        impl Function for NodeForSyntax {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let syntax_node = parameters.param()?.into_syntax_node_ref()?;
+                parameters.finish()?;
+                let node = graph.graph_node_for_syntax_node(syntax_node);
+                Ok(Value::GraphNode(node))
+            }
+
+            fn is_stanza_local(&self) -> bool {
+                // Every match that asks for the graph node of a syntax node gets the same one
+                false
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`edge-source`][`crate::reference::functions#edge-source`] function.
+        pub struct EdgeSource;
+
+        impl Function for EdgeSource {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let (source, _) = parameters.param()?.into_edge()?;
+                parameters.finish()?;
+                Ok(Value::GraphNode(source))
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`edge-sink`][`crate::reference::functions#edge-sink`] function.
+        pub struct EdgeSink;
+
+        impl Function for EdgeSink {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let (_, sink) = parameters.param()?.into_edge()?;
+                parameters.finish()?;
+                Ok(Value::GraphNode(sink))
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`get-attr`][`crate::reference::functions#get-attr`] function.
+        pub struct GetAttr;
+
+        impl Function for GetAttr {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let node = parameters.param()?.into_graph_node_ref()?;
+                let name = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                Ok(graph[node]
+                    .attributes
+                    .get(name.as_str())
+                    .cloned()
+                    .unwrap_or(Value::Null))
+            }
+
+            fn reads_graph(&self) -> bool {
+                true
+            }
+        }
     }
 
     pub mod bool {
@@ -508,9 +945,72 @@ pub mod stdlib {
                 _source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
-                let mut result = 0;
+                let mut result: u32 = 0;
+                while let Ok(parameter) = parameters.param() {
+                    let value = parameter.as_integer()?;
+                    result = result.checked_add(value).ok_or_else(|| {
+                        ExecutionError::IntegerOverflow(format!("adding {} and {}", result, value))
+                    })?;
+                }
+                Ok(Value::Integer(result))
+            }
+        }
+
+        /// The implementation of the standard [`times`][`crate::reference::functions#times`] function.
+        pub struct Times;
+
+        impl Function for Times {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let mut result: u32 = 1;
+                while let Ok(parameter) = parameters.param() {
+                    let value = parameter.as_integer()?;
+                    result = result.checked_mul(value).ok_or_else(|| {
+                        ExecutionError::IntegerOverflow(format!(
+                            "multiplying {} and {}",
+                            result, value
+                        ))
+                    })?;
+                }
+                Ok(Value::Integer(result))
+            }
+        }
+
+        /// The implementation of the standard [`min`][`crate::reference::functions#min`] function.
+        pub struct Min;
+
+        impl Function for Min {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let mut result = parameters.param()?.as_integer()?;
+                while let Ok(parameter) = parameters.param() {
+                    result = result.min(parameter.as_integer()?);
+                }
+                Ok(Value::Integer(result))
+            }
+        }
+
+        /// The implementation of the standard [`max`][`crate::reference::functions#max`] function.
+        pub struct Max;
+
+        impl Function for Max {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let mut result = parameters.param()?.as_integer()?;
                 while let Ok(parameter) = parameters.param() {
-                    result += parameter.as_integer()?;
+                    result = result.max(parameter.as_integer()?);
                 }
                 Ok(Value::Integer(result))
             }
@@ -518,6 +1018,9 @@ pub mod stdlib {
     }
 
     pub mod string {
+        use std::collections::HashMap;
+        use std::sync::Mutex;
+
         use super::*;
 
         /// The implementation of the standard [`format`][`crate::reference::functions#format`] function.
@@ -557,6 +1060,119 @@ pub mod stdlib {
             }
         }
 
+        /// The implementation of the standard [`matches`][`crate::reference::functions#matches`] function.
+        /// Recently used regular expressions are cached, so that they are not compiled on every
+        /// call.
+        #[derive(Default)]
+        pub struct Matches {
+            cache: RegexCache,
+        }
+
+        impl Function for Matches {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let text = parameters.param()?.into_string()?;
+                let pattern = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                let regex = self.cache.get(pattern, "matches")?;
+                Ok(Value::Boolean(regex.is_match(&text)))
+            }
+        }
+
+        /// The maximum number of compiled regular expressions that a [`RegexCache`][] holds
+        const MAX_CACHED_REGEXES: usize = 64;
+
+        /// A cache of compiled regular expressions, keyed by pattern.  The cache holds at most
+        /// [`MAX_CACHED_REGEXES`][] regular expressions, and is emptied when it is full, so that
+        /// patterns computed from the source cannot make it grow without bound.
+        #[derive(Default)]
+        struct RegexCache(Mutex<HashMap<String, Regex>>);
+
+        impl RegexCache {
+            /// Returns the compiled regular expression for `pattern`, compiling it and adding it
+            /// to the cache if it is not cached yet.  The lock is not held while compiling or
+            /// while the caller uses the regular expression.
+            fn get(&self, pattern: String, function: &str) -> Result<Regex, ExecutionError> {
+                if let Some(regex) = self.0.lock().unwrap().get(&pattern) {
+                    return Ok(regex.clone());
+                }
+                let regex = Regex::new(&pattern).map_err(|e| {
+                    ExecutionError::InvalidParameters(format!(
+                        "invalid regular expression `{}` in {}: {}",
+                        pattern, function, e
+                    ))
+                })?;
+                let mut cache = self.0.lock().unwrap();
+                if cache.len() >= MAX_CACHED_REGEXES {
+                    cache.clear();
+                }
+                cache.insert(pattern, regex.clone());
+                Ok(regex)
+            }
+        }
+
+        /// The implementation of the standard [`parse-int`][`crate::reference::functions#parse-int`] function.
+        pub struct ParseInt;
+
+        impl Function for ParseInt {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let text = parameters.param()?.into_string()?;
+                let radix = match parameters.optional_param()? {
+                    Some(radix) => radix.as_integer()?,
+                    None => 10,
+                };
+                parameters.finish()?;
+                if !(2..=36).contains(&radix) {
+                    return Err(ExecutionError::InvalidParameters(format!(
+                        "radix {} in parse-int must be between 2 and 36",
+                        radix
+                    )));
+                }
+                let value = u32::from_str_radix(&text, radix).map_err(|e| {
+                    ExecutionError::InvalidParameters(format!(
+                        "cannot parse `{}` as an integer in parse-int: {}",
+                        text, e
+                    ))
+                })?;
+                Ok(Value::Integer(value))
+            }
+        }
+
+        /// The implementation of the standard
+        /// [`regex-replace`][`crate::reference::functions#regex-replace`] function.  Recently
+        /// used regular expressions are cached, so that they are not compiled on every call.
+        #[derive(Default)]
+        pub struct RegexReplace {
+            cache: RegexCache,
+        }
+
+        impl Function for RegexReplace {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let text = parameters.param()?.into_string()?;
+                let pattern = parameters.param()?.into_string()?;
+                let replacement = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                let regex = self.cache.get(pattern, "regex-replace")?;
+                Ok(Value::String(
+                    regex.replace_all(&text, replacement).to_string().into(),
+                ))
+            }
+        }
+
         /// The implementation of the standard [`replace`][`crate::reference::functions#replace`] function.
         pub struct Replace;
 
@@ -575,10 +1191,174 @@ pub mod stdlib {
                 let replacement = parameters.param()?.into_string()?;
                 parameters.finish()?;
                 Ok(Value::String(
-                    pattern.replace_all(&text, replacement).to_string(),
+                    pattern.replace_all(&text, replacement).to_string().into(),
                 ))
             }
         }
+
+        /// The implementation of the standard [`string-length`][`crate::reference::functions#string-length`] function.
+        pub struct StringLength;
+
+        impl Function for StringLength {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let text = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                Ok(Value::Integer(text.chars().count() as u32))
+            }
+        }
+
+        /// The implementation of the standard [`repeat`][`crate::reference::functions#repeat`] function.
+        pub struct Repeat;
+
+        impl Repeat {
+            /// The maximum length, in bytes, of a string produced by `repeat`.
+            pub const MAX_LENGTH: usize = 1 << 20;
+        }
+
+        impl Function for Repeat {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let text = parameters.param()?.into_string()?;
+                let count = parameters.param()?.into_integer()? as usize;
+                parameters.finish()?;
+                match text.len().checked_mul(count) {
+                    Some(length) if length <= Self::MAX_LENGTH => Ok(text.repeat(count).into()),
+                    _ => Err(ExecutionError::InvalidParameters(format!(
+                        "repeating a string of length {} {} times exceeds the maximum length of {}",
+                        text.len(),
+                        count,
+                        Self::MAX_LENGTH
+                    ))),
+                }
+            }
+        }
+
+        /// The implementation of the standard [`trim`][`crate::reference::functions#trim`] function.
+        pub struct Trim;
+
+        impl Function for Trim {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let text = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                Ok(text.trim().into())
+            }
+        }
+
+        /// The implementation of the standard [`trim-start`][`crate::reference::functions#trim-start`] function.
+        pub struct TrimStart;
+
+        impl Function for TrimStart {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let text = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                Ok(text.trim_start().into())
+            }
+        }
+
+        /// The implementation of the standard [`trim-end`][`crate::reference::functions#trim-end`] function.
+        pub struct TrimEnd;
+
+        impl Function for TrimEnd {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let text = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                Ok(text.trim_end().into())
+            }
+        }
+
+        /// The implementation of the standard [`starts-with`][`crate::reference::functions#starts-with`] function.
+        pub struct StartsWith;
+
+        impl Function for StartsWith {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let text = parameters.param()?.into_string()?;
+                let prefix = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                Ok(Value::Boolean(text.starts_with(&prefix)))
+            }
+        }
+
+        /// The implementation of the standard [`ends-with`][`crate::reference::functions#ends-with`] function.
+        pub struct EndsWith;
+
+        impl Function for EndsWith {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let text = parameters.param()?.into_string()?;
+                let suffix = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                Ok(Value::Boolean(text.ends_with(&suffix)))
+            }
+        }
+
+        /// The implementation of the standard [`index-of`][`crate::reference::functions#index-of`] function.
+        pub struct IndexOf;
+
+        impl Function for IndexOf {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let text = parameters.param()?.into_string()?;
+                let needle = parameters.param()?.into_string()?;
+                parameters.finish()?;
+                Ok(match text.find(&needle) {
+                    Some(byte_index) => Value::Integer(text[..byte_index].chars().count() as u32),
+                    None => Value::Null,
+                })
+            }
+        }
+
+        /// The implementation of the standard [`to-string`][`crate::reference::functions#to-string`] function.
+        pub struct ToStr;
+
+        impl Function for ToStr {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let value = parameters.param()?;
+                parameters.finish()?;
+                Ok(Value::String(value.to_string().into()))
+            }
+        }
     }
 
     pub mod list {
@@ -612,8 +1392,20 @@ pub mod stdlib {
                 _source: &str,
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
-                let list = parameters.param()?.into_list()?;
-                Ok(list.is_empty().into())
+                let value = parameters.param()?;
+                parameters.finish()?;
+                let result = match &value {
+                    Value::List(list) => list.is_empty(),
+                    Value::Set(set) => set.is_empty(),
+                    Value::String(string) => string.is_empty(),
+                    _ => {
+                        return Err(ExecutionError::ExpectedList(format!(
+                            "(or a set or string) got {}",
+                            value
+                        )))
+                    }
+                };
+                Ok(result.into())
             }
         }
 
@@ -628,9 +1420,9 @@ pub mod stdlib {
                 parameters: &mut dyn Parameters,
             ) -> Result<Value, ExecutionError> {
                 let list = parameters.param()?.into_list()?;
-                let sep = match parameters.param() {
-                    Ok(sep) => sep.into_string()?,
-                    Err(_) => "".to_string(),
+                let sep = match parameters.optional_param()? {
+                    Some(sep) => sep.into_string()?,
+                    None => "".to_string(),
                 };
                 parameters.finish()?;
                 let result = list
@@ -656,5 +1448,174 @@ pub mod stdlib {
                 Ok((list.len() as u32).into())
             }
         }
+
+        /// The implementation of the standard [`reverse`][`crate::reference::functions#reverse`] function.
+        pub struct Reverse;
+
+        impl Function for Reverse {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let mut list = parameters.param()?.into_list()?;
+                parameters.finish()?;
+                list.reverse();
+                Ok(list.into())
+            }
+        }
+
+        /// The implementation of the standard [`sort`][`crate::reference::functions#sort`] function.
+        pub struct Sort;
+
+        impl Function for Sort {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let mut list = parameters.param()?.into_list()?;
+                parameters.finish()?;
+                if let Some(first) = list.first() {
+                    let kind = std::mem::discriminant(first);
+                    if let Some(other) = list.iter().find(|v| std::mem::discriminant(*v) != kind) {
+                        return Err(ExecutionError::InvalidParameters(format!(
+                            "Cannot sort values of different types: {} and {}",
+                            first, other
+                        )));
+                    }
+                }
+                list.sort();
+                Ok(list.into())
+            }
+        }
+
+        /// The implementation of the standard [`sort-by`][`crate::reference::functions#sort-by`]
+        /// function.  It calls the key function from the library that it is called from; called
+        /// on its own, it has no other functions to call.
+        pub struct SortBy;
+
+        impl Function for SortBy {
+            fn call(
+                &self,
+                graph: &mut Graph,
+                source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                self.call_in(&Functions::new(), graph, source, parameters)
+            }
+
+            fn call_in(
+                &self,
+                functions: &Functions,
+                graph: &mut Graph,
+                source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let list = parameters.param()?.into_list()?;
+                let key_function = Identifier::from(parameters.param()?.into_string()?.as_str());
+                parameters.finish()?;
+                let mut keyed = Vec::with_capacity(list.len());
+                for value in list {
+                    let key = functions.call(
+                        &key_function,
+                        graph,
+                        source,
+                        &mut std::iter::once(value.clone()),
+                    )?;
+                    keyed.push((key, value));
+                }
+                if let Some((first, _)) = keyed.first() {
+                    let kind = std::mem::discriminant(first);
+                    if let Some((other, _)) = keyed
+                        .iter()
+                        .find(|(k, _)| std::mem::discriminant(k) != kind)
+                    {
+                        return Err(ExecutionError::InvalidParameters(format!(
+                            "Cannot sort by keys of different types: {} and {}",
+                            first, other
+                        )));
+                    }
+                }
+                keyed.sort_by(|(left, _), (right, _)| left.cmp(right));
+                Ok(keyed
+                    .into_iter()
+                    .map(|(_, value)| value)
+                    .collect::<Vec<_>>()
+                    .into())
+            }
+
+            fn is_stanza_local(&self) -> bool {
+                // The key function is only known once we're called, so it might not be local
+                false
+            }
+        }
+    }
+
+    pub mod set {
+        use std::collections::BTreeSet;
+
+        use super::*;
+
+        /// The implementation of the standard [`union`][`crate::reference::functions#union`] function.
+        pub struct Union;
+
+        impl Function for Union {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let left = parameters.param()?.into_set()?;
+                let right = parameters.param()?.into_set()?;
+                parameters.finish()?;
+                Ok(left.union(&right).cloned().collect::<BTreeSet<_>>().into())
+            }
+        }
+
+        /// The implementation of the standard [`intersect`][`crate::reference::functions#intersect`] function.
+        pub struct Intersect;
+
+        impl Function for Intersect {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let left = parameters.param()?.into_set()?;
+                let right = parameters.param()?.into_set()?;
+                parameters.finish()?;
+                Ok(left
+                    .intersection(&right)
+                    .cloned()
+                    .collect::<BTreeSet<_>>()
+                    .into())
+            }
+        }
+
+        /// The implementation of the standard [`difference`][`crate::reference::functions#difference`] function.
+        pub struct Difference;
+
+        impl Function for Difference {
+            fn call(
+                &self,
+                _graph: &mut Graph,
+                _source: &str,
+                parameters: &mut dyn Parameters,
+            ) -> Result<Value, ExecutionError> {
+                let left = parameters.param()?.into_set()?;
+                let right = parameters.param()?.into_set()?;
+                parameters.finish()?;
+                Ok(left
+                    .difference(&right)
+                    .cloned()
+                    .collect::<BTreeSet<_>>()
+                    .into())
+            }
+        }
     }
 }
@@ -11,14 +11,18 @@ use std::borrow::Borrow;
 use std::collections::hash_map::Entry;
 use std::collections::BTreeSet;
 use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
 use std::fmt;
 use std::fs::File;
 use std::hash::Hash;
 use std::io::prelude::*;
 use std::io::stdout;
+use std::num::TryFromIntError;
 use std::ops::Index;
 use std::ops::IndexMut;
 use std::path::Path;
+use std::sync::Arc;
 
 use serde::ser::SerializeMap;
 use serde::ser::SerializeSeq;
@@ -26,7 +30,9 @@ use serde::Serialize;
 use serde::Serializer;
 use serde_json;
 use smallvec::SmallVec;
+use thiserror::Error;
 use tree_sitter::Node;
+use tree_sitter::Tree;
 
 use crate::execution::error::ExecutionError;
 use crate::Identifier;
@@ -34,21 +40,311 @@ use crate::Location;
 
 /// A graph produced by executing a graph DSL file.  Graphs include a lifetime parameter to ensure
 /// that they don't outlive the tree-sitter syntax tree that they are generated from.
-#[derive(Default)]
+///
+/// The textual and JSON output of a graph is deterministic: graph nodes and edges are output in
+/// index order, attributes are output sorted by name, and syntax nodes are only output as part of
+/// attribute values that refer to them, never by iterating over the graph's syntax nodes.
+///
+/// Graphs are neither `Send` nor `Sync`, since they hold on to tree-sitter [`Node`][]s, which
+/// cannot be shared across threads.  To process several source files in parallel, parse each file
+/// and execute the graph DSL file against it on its own thread.  (The parsed
+/// [`File`][crate::ast::File] and the [`Functions`][crate::functions::Functions] can be shared
+/// between threads.)  To execute the stanzas of a single file in parallel, use
+/// [`File::execute_parallel`][crate::ast::File::execute_parallel].
+#[derive(Clone, Default)]
 pub struct Graph<'tree> {
     syntax_nodes: HashMap<SyntaxNodeID, Node<'tree>>,
     graph_nodes: Vec<GraphNode>,
+    removed_graph_nodes: usize,
+    graph_nodes_for_syntax_nodes: HashMap<SyntaxNodeID, GraphNodeRef>,
 }
 
 type SyntaxNodeID = u32;
 type GraphNodeID = u32;
 
+/// The options for pretty-printing a graph, created using [`Graph::pretty_print`][] or
+/// [`OwnedGraph::pretty_print`][].  By default, graph nodes are output in index order, using the
+/// [`DefaultNodeRenderer`][], with attributes indented by two spaces.  Use the builder methods to
+/// change any of these, and format the result to get the output.
+#[derive(Clone, Copy)]
+pub struct PrettyPrint<'a> {
+    graph_nodes: &'a [GraphNode],
+    renderer: &'a dyn NodeRenderer,
+    sort_by: Option<&'a Identifier>,
+    by_first_reference: bool,
+    indent: usize,
+    separate_nodes: bool,
+}
+
+impl<'a> PrettyPrint<'a> {
+    fn new(graph_nodes: &'a [GraphNode]) -> Self {
+        PrettyPrint {
+            graph_nodes,
+            renderer: &DefaultNodeRenderer,
+            sort_by: None,
+            by_first_reference: false,
+            indent: 2,
+            separate_nodes: false,
+        }
+    }
+
+    /// Uses `renderer` to format graph nodes, syntax nodes, and strings.
+    pub fn renderer(self, renderer: &'a dyn NodeRenderer) -> Self {
+        PrettyPrint { renderer, ..self }
+    }
+
+    /// Orders graph nodes by the value of the attribute called `name` instead of by index.  Graph
+    /// nodes with equal values, or without the attribute, are ordered by index, after all graph
+    /// nodes that have it.  Graph nodes are still identified by their index in the output, unless
+    /// [`by_first_reference`][Self::by_first_reference] is also set.
+    pub fn sort_by(self, name: &'a Identifier) -> Self {
+        PrettyPrint {
+            sort_by: Some(name),
+            ..self
+        }
+    }
+
+    /// Orders and numbers graph nodes by when the output first refers to them.  After each graph
+    /// node, the graph nodes it refers to via its outgoing edges and its attribute values are
+    /// output, if they have not been already, before continuing with the next graph node in index
+    /// order, or in the order given by [`sort_by`][Self::sort_by] if that is set.  Graph nodes
+    /// are identified by their position in the output, both in `node` and `edge` lines and in
+    /// attribute values; the graph itself is not changed.  References to graph nodes that are not
+    /// output, because they have been removed, are numbered after all graph nodes that are.
+    pub fn by_first_reference(self, by_first_reference: bool) -> Self {
+        PrettyPrint {
+            by_first_reference,
+            ..self
+        }
+    }
+
+    /// Indents attributes by `indent` spaces instead of two.
+    pub fn indent(self, indent: usize) -> Self {
+        PrettyPrint { indent, ..self }
+    }
+
+    /// Separates each graph node, along with its outgoing edges, from the next one by a blank
+    /// line.
+    pub fn separate_nodes(self, separate_nodes: bool) -> Self {
+        PrettyPrint {
+            separate_nodes,
+            ..self
+        }
+    }
+
+    /// Returns the graph nodes in the order in which the output first refers to them, along with
+    /// the number of every graph node that the output refers to.  Starting from each of `roots`
+    /// that has not been output yet, graph nodes are output breadth-first, following outgoing
+    /// edges and graph node attribute values.
+    fn first_reference_order(
+        &self,
+        roots: &[(usize, &'a GraphNode)],
+    ) -> (
+        Vec<(usize, &'a GraphNode)>,
+        HashMap<GraphNodeID, GraphNodeID>,
+    ) {
+        let graph_nodes = self.graph_nodes;
+        let mut seen = graph_nodes
+            .iter()
+            .map(|node| node.removed)
+            .collect::<Vec<_>>();
+        let mut order = Vec::new();
+        let mut unreachable = BTreeSet::new();
+        let mut queue = VecDeque::new();
+        for (root, _) in roots {
+            if seen[*root] {
+                continue;
+            }
+            seen[*root] = true;
+            queue.push_back(*root);
+            while let Some(node_index) = queue.pop_front() {
+                let node = &graph_nodes[node_index];
+                order.push((node_index, node));
+                let mut references = node
+                    .outgoing_edges
+                    .iter()
+                    .map(|(sink, _)| GraphNodeRef(*sink))
+                    .collect::<Vec<_>>();
+                let mut names = node.attributes.values.keys().collect::<Vec<_>>();
+                names.sort();
+                for name in names {
+                    node.attributes.values[name].collect_graph_nodes(&mut references);
+                }
+                for (_, edge) in &node.outgoing_edges {
+                    for value in edge.attributes.values.values() {
+                        value.collect_graph_nodes(&mut references);
+                    }
+                }
+                for reference in references {
+                    match graph_nodes.get(reference.index()) {
+                        Some(node) if !node.removed => {
+                            if !seen[reference.index()] {
+                                seen[reference.index()] = true;
+                                queue.push_back(reference.index());
+                            }
+                        }
+                        _ => {
+                            unreachable.insert(reference.0);
+                        }
+                    }
+                }
+            }
+        }
+        let numbers = order
+            .iter()
+            .map(|(node_index, _)| *node_index as GraphNodeID)
+            .chain(unreachable)
+            .enumerate()
+            .map(|(position, node_index)| (node_index, position as GraphNodeID))
+            .collect();
+        (order, numbers)
+    }
+}
+
+impl fmt::Display for PrettyPrint<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let renderer = self.renderer;
+        let mut nodes = self
+            .graph_nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| !node.removed)
+            .collect::<Vec<_>>();
+        if let Some(name) = self.sort_by {
+            // Nodes without the attribute come last; ties keep index order.
+            nodes.sort_by_key(|(node_index, node)| {
+                let value = node.attributes.get(name);
+                (value.is_none(), value, *node_index)
+            });
+        }
+        let renumbered;
+        let renderer: &dyn NodeRenderer = if self.by_first_reference {
+            let (order, numbers) = self.first_reference_order(&nodes);
+            nodes = order;
+            renumbered = RenumberedNodeRenderer { renderer, numbers };
+            &renumbered
+        } else {
+            renderer
+        };
+        for (position, (node_index, node)) in nodes.into_iter().enumerate() {
+            if self.separate_nodes && position > 0 {
+                writeln!(f)?;
+            }
+            let node_ref = GraphNodeRef(node_index as GraphNodeID);
+            write!(f, "node ")?;
+            renderer.fmt_graph_node_id(f, node_ref)?;
+            writeln!(f)?;
+            node.attributes.fmt_rendered(f, renderer, self.indent)?;
+            for (sink, edge) in &node.outgoing_edges {
+                write!(f, "edge ")?;
+                renderer.fmt_graph_node_id(f, node_ref)?;
+                write!(f, " -> ")?;
+                renderer.fmt_graph_node_id(f, GraphNodeRef(*sink))?;
+                writeln!(f)?;
+                edge.attributes.fmt_rendered(f, renderer, self.indent)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Controls how graph nodes, syntax nodes, and strings are printed when pretty-printing a graph using
+/// [`PrettyPrint::renderer`][].  Each method defaults to the format used by
+/// [`Graph::pretty_print`][], so you only need to override the ones you want to change.
+pub trait NodeRenderer {
+    /// Formats the id of a graph node, as used in the `node` and `edge` lines of the output.
+    fn fmt_graph_node_id(&self, f: &mut fmt::Formatter, node: GraphNodeRef) -> fmt::Result {
+        write!(f, "{}", node.0)
+    }
+
+    /// Formats a reference to a graph node in an attribute value.
+    fn fmt_graph_node_ref(&self, f: &mut fmt::Formatter, node: GraphNodeRef) -> fmt::Result {
+        write!(f, "{}", node)
+    }
+
+    /// Formats a reference to a syntax node in an attribute value.
+    fn fmt_syntax_node_ref(&self, f: &mut fmt::Formatter, node: SyntaxNodeRef) -> fmt::Result {
+        write!(f, "{}", node)
+    }
+
+    /// Formats a string in an attribute value, including its quotes.  Defaults to Rust's escaping
+    /// of special characters.
+    fn fmt_string(&self, f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+        write!(f, "{:?}", value)
+    }
+}
+
+/// A [`NodeRenderer`][] that numbers graph nodes by their position in the output, instead of by
+/// their index, and otherwise defers to another renderer.  `numbers` must contain every graph node
+/// that the output refers to.
+struct RenumberedNodeRenderer<'a> {
+    renderer: &'a dyn NodeRenderer,
+    numbers: HashMap<GraphNodeID, GraphNodeID>,
+}
+
+impl RenumberedNodeRenderer<'_> {
+    fn renumber(&self, node: GraphNodeRef) -> Result<GraphNodeRef, fmt::Error> {
+        self.numbers
+            .get(&node.0)
+            .copied()
+            .map(GraphNodeRef)
+            .ok_or(fmt::Error)
+    }
+}
+
+impl NodeRenderer for RenumberedNodeRenderer<'_> {
+    fn fmt_graph_node_id(&self, f: &mut fmt::Formatter, node: GraphNodeRef) -> fmt::Result {
+        self.renderer.fmt_graph_node_id(f, self.renumber(node)?)
+    }
+
+    fn fmt_graph_node_ref(&self, f: &mut fmt::Formatter, node: GraphNodeRef) -> fmt::Result {
+        self.renderer.fmt_graph_node_ref(f, self.renumber(node)?)
+    }
+
+    fn fmt_syntax_node_ref(&self, f: &mut fmt::Formatter, node: SyntaxNodeRef) -> fmt::Result {
+        self.renderer.fmt_syntax_node_ref(f, node)
+    }
+
+    fn fmt_string(&self, f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+        self.renderer.fmt_string(f, value)
+    }
+}
+
+/// The [`NodeRenderer`][] used by [`Graph::pretty_print`][]
+pub struct DefaultNodeRenderer;
+
+impl NodeRenderer for DefaultNodeRenderer {}
+
+/// A [`NodeRenderer`][] that escapes strings the way JSON does, for consumers of the
+/// pretty-printed output that do not understand Rust's escaping.  Graph nodes and syntax nodes are
+/// printed as by [`Graph::pretty_print`][].
+pub struct JsonStringRenderer;
+
+impl NodeRenderer for JsonStringRenderer {
+    fn fmt_string(&self, f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+        let escaped = serde_json::to_string(value).map_err(|_| fmt::Error)?;
+        write!(f, "{}", escaped)
+    }
+}
+
 impl<'tree> Graph<'tree> {
     /// Creates a new, empty graph.
     pub fn new() -> Graph<'tree> {
         Graph::default()
     }
 
+    /// Creates a new, empty graph with room for at least the given number of graph nodes and
+    /// syntax nodes.  Use this with [`File::execute_into`][crate::ast::File::execute_into] when
+    /// you can estimate the size of the resulting graph, to avoid reallocations during execution.
+    pub fn with_capacity(graph_nodes: usize, syntax_nodes: usize) -> Graph<'tree> {
+        Graph {
+            syntax_nodes: HashMap::with_capacity(syntax_nodes),
+            graph_nodes: Vec::with_capacity(graph_nodes),
+            removed_graph_nodes: 0,
+            graph_nodes_for_syntax_nodes: HashMap::new(),
+        }
+    }
+
     /// Adds a syntax node to the graph, returning a graph DSL reference to it.
     ///
     /// The graph won't contain _every_ syntax node in the parsed syntax tree; it will only contain
@@ -72,24 +368,143 @@ impl<'tree> Graph<'tree> {
         GraphNodeRef(index)
     }
 
-    /// Pretty-prints the contents of this graph.
-    pub fn pretty_print<'a>(&'a self) -> impl fmt::Display + 'a {
-        struct DisplayGraph<'a, 'tree>(&'a Graph<'tree>);
+    /// Returns the graph node associated with a syntax node, creating it the first time it is
+    /// requested.  Every later request for the same syntax node returns the same graph node, for
+    /// as long as that graph node is not removed.
+    pub fn graph_node_for_syntax_node(&mut self, node: SyntaxNodeRef) -> GraphNodeRef {
+        if let Some(graph_node) = self.graph_nodes_for_syntax_nodes.get(&node.index) {
+            return *graph_node;
+        }
+        let graph_node = self.add_graph_node();
+        self.graph_nodes_for_syntax_nodes
+            .insert(node.index, graph_node);
+        graph_node
+    }
 
-        impl<'a, 'tree> fmt::Display for DisplayGraph<'a, 'tree> {
-            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-                let graph = self.0;
-                for (node_index, node) in graph.graph_nodes.iter().enumerate() {
-                    write!(f, "node {}\n{}", node_index, node.attributes)?;
-                    for (sink, edge) in &node.outgoing_edges {
-                        write!(f, "edge {} -> {}\n{}", node_index, *sink, edge.attributes)?;
-                    }
+    /// Merges the contents of another graph into this one.  The graph nodes of `other` are appended
+    /// after the graph nodes of this graph, and any references to them, in edges or in attribute
+    /// values, are updated accordingly.  Since the graph nodes of the two graphs remain distinct,
+    /// merging cannot cause any attribute or edge collisions.  If both graphs associate a graph
+    /// node with the same syntax node (see [`graph_node_for_syntax_node`][Self::graph_node_for_syntax_node]),
+    /// the association of this graph is kept.
+    pub fn merge(&mut self, other: Graph<'tree>) {
+        let offset = self.graph_nodes.len() as GraphNodeID;
+        for (index, node) in other.syntax_nodes {
+            self.syntax_nodes.entry(index).or_insert(node);
+        }
+        self.removed_graph_nodes += other.removed_graph_nodes;
+        self.graph_nodes.reserve(other.graph_nodes.len());
+        for mut node in other.graph_nodes {
+            for (sink, edge) in node.outgoing_edges.iter_mut() {
+                *sink += offset;
+                edge.attributes.offset_graph_nodes(offset);
+            }
+            node.attributes.offset_graph_nodes(offset);
+            self.graph_nodes.push(node);
+        }
+        for (index, graph_node) in other.graph_nodes_for_syntax_nodes {
+            self.graph_nodes_for_syntax_nodes
+                .entry(index)
+                .or_insert(GraphNodeRef(graph_node.0 + offset));
+        }
+    }
+
+    /// Detaches this graph from the syntax tree that it refers to, so that it can be sent to
+    /// another thread.  Use [`attach_all`][Self::attach_all] to turn it back into a graph.
+    pub(crate) fn detach(self) -> DetachedGraph {
+        DetachedGraph {
+            syntax_nodes: self.syntax_nodes.into_keys().collect(),
+            graph_nodes: self.graph_nodes,
+            removed_graph_nodes: self.removed_graph_nodes,
+            graph_nodes_for_syntax_nodes: self.graph_nodes_for_syntax_nodes,
+        }
+    }
+
+    /// Attaches detached graphs to the syntax tree that they were created from again, and merges
+    /// them, in order, into a single graph.  (See [`merge`][Self::merge].)  The tree is only
+    /// walked once, however many graphs there are.
+    pub(crate) fn attach_all(tree: &'tree Tree, detached: Vec<DetachedGraph>) -> Graph<'tree> {
+        let mut syntax_nodes = detached
+            .iter()
+            .flat_map(|graph| graph.syntax_nodes.iter())
+            .map(|index| (*index, None))
+            .collect::<HashMap<SyntaxNodeID, Option<Node<'tree>>>>();
+        let mut cursor = tree.walk();
+        'walk: loop {
+            let node = cursor.node();
+            if let Some(entry) = syntax_nodes.get_mut(&(node.id() as SyntaxNodeID)) {
+                *entry = Some(node);
+            }
+            if cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    break 'walk;
                 }
-                Ok(())
             }
         }
+        let mut result = Graph::new();
+        for graph in detached {
+            result.merge(Graph {
+                syntax_nodes: graph
+                    .syntax_nodes
+                    .iter()
+                    .map(|index| {
+                        let node = syntax_nodes[index].expect("syntax node is not in the tree");
+                        (*index, node)
+                    })
+                    .collect(),
+                graph_nodes: graph.graph_nodes,
+                removed_graph_nodes: graph.removed_graph_nodes,
+                graph_nodes_for_syntax_nodes: graph.graph_nodes_for_syntax_nodes,
+            });
+        }
+        result
+    }
 
-        DisplayGraph(self)
+    /// Removes a graph node from the graph, along with all of its incoming and outgoing edges.
+    ///
+    /// Removed nodes are tombstoned rather than compacted, so that the references to all other graph
+    /// nodes remain valid.  A removed node is no longer returned by [`iter_nodes`][Self::iter_nodes]
+    /// or included in the graph's output, but its reference is never reused.  Attribute values that
+    /// refer to the removed node are left as they are.
+    ///
+    /// Indexing the graph with the reference of a removed node still returns its tombstone, for
+    /// which [`GraphNode::is_removed`][] is true.  Edges and attributes added to the tombstone are
+    /// never output, but edges added from another graph node to the tombstone are, so check
+    /// `is_removed` before adding edges to a graph node that might have been removed.
+    pub fn remove_node(&mut self, node: GraphNodeRef) {
+        let removed = &mut self.graph_nodes[node.0 as usize];
+        if !removed.removed {
+            self.removed_graph_nodes += 1;
+        }
+        removed.outgoing_edges.clear();
+        removed.attributes = Attributes::new();
+        removed.removed = true;
+        self.graph_nodes_for_syntax_nodes
+            .retain(|_, graph_node| *graph_node != node);
+        for other in self.graph_nodes.iter_mut() {
+            if let Ok(index) = other
+                .outgoing_edges
+                .binary_search_by_key(&node.0, |(sink, _)| *sink)
+            {
+                other.outgoing_edges.remove(index);
+            }
+        }
+    }
+
+    /// Pretty-prints the contents of this graph.  The result can be formatted as is, or
+    /// configured first using the methods of [`PrettyPrint`][].
+    pub fn pretty_print(&self) -> PrettyPrint<'_> {
+        PrettyPrint::new(&self.graph_nodes)
+    }
+
+    /// Writes the pretty-printed contents of this graph to `writer`.  The output is the same as
+    /// that of [`pretty_print`][Graph::pretty_print], but is written directly to `writer` as it is
+    /// formatted, instead of being collected into a string first.
+    pub fn write_to(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        write!(writer, "{}", self.pretty_print())
     }
 
     pub fn display_json(&self, path: Option<&Path>) -> std::io::Result<()> {
@@ -100,13 +515,126 @@ impl<'tree> Graph<'tree> {
     }
 
     // Returns an iterator of references to all of the nodes in the graph.
-    pub fn iter_nodes(&self) -> impl Iterator<Item = GraphNodeRef> {
-        (0..self.graph_nodes.len() as u32).map(GraphNodeRef)
+    pub fn iter_nodes(&self) -> impl Iterator<Item = GraphNodeRef> + '_ {
+        (0..self.graph_nodes.len() as u32)
+            .filter(move |index| !self.graph_nodes[*index as usize].removed)
+            .map(GraphNodeRef)
     }
 
-    // Returns the number of nodes in the graph.
+    /// Returns references to all of the nodes in the graph that have an attribute with the given
+    /// name and value.
+    pub fn find_nodes_by_attribute(&self, name: Identifier, value: &Value) -> Vec<GraphNodeRef> {
+        self.iter_nodes()
+            .filter(|node| self[*node].attributes.get(&name) == Some(value))
+            .collect()
+    }
+
+    /// Returns the edges whose sink is `node`, along with their source graph nodes, ordered by
+    /// source.  Graph nodes only store their outgoing edges, so this looks up the edge to `node`
+    /// in every graph node, which takes O(N log E) time for N graph nodes and E edges per node.
+    pub fn incoming_edges(&self, node: GraphNodeRef) -> Vec<(GraphNodeRef, &Edge)> {
+        self.iter_nodes()
+            .filter_map(|source| self[source].get_edge(node).map(|edge| (source, edge)))
+            .collect()
+    }
+
+    /// Returns the number of graph nodes in the graph, not counting removed ones.  This does not
+    /// iterate over the graph nodes.
     pub fn node_count(&self) -> usize {
-        self.graph_nodes.len()
+        self.graph_nodes.len() - self.removed_graph_nodes
+    }
+
+    /// Returns the graph nodes of this graph that were added after it was cloned from `base`.
+    pub(crate) fn nodes_added_since<'a>(
+        &'a self,
+        base: &Graph,
+    ) -> impl Iterator<Item = GraphNodeRef> + 'a {
+        (base.graph_nodes.len() as u32..self.graph_nodes.len() as u32)
+            .filter(move |index| !self.graph_nodes[*index as usize].removed)
+            .map(GraphNodeRef)
+    }
+
+    /// Returns the number of syntax nodes in the graph.  The graph only contains the syntax nodes
+    /// that were referenced during execution, not every node of the syntax tree.
+    pub fn syntax_node_count(&self) -> usize {
+        self.syntax_nodes.len()
+    }
+
+    /// Returns the graph nodes of this graph in topological order, so that the source of every
+    /// edge comes before its sink.  Nodes that are not ordered relative to each other appear in
+    /// index order.  Returns a [`CycleError`][] identifying a node on a cycle if the graph is not
+    /// acyclic.
+    pub fn topological_order(&self) -> Result<Vec<GraphNodeRef>, CycleError> {
+        let mut incoming = vec![0usize; self.graph_nodes.len()];
+        for node in &self.graph_nodes {
+            for (sink, _) in &node.outgoing_edges {
+                incoming[*sink as usize] += 1;
+            }
+        }
+        let mut ready = self
+            .iter_nodes()
+            .filter(|node| incoming[node.0 as usize] == 0)
+            .collect::<VecDeque<_>>();
+        let mut order = Vec::with_capacity(self.graph_nodes.len());
+        while let Some(node) = ready.pop_front() {
+            order.push(node);
+            for (sink, _) in &self[node].outgoing_edges {
+                incoming[*sink as usize] -= 1;
+                if incoming[*sink as usize] == 0 {
+                    ready.push_back(GraphNodeRef(*sink));
+                }
+            }
+        }
+        if order.len() == self.node_count() {
+            return Ok(order);
+        }
+        // Every node that could not be ordered has an incoming edge from another such node, so
+        // following those edges backwards from any of them must eventually revisit a node, which
+        // is then on a cycle.
+        let mut visited = vec![false; self.graph_nodes.len()];
+        let mut node = self
+            .iter_nodes()
+            .find(|node| incoming[node.0 as usize] > 0)
+            .expect("missing unordered node");
+        while !visited[node.0 as usize] {
+            visited[node.0 as usize] = true;
+            node = self
+                .iter_nodes()
+                .find(|source| {
+                    incoming[source.0 as usize] > 0 && self[*source].get_edge(node).is_some()
+                })
+                .expect("missing unordered predecessor");
+        }
+        Err(CycleError(node))
+    }
+
+    /// Converts this graph into an [`OwnedGraph`][], which does not borrow the syntax tree, and
+    /// can therefore outlive it.  The syntax nodes that the graph refers to are replaced by
+    /// [`OwnedSyntaxNode`][] descriptors.
+    pub fn into_owned(self) -> OwnedGraph {
+        OwnedGraph {
+            syntax_nodes: self
+                .syntax_nodes
+                .into_iter()
+                .map(|(index, node)| (index, OwnedSyntaxNode::from(node)))
+                .collect(),
+            graph_nodes: self.graph_nodes,
+        }
+    }
+
+    /// Returns whether this graph has the same structure as another graph: the same graph nodes,
+    /// edges, and attributes.  Graph nodes are matched by their ids, so graph node references
+    /// must match exactly.  Syntax node references are compared by the kind and range of the
+    /// syntax node they refer to, so graphs built from different parses of the same source
+    /// compare equal.  The locations recorded when graph nodes and edges were created are
+    /// ignored.
+    pub fn structurally_eq(&self, other: &Graph) -> bool {
+        self.graph_nodes.len() == other.graph_nodes.len()
+            && self
+                .graph_nodes
+                .iter()
+                .zip(other.graph_nodes.iter())
+                .all(|(node, other_node)| node.structurally_eq(self, other_node, other))
     }
 }
 
@@ -132,19 +660,143 @@ impl<'tree> IndexMut<GraphNodeRef> for Graph<'_> {
 
 impl<'tree> Serialize for Graph<'tree> {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let mut seq = serializer.serialize_seq(Some(self.graph_nodes.len()))?;
+        let mut seq = serializer.serialize_seq(None)?;
         for (node_index, node) in self.graph_nodes.iter().enumerate() {
+            if node.removed {
+                continue;
+            }
             seq.serialize_element(&SerializeGraphNode(node_index, node))?;
         }
         seq.end()
     }
 }
 
+/// A graph that only records the ids of the syntax nodes it refers to, created using
+/// [`Graph::detach`][].  Unlike a graph, it can be sent between threads.
+pub(crate) struct DetachedGraph {
+    syntax_nodes: Vec<SyntaxNodeID>,
+    graph_nodes: Vec<GraphNode>,
+    removed_graph_nodes: usize,
+    graph_nodes_for_syntax_nodes: HashMap<SyntaxNodeID, GraphNodeRef>,
+}
+
+/// An error returned when ordering the nodes of a graph that contains a cycle
+#[derive(Debug, Error)]
+#[error("Graph contains a cycle through {0}")]
+pub struct CycleError(pub GraphNodeRef);
+
+/// An operation that executing a graph DSL file performs on a graph, as reported by
+/// [`File::dry_run`][crate::ast::File::dry_run]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum GraphEvent {
+    /// A graph node was created
+    CreateGraphNode(GraphNodeRef),
+    /// An attribute was added to a graph node, or overwritten by `set-attr`
+    AddGraphNodeAttribute(GraphNodeRef, Identifier, Value),
+    /// An attribute was deleted from a graph node
+    DeleteGraphNodeAttribute(GraphNodeRef, Identifier),
+    /// An edge was created between two graph nodes
+    CreateEdge(GraphNodeRef, GraphNodeRef),
+    /// An attribute was added to an edge, or overwritten by `set-attr`
+    AddEdgeAttribute(GraphNodeRef, GraphNodeRef, Identifier, Value),
+    /// An attribute was deleted from an edge
+    DeleteEdgeAttribute(GraphNodeRef, GraphNodeRef, Identifier),
+}
+
+impl fmt::Display for GraphEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::CreateGraphNode(node) => write!(f, "node {}", node),
+            Self::AddGraphNodeAttribute(node, name, value) => {
+                write!(f, "attr ({}) {} = {}", node, name, value)
+            }
+            Self::DeleteGraphNodeAttribute(node, name) => write!(f, "del-attr ({}) {}", node, name),
+            Self::CreateEdge(source, sink) => write!(f, "edge {} -> {}", source, sink),
+            Self::AddEdgeAttribute(source, sink, name, value) => {
+                write!(f, "attr ({} -> {}) {} = {}", source, sink, name, value)
+            }
+            Self::DeleteEdgeAttribute(source, sink, name) => {
+                write!(f, "del-attr ({} -> {}) {}", source, sink, name)
+            }
+        }
+    }
+}
+
+/// A graph that does not borrow the syntax tree that it was generated from, created using
+/// [`Graph::into_owned`][].  Attribute values still refer to syntax nodes using
+/// [`SyntaxNodeRef`][]s, which can be resolved into [`OwnedSyntaxNode`][] descriptors by
+/// indexing the graph.
+pub struct OwnedGraph {
+    syntax_nodes: HashMap<SyntaxNodeID, OwnedSyntaxNode>,
+    graph_nodes: Vec<GraphNode>,
+}
+
+impl OwnedGraph {
+    /// Pretty-prints the contents of this graph.  The output is the same as that of the
+    /// [`Graph`][] this graph was created from.
+    pub fn pretty_print(&self) -> PrettyPrint<'_> {
+        PrettyPrint::new(&self.graph_nodes)
+    }
+
+    // Returns an iterator of references to all of the nodes in the graph.
+    pub fn iter_nodes(&self) -> impl Iterator<Item = GraphNodeRef> + '_ {
+        (0..self.graph_nodes.len() as u32)
+            .filter(move |index| !self.graph_nodes[*index as usize].removed)
+            .map(GraphNodeRef)
+    }
+
+    // Returns the number of nodes in the graph.
+    pub fn node_count(&self) -> usize {
+        self.graph_nodes.iter().filter(|node| !node.removed).count()
+    }
+}
+
+impl Index<SyntaxNodeRef> for OwnedGraph {
+    type Output = OwnedSyntaxNode;
+    fn index(&self, node_ref: SyntaxNodeRef) -> &OwnedSyntaxNode {
+        &self.syntax_nodes[&node_ref.index]
+    }
+}
+
+impl Index<GraphNodeRef> for OwnedGraph {
+    type Output = GraphNode;
+    fn index(&self, index: GraphNodeRef) -> &GraphNode {
+        &self.graph_nodes[index.0 as usize]
+    }
+}
+
+/// A description of a syntax node that does not borrow the syntax tree it belongs to
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OwnedSyntaxNode {
+    /// The kind of the syntax node
+    pub kind: &'static str,
+    /// The byte range of the syntax node in the source
+    pub byte_range: std::ops::Range<usize>,
+    /// The position of the start of the syntax node
+    pub start_position: tree_sitter::Point,
+    /// The position of the end of the syntax node
+    pub end_position: tree_sitter::Point,
+}
+
+impl From<Node<'_>> for OwnedSyntaxNode {
+    fn from(node: Node) -> OwnedSyntaxNode {
+        OwnedSyntaxNode {
+            kind: node.kind(),
+            byte_range: node.byte_range(),
+            start_position: node.start_position(),
+            end_position: node.end_position(),
+        }
+    }
+}
+
 /// A node in a graph
+#[derive(Clone)]
 pub struct GraphNode {
     outgoing_edges: SmallVec<[(GraphNodeID, Edge); 8]>,
     /// The set of attributes associated with this graph node
     pub attributes: Attributes,
+    pub(crate) created_at: Option<Location>,
+    removed: bool,
 }
 
 impl GraphNode {
@@ -152,9 +804,23 @@ impl GraphNode {
         GraphNode {
             outgoing_edges: SmallVec::new(),
             attributes: Attributes::new(),
+            created_at: None,
+            removed: false,
         }
     }
 
+    /// Returns whether this node has been removed from its graph using
+    /// [`Graph::remove_node`][].
+    pub fn is_removed(&self) -> bool {
+        self.removed
+    }
+
+    /// Returns the location of the DSL statement that created this node.  This is only recorded
+    /// if [`ExecutionConfig::record_locations`][crate::ExecutionConfig::record_locations] is set.
+    pub fn created_at(&self) -> Option<Location> {
+        self.created_at
+    }
+
     /// Adds an edge to this node.  There can be at most one edge connecting any two graph nodes;
     /// the result indicates whether the edge is new (`Ok`) or already existed (`Err`).  In either
     /// case, you also get a mutable reference to the [`Edge`][] instance for the edge.
@@ -201,6 +867,27 @@ impl GraphNode {
     pub fn edge_count(&self) -> usize {
         self.outgoing_edges.len()
     }
+
+    fn structurally_eq(&self, graph: &Graph, other: &GraphNode, other_graph: &Graph) -> bool {
+        if self.removed || other.removed {
+            return self.removed == other.removed;
+        }
+        self.attributes
+            .structurally_eq(graph, &other.attributes, other_graph)
+            && self.outgoing_edges.len() == other.outgoing_edges.len()
+            && self
+                .outgoing_edges
+                .iter()
+                .zip(other.outgoing_edges.iter())
+                .all(|((sink, edge), (other_sink, other_edge))| {
+                    sink == other_sink
+                        && edge.attributes.structurally_eq(
+                            graph,
+                            &other_edge.attributes,
+                            other_graph,
+                        )
+                })
+    }
 }
 
 struct SerializeGraphNode<'a>(usize, &'a GraphNode);
@@ -246,17 +933,26 @@ impl<'a> Serialize for SerializeGraphNodeEdge<'a> {
 }
 
 /// An edge between two nodes in a graph
+#[derive(Clone)]
 pub struct Edge {
     /// The set of attributes associated with this edge
     pub attributes: Attributes,
+    pub(crate) created_at: Option<Location>,
 }
 
 impl Edge {
     fn new() -> Edge {
         Edge {
             attributes: Attributes::new(),
+            created_at: None,
         }
     }
+
+    /// Returns the location of the DSL statement that created this edge.  This is only recorded
+    /// if [`ExecutionConfig::record_locations`][crate::ExecutionConfig::record_locations] is set.
+    pub fn created_at(&self) -> Option<Location> {
+        self.created_at
+    }
 }
 
 /// A set of attributes associated with a graph node or edge
@@ -288,6 +984,21 @@ impl Attributes {
         }
     }
 
+    /// Sets an attribute in this attribute set, replacing the value of any existing attribute
+    /// with the same name.
+    pub fn set<V: Into<Value>>(&mut self, name: Identifier, value: V) {
+        self.values.insert(name, value.into());
+    }
+
+    /// Removes an attribute from this attribute set, returning its value, if it existed.
+    pub fn remove<Q>(&mut self, name: &Q) -> Option<Value>
+    where
+        Q: ?Sized + Eq + Hash,
+        Identifier: Borrow<Q>,
+    {
+        self.values.remove(name)
+    }
+
     /// Returns the value of a particular attribute, if it exists.
     pub fn get<Q>(&self, name: &Q) -> Option<&Value>
     where
@@ -300,25 +1011,93 @@ impl Attributes {
     pub fn iter(&self) -> impl Iterator<Item = (&Identifier, &Value)> {
         self.values.iter()
     }
+
+    /// Returns the number of attributes in this attribute set.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns whether this attribute set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    fn structurally_eq(&self, graph: &Graph, other: &Attributes, other_graph: &Graph) -> bool {
+        self.values.len() == other.values.len()
+            && self.values.iter().all(|(name, value)| {
+                other.values.get(name).is_some_and(|other_value| {
+                    value.structurally_eq(graph, other_value, other_graph)
+                })
+            })
+    }
+
+    fn offset_graph_nodes(&mut self, offset: GraphNodeID) {
+        for value in self.values.values_mut() {
+            value.offset_graph_nodes(offset);
+        }
+    }
 }
 
-impl std::fmt::Display for Attributes {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+/// Iterates over the attributes in an attribute set, sorted by name, in the same order in which
+/// they are output
+impl<'a> IntoIterator for &'a Attributes {
+    type Item = (&'a Identifier, &'a Value);
+    type IntoIter = AttributesIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let mut values = self.values.iter().collect::<Vec<_>>();
+        values.sort_by_key(|(name, _)| *name);
+        AttributesIter(values.into_iter())
+    }
+}
+
+/// An iterator over the attributes in an attribute set, sorted by name
+pub struct AttributesIter<'a>(std::vec::IntoIter<(&'a Identifier, &'a Value)>);
+
+impl<'a> Iterator for AttributesIter<'a> {
+    type Item = (&'a Identifier, &'a Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl Attributes {
+    fn fmt_rendered(
+        &self,
+        f: &mut fmt::Formatter,
+        renderer: &dyn NodeRenderer,
+        indent: usize,
+    ) -> fmt::Result {
         let mut keys = self.values.keys().collect::<Vec<_>>();
         keys.sort_by(|a, b| a.cmp(b));
         for key in &keys {
             let value = &self.values[*key];
-            write!(f, "  {}: {:?}\n", key, value)?;
+            write!(f, "{:indent$}{}: ", "", key, indent = indent)?;
+            value.fmt_rendered(f, renderer)?;
+            writeln!(f)?;
         }
         Ok(())
     }
 }
 
+impl std::fmt::Display for Attributes {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.fmt_rendered(f, &DefaultNodeRenderer, 2)
+    }
+}
+
 impl Serialize for Attributes {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut keys = self.values.keys().collect::<Vec<_>>();
+        keys.sort();
         let mut map = serializer.serialize_map(None)?;
-        for (key, value) in &self.values {
-            map.serialize_entry(key, value)?;
+        for key in keys {
+            map.serialize_entry(key, &self.values[key])?;
         }
         map.end()
     }
@@ -331,16 +1110,77 @@ pub enum Value {
     Null,
     Boolean(bool),
     Integer(u32),
-    String(String),
+    String(StringValue),
     // Compound
     List(Vec<Value>),
     Set(BTreeSet<Value>),
     // References
     SyntaxNode(SyntaxNodeRef),
     GraphNode(GraphNodeRef),
+    Edge(GraphNodeRef, GraphNodeRef),
+}
+
+/// The type of a [`Value`][], used to declare the expected types of global variables.  New types
+/// of values may be added, so matching on this enum needs a wildcard arm.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum ExpectedType {
+    Null,
+    Boolean,
+    Integer,
+    String,
+    List,
+    Set,
+    SyntaxNode,
+    GraphNode,
+    Edge,
+}
+
+impl ExpectedType {
+    /// Returns the type of a value.
+    pub fn of(value: &Value) -> ExpectedType {
+        match value {
+            Value::Null => ExpectedType::Null,
+            Value::Boolean(_) => ExpectedType::Boolean,
+            Value::Integer(_) => ExpectedType::Integer,
+            Value::String(_) => ExpectedType::String,
+            Value::List(_) => ExpectedType::List,
+            Value::Set(_) => ExpectedType::Set,
+            Value::SyntaxNode(_) => ExpectedType::SyntaxNode,
+            Value::GraphNode(_) => ExpectedType::GraphNode,
+            Value::Edge(_, _) => ExpectedType::Edge,
+        }
+    }
+
+    /// Returns whether a value has this type.
+    pub fn matches(&self, value: &Value) -> bool {
+        *self == ExpectedType::of(value)
+    }
+}
+
+impl fmt::Display for ExpectedType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExpectedType::Null => write!(f, "null"),
+            ExpectedType::Boolean => write!(f, "boolean"),
+            ExpectedType::Integer => write!(f, "integer"),
+            ExpectedType::String => write!(f, "string"),
+            ExpectedType::List => write!(f, "list"),
+            ExpectedType::Set => write!(f, "set"),
+            ExpectedType::SyntaxNode => write!(f, "syntax node"),
+            ExpectedType::GraphNode => write!(f, "graph node"),
+            ExpectedType::Edge => write!(f, "edge"),
+        }
+    }
 }
 
 impl Value {
+    /// Creates a string value that shares the storage of an interned string.  Cloning the value, or
+    /// creating other values from the same interned string, does not copy the string's contents.
+    pub fn from_interned(value: Arc<str>) -> Value {
+        Value::String(StringValue::Interned(value))
+    }
+
     /// Check if this value is null
     pub fn is_null(&self) -> bool {
         match self {
@@ -382,14 +1222,14 @@ impl Value {
     /// Coerces this value into a string, returning an error if it's some other type of value.
     pub fn into_string(self) -> Result<String, ExecutionError> {
         match self {
-            Value::String(value) => Ok(value),
+            Value::String(value) => Ok(value.into()),
             _ => Err(ExecutionError::ExpectedString(format!("got {}", self))),
         }
     }
 
     pub fn as_str(&self) -> Result<&str, ExecutionError> {
         match self {
-            Value::String(value) => Ok(value),
+            Value::String(value) => Ok(value.as_str()),
             _ => Err(ExecutionError::ExpectedString(format!("got {}", self))),
         }
     }
@@ -409,6 +1249,21 @@ impl Value {
         }
     }
 
+    /// Coerces this value into a set, returning an error if it's some other type of value.
+    pub fn into_set(self) -> Result<BTreeSet<Value>, ExecutionError> {
+        match self {
+            Value::Set(values) => Ok(values),
+            _ => Err(ExecutionError::ExpectedSet(format!("got {}", self))),
+        }
+    }
+
+    pub fn as_set(&self) -> Result<&BTreeSet<Value>, ExecutionError> {
+        match self {
+            Value::Set(values) => Ok(values),
+            _ => Err(ExecutionError::ExpectedSet(format!("got {}", self))),
+        }
+    }
+
     /// Coerces this value into a graph node reference, returning an error if it's some other type
     /// of value.
     pub fn into_graph_node_ref<'a, 'tree>(self) -> Result<GraphNodeRef, ExecutionError> {
@@ -418,6 +1273,12 @@ impl Value {
         }
     }
 
+    /// Coerces this value into a graph node, returning an error if it's some other type of value.
+    /// This is equivalent to `&graph[value.into_graph_node_ref()?]`.
+    pub fn into_graph_node<'a>(self, graph: &'a Graph) -> Result<&'a GraphNode, ExecutionError> {
+        Ok(&graph[self.into_graph_node_ref()?])
+    }
+
     pub fn as_graph_node_ref<'a, 'tree>(&self) -> Result<GraphNodeRef, ExecutionError> {
         match self {
             Value::GraphNode(node) => Ok(*node),
@@ -425,6 +1286,48 @@ impl Value {
         }
     }
 
+    /// Coerces this value into a sequence of attribute names and values, returning an error if
+    /// it's not a list or set of two-element lists, each consisting of a string name and a value.
+    pub fn into_attribute_pairs(self) -> Result<Vec<(Identifier, Value)>, ExecutionError> {
+        let pairs = match self {
+            Value::List(values) => values,
+            Value::Set(values) => values.into_iter().collect(),
+            _ => return Err(ExecutionError::ExpectedList(format!("got {}", self))),
+        };
+        pairs
+            .into_iter()
+            .map(|pair| match pair {
+                Value::List(mut pair) if pair.len() == 2 => {
+                    let value = pair.pop().unwrap();
+                    let name = pair.pop().unwrap().into_string()?;
+                    Ok((Identifier::from(name.as_str()), value))
+                }
+                _ => Err(ExecutionError::ExpectedAttributePair(format!(
+                    "got {}",
+                    pair
+                ))),
+            })
+            .collect()
+    }
+
+    /// Coerces this value into the source and sink of an edge, returning an error if it's some
+    /// other type of value.
+    pub fn into_edge(self) -> Result<(GraphNodeRef, GraphNodeRef), ExecutionError> {
+        match self {
+            Value::Edge(source, sink) => Ok((source, sink)),
+            _ => Err(ExecutionError::ExpectedEdge(format!("got {}", self))),
+        }
+    }
+
+    /// Returns the source and sink of the edge this value refers to, returning an error if it's
+    /// some other type of value.
+    pub fn as_edge(&self) -> Result<(GraphNodeRef, GraphNodeRef), ExecutionError> {
+        match self {
+            Value::Edge(source, sink) => Ok((*source, *sink)),
+            _ => Err(ExecutionError::ExpectedEdge(format!("got {}", self))),
+        }
+    }
+
     /// Coerces this value into a syntax node reference, returning an error if it's some other type
     /// of value.
     pub fn into_syntax_node_ref<'a, 'tree>(self) -> Result<SyntaxNodeRef, ExecutionError> {
@@ -452,6 +1355,76 @@ impl Value {
     }
 }
 
+impl Value {
+    fn structurally_eq(&self, graph: &Graph, other: &Value, other_graph: &Graph) -> bool {
+        match (self, other) {
+            (Value::List(values), Value::List(other_values)) => {
+                values.len() == other_values.len()
+                    && values
+                        .iter()
+                        .zip(other_values.iter())
+                        .all(|(value, other_value)| {
+                            value.structurally_eq(graph, other_value, other_graph)
+                        })
+            }
+            // Sets are ordered by syntax node id, which can differ between graphs, so match
+            // their elements without relying on the order.
+            (Value::Set(values), Value::Set(other_values)) => {
+                values.len() == other_values.len()
+                    && values.iter().all(|value| {
+                        other_values.iter().any(|other_value| {
+                            value.structurally_eq(graph, other_value, other_graph)
+                        })
+                    })
+            }
+            (Value::SyntaxNode(node), Value::SyntaxNode(other_node)) => {
+                node.kind == other_node.kind
+                    && graph.syntax_nodes.get(&node.index).map(|n| n.range())
+                        == other_graph
+                            .syntax_nodes
+                            .get(&other_node.index)
+                            .map(|n| n.range())
+            }
+            _ => self == other,
+        }
+    }
+
+    fn collect_graph_nodes(&self, nodes: &mut Vec<GraphNodeRef>) {
+        match self {
+            Value::GraphNode(node) => nodes.push(*node),
+            Value::Edge(source, sink) => nodes.extend([*source, *sink]),
+            Value::List(values) => values.iter().for_each(|v| v.collect_graph_nodes(nodes)),
+            Value::Set(values) => values.iter().for_each(|v| v.collect_graph_nodes(nodes)),
+            _ => {}
+        }
+    }
+
+    fn offset_graph_nodes(&mut self, offset: GraphNodeID) {
+        match self {
+            Value::GraphNode(node) => node.0 += offset,
+            Value::Edge(source, sink) => {
+                source.0 += offset;
+                sink.0 += offset;
+            }
+            Value::List(values) => {
+                for value in values.iter_mut() {
+                    value.offset_graph_nodes(offset);
+                }
+            }
+            Value::Set(values) => {
+                *values = std::mem::take(values)
+                    .into_iter()
+                    .map(|mut value| {
+                        value.offset_graph_nodes(offset);
+                        value
+                    })
+                    .collect();
+            }
+            _ => {}
+        }
+    }
+}
+
 impl From<bool> for Value {
     fn from(value: bool) -> Value {
         Value::Boolean(value)
@@ -464,15 +1437,42 @@ impl From<u32> for Value {
     }
 }
 
+/// Converts a signed integer into a value, failing if the integer is negative, since graph DSL
+/// integers are unsigned.
+impl TryFrom<i32> for Value {
+    type Error = TryFromIntError;
+    fn try_from(value: i32) -> Result<Value, TryFromIntError> {
+        u32::try_from(value).map(Value::Integer)
+    }
+}
+
+/// Converts a signed integer into a value, failing if the integer is negative or does not fit in
+/// a `u32`.
+impl TryFrom<i64> for Value {
+    type Error = TryFromIntError;
+    fn try_from(value: i64) -> Result<Value, TryFromIntError> {
+        u32::try_from(value).map(Value::Integer)
+    }
+}
+
+/// Converts a `usize`, such as a length or an index, into a value, failing if the integer does not
+/// fit in a `u32`.
+impl TryFrom<usize> for Value {
+    type Error = TryFromIntError;
+    fn try_from(value: usize) -> Result<Value, TryFromIntError> {
+        u32::try_from(value).map(Value::Integer)
+    }
+}
+
 impl From<&str> for Value {
     fn from(value: &str) -> Value {
-        Value::String(value.to_string())
+        Value::String(value.into())
     }
 }
 
 impl From<String> for Value {
     fn from(value: String) -> Value {
-        Value::String(value)
+        Value::String(value.into())
     }
 }
 
@@ -488,6 +1488,132 @@ impl From<BTreeSet<Value>> for Value {
     }
 }
 
+/// The contents of a string value.  Strings are usually owned, but can also share the storage of
+/// an interned string; see [`Value::from_interned`][].  Both representations of the same string
+/// are equal.
+#[derive(Clone)]
+pub enum StringValue {
+    Owned(String),
+    Interned(Arc<str>),
+}
+
+impl StringValue {
+    pub fn as_str(&self) -> &str {
+        match self {
+            StringValue::Owned(value) => value,
+            StringValue::Interned(value) => value,
+        }
+    }
+
+    /// Returns the contents of this string as an owned `String`, copying them only if they are
+    /// interned.
+    pub fn into_string(self) -> String {
+        self.into()
+    }
+}
+
+impl AsRef<str> for StringValue {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl std::ops::Deref for StringValue {
+    type Target = str;
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for StringValue {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for StringValue {
+    fn from(value: &str) -> StringValue {
+        StringValue::Owned(value.to_string())
+    }
+}
+
+impl From<String> for StringValue {
+    fn from(value: String) -> StringValue {
+        StringValue::Owned(value)
+    }
+}
+
+impl From<StringValue> for String {
+    fn from(value: StringValue) -> String {
+        match value {
+            StringValue::Owned(value) => value,
+            StringValue::Interned(value) => value.to_string(),
+        }
+    }
+}
+
+impl PartialEq for StringValue {
+    fn eq(&self, other: &StringValue) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for StringValue {}
+
+impl PartialEq<str> for StringValue {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for StringValue {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for StringValue {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialOrd for StringValue {
+    fn partial_cmp(&self, other: &StringValue) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for StringValue {
+    fn cmp(&self, other: &StringValue) -> std::cmp::Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Hash for StringValue {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl std::fmt::Display for StringValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+impl std::fmt::Debug for StringValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+impl Serialize for StringValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -529,12 +1655,13 @@ impl std::fmt::Display for Value {
             }
             Value::SyntaxNode(node) => node.fmt(f),
             Value::GraphNode(node) => node.fmt(f),
+            Value::Edge(source, sink) => write!(f, "({} -> {})", source, sink),
         }
     }
 }
 
-impl std::fmt::Debug for Value {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl Value {
+    fn fmt_rendered(&self, f: &mut fmt::Formatter, renderer: &dyn NodeRenderer) -> fmt::Result {
         match self {
             Value::Null => write!(f, "#null"),
             Value::Boolean(value) => {
@@ -545,17 +1672,16 @@ impl std::fmt::Debug for Value {
                 }
             }
             Value::Integer(value) => write!(f, "{:?}", value),
-            Value::String(value) => write!(f, "{:?}", value),
+            Value::String(value) => renderer.fmt_string(f, value.as_str()),
             Value::List(value) => {
                 write!(f, "[")?;
                 let mut first = true;
                 for element in value {
-                    if first {
-                        write!(f, "{:?}", element)?;
-                        first = false;
-                    } else {
-                        write!(f, ", {:?}", element)?;
+                    if !first {
+                        write!(f, ", ")?;
                     }
+                    element.fmt_rendered(f, renderer)?;
+                    first = false;
                 }
                 write!(f, "]")
             }
@@ -563,21 +1689,33 @@ impl std::fmt::Debug for Value {
                 write!(f, "{{")?;
                 let mut first = true;
                 for element in value {
-                    if first {
-                        write!(f, "{:?}", element)?;
-                        first = false;
-                    } else {
-                        write!(f, ", {:?}", element)?;
+                    if !first {
+                        write!(f, ", ")?;
                     }
+                    element.fmt_rendered(f, renderer)?;
+                    first = false;
                 }
                 write!(f, "}}")
             }
-            Value::SyntaxNode(node) => node.fmt(f),
-            Value::GraphNode(node) => node.fmt(f),
+            Value::SyntaxNode(node) => renderer.fmt_syntax_node_ref(f, *node),
+            Value::GraphNode(node) => renderer.fmt_graph_node_ref(f, *node),
+            Value::Edge(source, sink) => {
+                write!(f, "(")?;
+                renderer.fmt_graph_node_ref(f, *source)?;
+                write!(f, " -> ")?;
+                renderer.fmt_graph_node_ref(f, *sink)?;
+                write!(f, ")")
+            }
         }
     }
 }
 
+impl std::fmt::Debug for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.fmt_rendered(f, &DefaultNodeRenderer)
+    }
+}
+
 impl Serialize for Value {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         match self {
@@ -628,18 +1766,45 @@ impl Serialize for Value {
                 map.serialize_entry("id", &node.0)?;
                 map.end()
             }
+            Value::Edge(source, sink) => {
+                let mut map = serializer.serialize_map(None)?;
+                map.serialize_entry("type", "edge")?;
+                map.serialize_entry("source", &source.0)?;
+                map.serialize_entry("sink", &sink.0)?;
+                map.end()
+            }
         }
     }
 }
 
-/// A reference to a syntax node in a graph
-#[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
+/// A reference to a syntax node in a graph.  References are equal if they refer to the same
+/// syntax node, as identified by tree-sitter's [`Node::id`][].
+///
+/// References are ordered by the start position of their syntax node, and then by its kind, so
+/// that sets of syntax nodes are displayed in source order.  Tree-sitter's node ids can differ
+/// between runs, so they are only used to order distinct nodes with the same position and kind.
+#[derive(Clone, Copy, Eq, Hash, PartialEq)]
 pub struct SyntaxNodeRef {
     index: SyntaxNodeID,
     kind: &'static str,
     position: tree_sitter::Point,
 }
 
+impl Ord for SyntaxNodeRef {
+    fn cmp(&self, other: &SyntaxNodeRef) -> std::cmp::Ordering {
+        self.position
+            .cmp(&other.position)
+            .then_with(|| self.kind.cmp(other.kind))
+            .then_with(|| self.index.cmp(&other.index))
+    }
+}
+
+impl PartialOrd for SyntaxNodeRef {
+    fn partial_cmp(&self, other: &SyntaxNodeRef) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl From<tree_sitter::Point> for Location {
     fn from(point: tree_sitter::Point) -> Location {
         Location {
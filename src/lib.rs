@@ -40,9 +40,11 @@ mod variables;
 pub use execution::error::ExecutionError;
 pub use execution::CancellationError;
 pub use execution::CancellationFlag;
+pub use execution::Execution;
 pub use execution::ExecutionConfig;
 pub use execution::Match;
 pub use execution::NoCancellation;
+pub use execution::StanzaSelector;
 pub use parser::Location;
 pub use parser::ParseError;
 pub use variables::Globals as Variables;
@@ -58,14 +60,32 @@ use serde::Serialize;
 use serde::Serializer;
 
 /// An identifier that appears in a graph DSL file or in the graph that is produced as an output.
+///
+/// Identifiers are not interned in any shared context: each one owns its (reference-counted)
+/// name, so you can create them directly from a string, and get their name back via
+/// [`as_str`][Self::as_str].  Attributes can also be looked up using a plain `&str`:
+///
+/// ```
+/// # use tree_sitter_graph::graph::Attributes;
+/// # use tree_sitter_graph::Identifier;
+/// let name = Identifier::from("name");
+/// assert_eq!(name.as_str(), "name");
+///
+/// let mut attributes = Attributes::new();
+/// attributes.set(name, "value");
+/// assert!(attributes.get("name").is_some());
+/// assert!(attributes.get("unknown").is_none());
+/// ```
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub struct Identifier(Arc<String>);
 
 impl Identifier {
+    /// Returns the name of this identifier.
     pub fn as_str(&self) -> &str {
         self.0.as_str()
     }
 
+    /// Returns the name of this identifier as an owned string.
     pub fn into_string(mut self) -> String {
         Arc::make_mut(&mut self.0);
         Arc::try_unwrap(self.0).unwrap()
@@ -5,10 +5,13 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::collections::HashMap;
 use std::fmt::Display;
+use std::io;
 use std::iter::Peekable;
 use std::path::Path;
 use std::str::Chars;
+use std::sync::Arc;
 
 use regex::Regex;
 use thiserror::Error;
@@ -28,12 +31,51 @@ use crate::Identifier;
 
 pub const FULL_MATCH: &str = "__tsg__full_match";
 
+/// The maximum depth to which expressions can be nested.  Parsing, checking, and evaluating
+/// expressions all recurse over their nesting, so deeper expressions are rejected while parsing,
+/// instead of overflowing the stack.  It is also the default for
+/// [`ExecutionConfig::max_expression_depth`][crate::ExecutionConfig::max_expression_depth], so
+/// that parsed files never exceed the default execution limit.
+pub(crate) const MAX_EXPRESSION_DEPTH: usize = 64;
+
 impl ast::File {
     /// Parses a graph DSL file, returning a new `File` instance.
     pub fn from_str(language: Language, source: &str) -> Result<Self, ParseError> {
+        Self::from_str_with_resolver(language, source, no_resolver)
+    }
+
+    /// Parses a graph DSL file, returning a new `File` instance.  Any `include` directives in the
+    /// file are resolved by calling `resolver` with the included path, which must return the
+    /// content of the included file.
+    pub fn from_str_with_resolver<R>(
+        language: Language,
+        source: &str,
+        mut resolver: R,
+    ) -> Result<Self, ParseError>
+    where
+        R: FnMut(&str) -> Result<String, io::Error>,
+    {
         let mut file = ast::File::new(language);
-        #[allow(deprecated)]
-        file.parse(source)?;
+        Parser::new(source).parse_into_file_with_resolver(&mut file, &mut resolver)?;
+        file.check()?;
+        Ok(file)
+    }
+
+    /// Parses a graph DSL file whose stanzas can target several languages, returning a new `File`
+    /// instance.  Stanzas target `language` by default; a stanza with a `language NAME` label
+    /// targets the language with that name in `languages` instead.  Use
+    /// [`execute_with_languages`][Self::execute_with_languages] to execute the file.
+    pub fn from_str_with_languages(
+        language: Language,
+        languages: &[(&str, Language)],
+        source: &str,
+    ) -> Result<Self, ParseError> {
+        let mut file = ast::File::new(language);
+        for (name, language) in languages {
+            file.languages
+                .insert(Identifier::from(*name), ast::File::new(*language));
+        }
+        Parser::new(source).parse_into_file_with_resolver(&mut file, &mut no_resolver)?;
         file.check()?;
         Ok(file)
     }
@@ -47,32 +89,59 @@ impl ast::File {
     }
 }
 
+/// Resolver used when no include resolver is provided, which fails for every included file.
+fn no_resolver(path: &str) -> Result<String, io::Error> {
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("no resolver available for included file {}", path),
+    ))
+}
+
 // ----------------------------------------------------------------------------
 // Parse errors
 
 /// An error that can occur while parsing a graph DSL file
 #[derive(Debug, Error)]
 pub enum ParseError {
+    #[error("Cyclic include of \"{0}\" at {1}")]
+    CyclicInclude(String, Location),
     #[error("Expected quantifier at {0}")]
     ExpectedQuantifier(Location),
     #[error("Expected '{0}' at {1}")]
     ExpectedToken(&'static str, Location),
     #[error("Expected variable name at {0}")]
     ExpectedVariable(Location),
+    #[error("Expression nested more than {0} levels deep at {1}")]
+    ExpressionTooDeep(usize, Location),
     #[error("Expected unscoped variable at {0}")]
     ExpectedUnscopedVariable(Location),
+    #[error("Cannot include \"{0}\" at {2}: {1}")]
+    IncludeFailed(String, io::Error, Location),
+    /// An error occurred while parsing the included file with the given path.  The location of
+    /// the error is relative to that file.
+    #[error("In included file \"{0}\": {1}")]
+    InIncludedFile(String, Box<ParseError>),
+    #[error("Integer constant {0} out of range at {1}")]
+    IntegerOutOfRange(String, Location),
+    #[error("Invalid integer constant {0} at {1}")]
+    InvalidInteger(String, Location),
     #[error("Invalid regular expression /{0}/ at {1}")]
     InvalidRegex(String, Location),
     #[error("Expected integer constant in regex capture at {0}")]
     InvalidRegexCapture(Location),
-    #[error("Invalid query pattern: {}", _0.message)]
-    QueryError(#[from] QueryError),
+    /// The query of the stanza at the given location is invalid.  The location of the query
+    /// error itself is adjusted to be relative to the graph DSL file.  The last field contains
+    /// the source text of the stanza's query.
+    #[error("Invalid query pattern in stanza at {1}: {}", _0.message)]
+    QueryError(QueryError, Location, String),
     #[error("Unexpected character '{0}' in {1} at {2}")]
     UnexpectedCharacter(char, &'static str, Location),
     #[error("Unexpected end of file at {0}")]
     UnexpectedEOF(Location),
     #[error("Unexpected keyword '{0}' at {1}")]
     UnexpectedKeyword(String, Location),
+    #[error("Undefined language {0} at {1}")]
+    UndefinedLanguage(String, Location),
     #[error("Unexpected literal '#{0}' at {1}")]
     UnexpectedLiteral(String, Location),
     #[error("Query contains multiple patterns at {0}")]
@@ -104,25 +173,36 @@ struct DisplayParseErrorPretty<'a> {
 impl std::fmt::Display for DisplayParseErrorPretty<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let location = match self.error {
+            ParseError::CyclicInclude(_, location) => *location,
             ParseError::ExpectedQuantifier(location) => *location,
             ParseError::ExpectedToken(_, location) => *location,
             ParseError::ExpectedVariable(location) => *location,
+            ParseError::ExpressionTooDeep(_, location) => *location,
             ParseError::ExpectedUnscopedVariable(location) => *location,
+            ParseError::IncludeFailed(_, _, location) => *location,
+            ParseError::IntegerOutOfRange(_, location) => *location,
+            ParseError::InvalidInteger(_, location) => *location,
             ParseError::InvalidRegex(_, location) => *location,
             ParseError::InvalidRegexCapture(location) => *location,
-            ParseError::QueryError(err) => Location {
+            ParseError::QueryError(err, _, _) => Location {
                 row: err.row,
                 column: err.column,
             },
             ParseError::UnexpectedCharacter(_, _, location) => *location,
             ParseError::UnexpectedEOF(location) => *location,
             ParseError::UnexpectedKeyword(_, location) => *location,
+            ParseError::UndefinedLanguage(_, location) => *location,
             ParseError::UnexpectedLiteral(_, location) => *location,
             ParseError::UnexpectedQueryPatterns(location) => *location,
             ParseError::Check(err) => {
                 write!(f, "{}", err.display_pretty(self.path, self.source))?;
                 return Ok(());
             }
+            ParseError::InIncludedFile(_, _) => {
+                // The source of the included file is not available to show an excerpt
+                writeln!(f, "{}", self.error)?;
+                return Ok(());
+            }
         };
         writeln!(f, "{}", self.error)?;
         write!(
@@ -196,6 +276,12 @@ struct Parser<'a> {
     offset: usize,
     location: Location,
     query_source: String,
+    /// The combined query source of the stanzas of each other language
+    language_query_sources: HashMap<Identifier, String>,
+    /// The number of expressions currently being parsed, one inside the other
+    expression_depth: usize,
+    /// The path of the included file being parsed, or `None` for the main file
+    file: Option<String>,
 }
 
 fn is_ident_start(c: char) -> bool {
@@ -216,6 +302,9 @@ impl<'a> Parser<'a> {
             offset: 0,
             location: Location::default(),
             query_source,
+            language_query_sources: HashMap::new(),
+            expression_depth: 0,
+            file: None,
         }
     }
 }
@@ -289,9 +378,48 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_into_file(&mut self, file: &mut ast::File) -> Result<(), ParseError> {
+        self.parse_into_file_with_resolver(file, &mut no_resolver)
+    }
+
+    fn parse_into_file_with_resolver(
+        &mut self,
+        file: &mut ast::File,
+        resolver: &mut dyn FnMut(&str) -> Result<String, io::Error>,
+    ) -> Result<(), ParseError> {
+        self.parse_declarations(file, resolver, &mut Vec::new())?;
+        // we can unwrap here because all queries have already been parsed before
+        file.query = Some(Arc::new(
+            Query::new(file.language, &self.query_source).unwrap(),
+        ));
+        for (name, language_file) in file.languages.iter_mut() {
+            let query_source = self
+                .language_query_sources
+                .get(name)
+                .map(String::as_str)
+                .unwrap_or_default();
+            language_file.query = Some(Arc::new(
+                Query::new(language_file.language, query_source).unwrap(),
+            ));
+            language_file.globals = file.globals.clone();
+            language_file.shorthands = file.shorthands.clone();
+        }
+        Ok(())
+    }
+
+    fn parse_declarations(
+        &mut self,
+        file: &mut ast::File,
+        resolver: &mut dyn FnMut(&str) -> Result<String, io::Error>,
+        include_stack: &mut Vec<String>,
+    ) -> Result<(), ParseError> {
+        let mut whitespace_start = self.offset;
         self.consume_whitespace();
         while self.try_peek().is_some() {
-            if let Ok(_) = self.consume_token("global") {
+            let doc = self.leading_comment(whitespace_start);
+            if self.consume_token("include").is_ok() {
+                self.consume_whitespace();
+                self.parse_include(file, resolver, include_stack)?;
+            } else if let Ok(_) = self.consume_token("global") {
                 self.consume_whitespace();
                 let global = self.parse_global()?;
                 file.globals.push(global);
@@ -300,16 +428,74 @@ impl<'a> Parser<'a> {
                 let shorthand = self.parse_shorthand()?;
                 file.shorthands.add(shorthand);
             } else {
-                let stanza = self.parse_stanza(file.language)?;
-                file.stanzas.push(stanza);
+                self.parse_labeled_stanza(file, doc)?;
             }
+            whitespace_start = self.offset;
             self.consume_whitespace();
         }
-        // we can unwrap here because all queries have already been parsed before
-        file.query = Some(Query::new(file.language, &self.query_source).unwrap());
         Ok(())
     }
 
+    /// Returns the text of the `;` comment lines that directly precede the current position,
+    /// within the whitespace that was skipped starting at `whitespace_start`.  A blank line ends
+    /// the comment, as does a comment that follows other content on the same line.
+    fn leading_comment(&self, whitespace_start: usize) -> Option<String> {
+        let mut lines = self.source[whitespace_start..self.offset]
+            .split('\n')
+            .collect::<Vec<_>>();
+        // The last line is the indentation of the current declaration, and the first one is the
+        // end of the line of the previous declaration, unless the whitespace starts a line.
+        lines.pop();
+        if whitespace_start > 0
+            && !self.source[..whitespace_start].ends_with('\n')
+            && !lines.is_empty()
+        {
+            lines.remove(0);
+        }
+        let mut comment = lines
+            .iter()
+            .rev()
+            .map(|line| line.trim())
+            .take_while(|line| line.starts_with(';'))
+            .map(|line| {
+                let text = line.trim_start_matches(';');
+                text.strip_prefix(' ').unwrap_or(text)
+            })
+            .collect::<Vec<_>>();
+        if comment.is_empty() {
+            return None;
+        }
+        comment.reverse();
+        Some(comment.join("\n"))
+    }
+
+    fn parse_include(
+        &mut self,
+        file: &mut ast::File,
+        resolver: &mut dyn FnMut(&str) -> Result<String, io::Error>,
+        include_stack: &mut Vec<String>,
+    ) -> Result<(), ParseError> {
+        let location = self.location;
+        let path = self.parse_string()?;
+        if include_stack.contains(&path) {
+            return Err(ParseError::CyclicInclude(path, location));
+        }
+        let content =
+            resolver(&path).map_err(|e| ParseError::IncludeFailed(path.clone(), e, location))?;
+        // The included stanzas are added to the same combined query, so that pattern indices
+        // keep lining up with the stanza list of the file.
+        let mut parser = Parser::new(&content);
+        parser.query_source = std::mem::take(&mut self.query_source);
+        parser.language_query_sources = std::mem::take(&mut self.language_query_sources);
+        parser.file = Some(path.clone());
+        include_stack.push(path.clone());
+        let result = parser.parse_declarations(file, resolver, include_stack);
+        include_stack.pop();
+        self.query_source = parser.query_source;
+        self.language_query_sources = parser.language_query_sources;
+        result.map_err(|e| ParseError::InIncludedFile(path, Box::new(e)))
+    }
+
     fn parse_global(&mut self) -> Result<ast::Global, ParseError> {
         let location = self.location;
         let name = self.parse_identifier("global variable")?;
@@ -364,23 +550,79 @@ impl<'a> Parser<'a> {
         Ok(quantifier)
     }
 
-    fn parse_stanza(&mut self, language: Language) -> Result<ast::Stanza, ParseError> {
+    /// Parses a stanza, along with any `stanza` and `language` labels in front of it, and adds
+    /// it to the file for its language.
+    fn parse_labeled_stanza(
+        &mut self,
+        file: &mut ast::File,
+        doc: Option<String>,
+    ) -> Result<(), ParseError> {
+        let mut name = None;
+        let mut language_name = None;
+        loop {
+            if name.is_none() && self.consume_token("stanza").is_ok() {
+                self.consume_whitespace();
+                name = Some(self.parse_identifier("stanza name")?);
+            } else if language_name.is_none() && self.consume_token("language").is_ok() {
+                self.consume_whitespace();
+                let location = self.location;
+                let language = self.parse_identifier("language name")?;
+                if !file.languages.contains_key(&language) {
+                    return Err(ParseError::UndefinedLanguage(
+                        language.to_string(),
+                        location,
+                    ));
+                }
+                language_name = Some(language);
+            } else {
+                break;
+            }
+            self.consume_whitespace();
+        }
+        let file = match &language_name {
+            Some(language) => file.languages.get_mut(language).unwrap(),
+            None => file,
+        };
+        let stanza = self.parse_stanza(file.language, language_name, name, doc)?;
+        file.stanzas.push(stanza);
+        Ok(())
+    }
+
+    fn parse_stanza(
+        &mut self,
+        language: Language,
+        language_name: Option<Identifier>,
+        name: Option<Identifier>,
+        doc: Option<String>,
+    ) -> Result<ast::Stanza, ParseError> {
         let start = self.location;
-        let (query, full_match_stanza_capture_index) = self.parse_query(language)?;
+        let query_start = self.offset;
+        let (query, full_match_stanza_capture_index) =
+            self.parse_query(language, language_name.as_ref())?;
+        let query_source = self.source[query_start..self.offset].trim_end().to_string();
         self.consume_whitespace();
         let statements = self.parse_statements()?;
         let end = self.location;
         let range = Range { start, end };
         Ok(ast::Stanza {
-            query,
+            name,
+            doc,
+            query: Arc::new(query),
+            language: language_name,
+            query_source,
             statements,
             full_match_stanza_capture_index,
             full_match_file_capture_index: usize::MAX, // set in checker
             range,
+            file: self.file.clone(),
         })
     }
 
-    fn parse_query(&mut self, language: Language) -> Result<(Query, usize), ParseError> {
+    fn parse_query(
+        &mut self,
+        language: Language,
+        language_name: Option<&Identifier>,
+    ) -> Result<(Query, usize), ParseError> {
         let location = self.location;
         let query_start = self.offset;
         self.skip_query()?;
@@ -388,8 +630,15 @@ impl<'a> Parser<'a> {
         let query_source = self.source[query_start..query_end].to_owned() + "@" + FULL_MATCH;
         // If tree-sitter allowed us to incrementally add patterns to a query, we wouldn't need
         // the global query_source.
-        self.query_source += &query_source;
-        self.query_source += "\n";
+        let file_query_source = match language_name {
+            Some(language) => self
+                .language_query_sources
+                .entry(language.clone())
+                .or_default(),
+            None => &mut self.query_source,
+        };
+        *file_query_source += &query_source;
+        *file_query_source += "\n";
         let query = Query::new(language, &query_source).map_err(|mut e| {
             // the column of the first row of a query pattern must be shifted by the whitespace
             // that was already consumed
@@ -399,7 +648,8 @@ impl<'a> Parser<'a> {
             }
             e.row += location.row;
             e.offset += query_start;
-            e
+            let query_text = self.source[query_start..query_end].trim_end().to_string();
+            ParseError::QueryError(e, location, query_text)
         })?;
         if query.pattern_count() > 1 {
             return Err(ParseError::UnexpectedQueryPatterns(location));
@@ -481,17 +731,41 @@ impl<'a> Parser<'a> {
         let keyword = self.parse_name("keyword")?;
         self.consume_whitespace();
         if keyword == "let" {
-            let variable = self.parse_variable()?;
+            let expression_location = self.location;
+            // `let [a, b] = value` unpacks a list into several variables
+            let (variable, variables) = match self.parse_expression()? {
+                ast::Expression::Variable(variable) => (Some(variable), Vec::new()),
+                ast::Expression::ListLiteral(list) => {
+                    let variables = list
+                        .elements
+                        .into_iter()
+                        .map(|element| match element {
+                            ast::Expression::Variable(variable) => Ok(variable),
+                            _ => Err(ParseError::ExpectedVariable(expression_location)),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    (None, variables)
+                }
+                _ => return Err(ParseError::ExpectedVariable(expression_location)),
+            };
             self.consume_whitespace();
             self.consume_token("=")?;
             self.consume_whitespace();
             let value = self.parse_expression()?;
-            Ok(ast::DeclareImmutable {
-                variable,
-                value,
-                location: keyword_location,
+            match variable {
+                Some(variable) => Ok(ast::DeclareImmutable {
+                    variable,
+                    value,
+                    location: keyword_location,
+                }
+                .into()),
+                None => Ok(ast::DestructureImmutable {
+                    variables,
+                    value,
+                    location: keyword_location,
+                }
+                .into()),
             }
-            .into())
         } else if keyword == "var" {
             let variable = self.parse_variable()?;
             self.consume_whitespace();
@@ -524,18 +798,39 @@ impl<'a> Parser<'a> {
             }
             .into())
         } else if keyword == "edge" {
-            let source = self.parse_expression()?;
+            let expression_location = self.location;
+            let mut source = self.parse_expression()?;
             self.consume_whitespace();
+            let edge = if self.try_peek() == Some('=') {
+                let edge = match source {
+                    ast::Expression::Variable(variable) => variable,
+                    _ => return Err(ParseError::ExpectedVariable(expression_location)),
+                };
+                self.consume_token("=")?;
+                self.consume_whitespace();
+                source = self.parse_expression()?;
+                self.consume_whitespace();
+                Some(edge)
+            } else {
+                None
+            };
             self.consume_token("->")?;
             self.consume_whitespace();
             let sink = self.parse_expression()?;
             Ok(ast::CreateEdge {
+                edge,
                 source,
                 sink,
                 location: keyword_location,
             }
             .into())
-        } else if keyword == "attr" {
+        } else if keyword == "attr" || keyword == "set-attr" {
+            let overwrite = keyword == "set-attr";
+            let skip_null = self.try_peek() == Some('?');
+            if skip_null {
+                self.skip().unwrap();
+                self.consume_whitespace();
+            }
             self.consume_token("(")?;
             self.consume_whitespace();
             let node_or_source = self.parse_expression()?;
@@ -554,6 +849,8 @@ impl<'a> Parser<'a> {
                     source,
                     sink,
                     attributes,
+                    overwrite,
+                    skip_null,
                     location: keyword_location,
                 }
                 .into())
@@ -562,10 +859,47 @@ impl<'a> Parser<'a> {
                 self.consume_whitespace();
                 self.consume_token(")")?;
                 self.consume_whitespace();
-                let attributes = self.parse_attributes()?;
+                let (attributes, spreads) = self.parse_attributes_with_spreads()?;
                 Ok(ast::AddGraphNodeAttribute {
                     node,
                     attributes,
+                    spreads,
+                    overwrite,
+                    skip_null,
+                    location: keyword_location,
+                }
+                .into())
+            }
+        } else if keyword == "del-attr" {
+            self.consume_token("(")?;
+            self.consume_whitespace();
+            let node_or_source = self.parse_expression()?;
+            self.consume_whitespace();
+
+            if self.peek()? == '-' {
+                let source = node_or_source;
+                self.consume_token("->")?;
+                self.consume_whitespace();
+                let sink = self.parse_expression()?;
+                self.consume_whitespace();
+                self.consume_token(")")?;
+                self.consume_whitespace();
+                let names = self.parse_attribute_names()?;
+                Ok(ast::DeleteEdgeAttribute {
+                    source,
+                    sink,
+                    names,
+                    location: keyword_location,
+                }
+                .into())
+            } else {
+                let node = node_or_source;
+                self.consume_token(")")?;
+                self.consume_whitespace();
+                let names = self.parse_attribute_names()?;
+                Ok(ast::DeleteGraphNodeAttribute {
+                    node,
+                    names,
                     location: keyword_location,
                 }
                 .into())
@@ -591,8 +925,19 @@ impl<'a> Parser<'a> {
             self.consume_token("{")?;
             self.consume_whitespace();
             let mut arms = Vec::new();
+            let mut default_arm = None;
             while self.peek()? != '}' {
                 let pattern_location = self.location;
+                if self.consume_token("else").is_ok() {
+                    self.consume_whitespace();
+                    let statements = self.parse_statements()?;
+                    default_arm = Some(ast::ScanDefaultArm {
+                        statements,
+                        location: pattern_location,
+                    });
+                    self.consume_whitespace();
+                    break;
+                }
                 let pattern = self.parse_string()?;
                 let regex = Regex::new(&pattern)
                     .map_err(|_| ParseError::InvalidRegex(pattern.into(), pattern_location))?;
@@ -609,6 +954,7 @@ impl<'a> Parser<'a> {
             Ok(ast::Scan {
                 value,
                 arms,
+                default_arm,
                 location: keyword_location,
             }
             .into())
@@ -759,12 +1105,150 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Checks that an expression `extra` levels below the one currently being parsed is not
+    /// nested too deeply.
+    fn check_expression_depth(&self, extra: usize) -> Result<(), ParseError> {
+        if self.expression_depth + extra > MAX_EXPRESSION_DEPTH {
+            return Err(ParseError::ExpressionTooDeep(
+                MAX_EXPRESSION_DEPTH,
+                self.location,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Parses an expression nested inside the one currently being parsed, using `parse`.
+    fn parse_nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<ast::Expression, ParseError>,
+    ) -> Result<ast::Expression, ParseError> {
+        self.check_expression_depth(1)?;
+        self.expression_depth += 1;
+        let result = parse(self);
+        self.expression_depth -= 1;
+        result
+    }
+
     fn parse_expression(&mut self) -> Result<ast::Expression, ParseError> {
+        self.parse_nested(Self::parse_disjunction)
+    }
+
+    fn parse_disjunction(&mut self) -> Result<ast::Expression, ParseError> {
+        let location = self.location;
+        let mut expression = self.parse_conjunction()?;
+        // Chains of operators are parsed iteratively, but still nest one level per operator.
+        let mut depth = 0;
+        while self.consume_token("||").is_ok() {
+            depth += 1;
+            self.check_expression_depth(depth)?;
+            self.consume_whitespace();
+            let right = self.parse_conjunction()?;
+            expression = ast::Logical {
+                operator: ast::LogicalOperator::Or,
+                left: Box::new(expression),
+                right: Box::new(right),
+                location,
+            }
+            .into();
+        }
+        Ok(expression)
+    }
+
+    fn parse_conjunction(&mut self) -> Result<ast::Expression, ParseError> {
+        let location = self.location;
+        let mut expression = self.parse_comparison()?;
+        let mut depth = 0;
+        while self.consume_token("&&").is_ok() {
+            depth += 1;
+            self.check_expression_depth(depth)?;
+            self.consume_whitespace();
+            let right = self.parse_comparison()?;
+            expression = ast::Logical {
+                operator: ast::LogicalOperator::And,
+                left: Box::new(expression),
+                right: Box::new(right),
+                location,
+            }
+            .into();
+        }
+        Ok(expression)
+    }
+
+    fn parse_comparison(&mut self) -> Result<ast::Expression, ParseError> {
+        let location = self.location;
+        let left = self.parse_negation()?;
+        let operator = match self.parse_comparison_operator() {
+            Some(operator) => operator,
+            None => return Ok(left),
+        };
+        self.consume_whitespace();
+        let right = self.parse_negation()?;
+        // Comparisons are non-associative, so `a == b == c` is not allowed.
+        let next_location = self.location;
+        let next_char = self.try_peek();
+        if let (Some(ch), Some(_)) = (next_char, self.parse_comparison_operator()) {
+            return Err(ParseError::UnexpectedCharacter(
+                ch,
+                "comparison",
+                next_location,
+            ));
+        }
+        Ok(ast::Comparison {
+            operator,
+            left: Box::new(left),
+            right: Box::new(right),
+            location,
+        }
+        .into())
+    }
+
+    fn parse_negation(&mut self) -> Result<ast::Expression, ParseError> {
+        let location = self.location;
+        if self.try_peek() == Some('!') && !self.source[self.offset..].starts_with("!=") {
+            self.skip()?;
+            self.consume_whitespace();
+            let value = self.parse_nested(Self::parse_negation)?;
+            return Ok(ast::Not {
+                value: Box::new(value),
+                location,
+            }
+            .into());
+        }
+        self.parse_operand()
+    }
+
+    fn parse_comparison_operator(&mut self) -> Option<ast::ComparisonOperator> {
+        // Two-character operators must be tried before their one-character prefixes.
+        let operators = [
+            ("==", ast::ComparisonOperator::Eq),
+            ("!=", ast::ComparisonOperator::Ne),
+            ("<=", ast::ComparisonOperator::Le),
+            (">=", ast::ComparisonOperator::Ge),
+            ("<", ast::ComparisonOperator::Lt),
+            (">", ast::ComparisonOperator::Gt),
+        ];
+        for (token, operator) in operators {
+            if self.consume_token(token).is_ok() {
+                return Some(operator);
+            }
+        }
+        None
+    }
+
+    fn parse_operand(&mut self) -> Result<ast::Expression, ParseError> {
         let mut expression = match self.peek()? {
             '#' => self.parse_literal()?,
             '"' => self.parse_string()?.into(),
             '@' => self.parse_capture()?.into(),
             '$' => self.parse_regex_capture()?.into(),
+            '(' if self.is_at_parenthesized_expression() => {
+                self.consume_token("(")?;
+                self.consume_whitespace();
+                let expression = self.parse_expression()?;
+                self.consume_whitespace();
+                self.consume_token(")")?;
+                expression
+            }
             '(' => self.parse_call()?,
             '[' => self.parse_list()?,
             '{' => self.parse_set()?,
@@ -783,7 +1267,10 @@ impl<'a> Parser<'a> {
             }
         };
         self.consume_whitespace();
+        let mut depth = 0;
         while self.try_peek() == Some('.') {
+            depth += 1;
+            self.check_expression_depth(depth)?;
             self.skip().unwrap();
             self.consume_whitespace();
             let location = self.location;
@@ -800,6 +1287,19 @@ impl<'a> Parser<'a> {
         Ok(expression)
     }
 
+    /// Returns whether the opening parenthesis at the current position starts a parenthesized
+    /// expression, as in `(a || b)`, rather than a function call, as in `(f a b)`.
+    fn is_at_parenthesized_expression(&self) -> bool {
+        let rest = self.source[self.offset + 1..].trim_start();
+        if !rest.starts_with(is_ident_start) {
+            return true;
+        }
+        let rest = rest.trim_start_matches(is_ident).trim_start();
+        ["==", "!=", "<", ">", "&&", "||", "."]
+            .iter()
+            .any(|token| rest.starts_with(token))
+    }
+
     fn parse_call(&mut self) -> Result<ast::Expression, ParseError> {
         self.consume_token("(")?;
         self.consume_whitespace();
@@ -935,11 +1435,23 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_integer_constant(&mut self) -> Result<ast::Expression, ParseError> {
-        // We'll have already verified that the next digit is an integer.
+        // We'll have already verified that the next character is a digit.
+        let location = self.location;
         let start = self.offset;
-        self.consume_while(|ch| ch.is_ascii_digit());
-        let end = self.offset;
-        let value = u32::from_str_radix(&self.source[start..end], 10).unwrap();
+        self.consume_while(|ch| ch.is_ascii_alphanumeric());
+        let text = &self.source[start..self.offset];
+        let (radix, digits) = match text.get(..2) {
+            Some("0x") => (16, &text[2..]),
+            Some("0b") => (2, &text[2..]),
+            Some("0o") => (8, &text[2..]),
+            _ => (10, text),
+        };
+        // from_str_radix would also accept a leading `+`, so check the digits explicitly
+        if digits.is_empty() || !digits.chars().all(|ch| ch.is_digit(radix)) {
+            return Err(ParseError::InvalidInteger(text.to_string(), location));
+        }
+        let value = u32::from_str_radix(digits, radix)
+            .map_err(|_| ParseError::IntegerOutOfRange(text.to_string(), location))?;
         Ok(ast::IntegerConstant { value }.into())
     }
 
@@ -964,6 +1476,14 @@ impl<'a> Parser<'a> {
     fn parse_regex_capture(&mut self) -> Result<ast::RegexCapture, ParseError> {
         let regex_capture_location = self.location;
         self.consume_token("$")?;
+        if self.try_peek().is_some_and(is_ident_start) {
+            let name = Identifier::from(self.parse_name("regex capture")?);
+            return Ok(ast::RegexCapture {
+                match_index: usize::MAX, // set in checker
+                name: Some(name),
+                location: regex_capture_location,
+            });
+        }
         let start = self.offset;
         self.consume_while(|ch| ch.is_ascii_digit());
         let end = self.offset;
@@ -971,7 +1491,11 @@ impl<'a> Parser<'a> {
             return Err(ParseError::InvalidRegexCapture(regex_capture_location));
         }
         let match_index = usize::from_str_radix(&self.source[start..end], 10).unwrap();
-        Ok(ast::RegexCapture { match_index }.into())
+        Ok(ast::RegexCapture {
+            match_index,
+            name: None,
+            location: regex_capture_location,
+        })
     }
 
     fn parse_attributes(&mut self) -> Result<Vec<ast::Attribute>, ParseError> {
@@ -986,6 +1510,40 @@ impl<'a> Parser<'a> {
         Ok(attributes)
     }
 
+    fn parse_attributes_with_spreads(
+        &mut self,
+    ) -> Result<(Vec<ast::Attribute>, Vec<ast::Expression>), ParseError> {
+        let mut attributes = Vec::new();
+        let mut spreads = Vec::new();
+        loop {
+            if self.source[self.offset..].starts_with("..") {
+                self.consume_token("..")?;
+                spreads.push(self.parse_expression()?);
+            } else {
+                attributes.push(self.parse_attribute()?);
+            }
+            self.consume_whitespace();
+            if self.try_peek() != Some(',') {
+                break;
+            }
+            self.skip().unwrap();
+            self.consume_whitespace();
+        }
+        Ok((attributes, spreads))
+    }
+
+    fn parse_attribute_names(&mut self) -> Result<Vec<Identifier>, ParseError> {
+        let mut names = vec![self.parse_identifier("attribute name")?];
+        self.consume_whitespace();
+        while self.try_peek() == Some(',') {
+            self.skip().unwrap();
+            self.consume_whitespace();
+            names.push(self.parse_identifier("attribute name")?);
+            self.consume_whitespace();
+        }
+        Ok(names)
+    }
+
     fn parse_attribute(&mut self) -> Result<ast::Attribute, ParseError> {
         let name = self.parse_identifier("attribute name")?;
         self.consume_whitespace();
@@ -24,6 +24,25 @@
 //! The compared values must be of the same type. Null values are equal to each
 //! other and can be compared to values of any type.
 //!
+//! Syntax nodes are compared by identity: two syntax node values are equal if they refer to the
+//! same node in the syntax tree, for instance because two captures matched the same node.  Distinct
+//! nodes are never equal, even if they have the same type and source text; compare their
+//! [`source-text`](#source-text) to compare their contents.
+//!
+//! ## `hash`
+//!
+//! Computes a hash of a value.
+//!
+//!   - Input parameters: one value
+//!   - Output value: an integer hash of the value
+//!
+//! The hash is the 32-bit [FNV-1a][] hash of the string, or for other values, of their printed
+//! form.  It is the same across runs and platforms, so it can be used to derive stable
+//! identifiers.  Note that a string and a value whose printed form is that string have the same
+//! hash.
+//!
+//! [FNV-1a]: http://www.isthe.com/chongo/tech/comp/fnv/index.html
+//!
 //! ## `is-null`
 //!
 //! Check if an optional value is missing.
@@ -40,6 +59,43 @@
 //!   - Input parameters: none
 //!   - Output value: a reference to the new graph node
 //!
+//! ## `node-for-syntax`
+//!
+//! Returns the graph node associated with a syntax node.  The graph node is created the first
+//! time it is requested for a syntax node, and every later call for the same syntax node returns
+//! the same graph node.  This gives you a single graph node per syntax node, without having to
+//! store it in a scoped variable.
+//!
+//!   - Input parameters: a syntax node
+//!   - Output value: a reference to the graph node associated with the syntax node
+//!
+//! ## `edge-source`
+//!
+//! Returns the source node of an edge that was bound to a variable by an `edge` statement.
+//!
+//!   - Input parameters: an edge
+//!   - Output value: a reference to the source graph node of the edge
+//!
+//! ## `edge-sink`
+//!
+//! Returns the sink node of an edge that was bound to a variable by an `edge` statement.
+//!
+//!   - Input parameters: an edge
+//!   - Output value: a reference to the sink graph node of the edge
+//!
+//! ## `get-attr`
+//!
+//! Returns the value of an attribute of a graph node, or `#null` if the graph node does not have
+//! an attribute with that name.  When executing lazily, the attribute is read once the graph is
+//! being built, so it sees attributes added by statements of earlier matches.  For this reason,
+//! when executing lazily, calls to `get-attr` fail where a local value is required, such as in
+//! conditions.
+//!
+//!   - Input parameters:
+//!     - `node`: a graph node
+//!     - `name`: a string
+//!   - Output value: the value of the attribute named `name` of `node`, or `#null`
+//!
 //! # Logical functions
 //!
 //! ## `not`
@@ -74,6 +130,31 @@
 //!   - Input parameters: zero or more integers
 //!   - Output value: the sum of all of the input integers
 //!
+//! Fails if the sum does not fit in an integer, instead of wrapping around.
+//!
+//! ## `times`
+//!
+//! Multiplies integers together.
+//!
+//!   - Input parameters: zero or more integers
+//!   - Output value: the product of all of the input integers
+//!
+//! Fails if the product does not fit in an integer, instead of wrapping around.
+//!
+//! ## `min`
+//!
+//! Returns the smallest of one or more integers.
+//!
+//!   - Input parameters: one or more integers
+//!   - Output value: the smallest of the input integers
+//!
+//! ## `max`
+//!
+//! Returns the largest of one or more integers.
+//!
+//!   - Input parameters: one or more integers
+//!   - Output value: the largest of the input integers
+//!
 //! # String functions
 //!
 //! ## `format`
@@ -88,6 +169,44 @@
 //!
 //! Placeholders are written as `{}`. To produce literal braces, use `{{` and `}}` instead.
 //!
+//! ## `matches`
+//!
+//! Tests whether a regular expression matches a string.
+//!
+//!   - Input parameters:
+//!     - `text`: a string to look for a match in
+//!     - `pattern`: a string defining the regular expression to search for
+//!
+//!   - Output value: a boolean indicating whether the regular expression matches anywhere in
+//!     `text`
+//!
+//! The regular expression syntax is the same as for [`replace`](#replace).  Use `^` and `$` to
+//! anchor the match to the start or end of the string.
+//!
+//! ## `parse-int`
+//!
+//! Parses a string as an integer.
+//!
+//!   - Input parameters:
+//!     - `text`: the string to parse
+//!     - `radix`: an optional integer between 2 and 36, defaulting to 10
+//!
+//!   - Output value: the integer represented by `text`
+//!
+//! It is an error if `text` is not a valid non-negative integer in the given radix.
+//!
+//! ## `repeat`
+//!
+//! Repeats a string.
+//!
+//!   - Input parameters:
+//!     - `text`: the string to repeat
+//!     - `count`: the number of times to repeat it
+//!
+//!   - Output value: `text` repeated `count` times
+//!
+//! It is an error if the result would be longer than 1 MiB.
+//!
 //! ## `replace`
 //!
 //! Applies a regular expression to a string, replacing any text that matches.
@@ -105,6 +224,78 @@
 //! [`Regex::new`]: https://docs.rs/regex/*/regex/struct.Regex.html#method.new
 //! [`Regex::replace_all`]: https://docs.rs/regex/*/regex/struct.Regex.html#method.replace_all
 //!
+//! ## `regex-replace`
+//!
+//! Applies a regular expression to a string, replacing any text that matches, in the same way as
+//! [`replace`](#replace).  Recently used patterns are not compiled again, which makes this function
+//! cheaper than `replace` when it is called with the same pattern for many syntax nodes, and an
+//! invalid pattern is reported as an invalid parameter.
+//!
+//!   - Input parameters:
+//!     - `text`: a string to look for matches in
+//!     - `pattern`: a string defining the regular expression to search for
+//!     - `replacement`: the text to replace any matches with, which can refer to the capture
+//!       groups of `pattern` as `$1` or `${name}`
+//!   - Output value: `text`, with every match of `pattern` replaced by `replacement`
+//!
+//! ## `string-length`
+//!
+//! Returns the length of a string.
+//!
+//!   - Input parameters: a string
+//!   - Output value: the number of characters in the string
+//!
+//! ## `starts-with`
+//!
+//! Tests whether a string starts with a given prefix.
+//!
+//!   - Input parameters:
+//!     - `text`: the string to test
+//!     - `prefix`: the prefix to look for
+//!
+//!   - Output value: a boolean indicating whether `text` starts with `prefix`
+//!
+//! ## `ends-with`
+//!
+//! Tests whether a string ends with a given suffix.
+//!
+//!   - Input parameters:
+//!     - `text`: the string to test
+//!     - `suffix`: the suffix to look for
+//!
+//!   - Output value: a boolean indicating whether `text` ends with `suffix`
+//!
+//! ## `index-of`
+//!
+//! Finds the first occurrence of a substring in a string.
+//!
+//!   - Input parameters:
+//!     - `text`: the string to search in
+//!     - `needle`: the substring to search for
+//!
+//!   - Output value: the character index of the first occurrence of `needle` in `text`, or `#null`
+//!     if `text` does not contain `needle`.  (Integers in the graph DSL are unsigned, so `-1`
+//!     cannot be used to indicate a missing substring.)
+//!
+//! ## `trim`, `trim-start`, `trim-end`
+//!
+//! Removes leading and/or trailing whitespace from a string.
+//!
+//!   - Input parameters: a string
+//!   - Output value: the string with whitespace removed from both ends (`trim`), from the start
+//!     only (`trim-start`), or from the end only (`trim-end`)
+//!
+//! Whitespace is defined by the Unicode `White_Space` property.
+//!
+//! ## `to-string`
+//!
+//! Converts any value to a string.
+//!
+//!   - Input parameters: any value
+//!   - Output value: a string containing the value's printed form.  Strings are returned as-is,
+//!     integers as their decimal representation, and graph and syntax nodes in the same form as
+//!     in the graph output (for example, `[graph node 2]`).
+//!
 //! # List functions
 //!
 //! ## `concat`
@@ -116,10 +307,10 @@
 //!
 //! ## `is-empty`
 //!
-//! Test whether a list is empty or not.
+//! Test whether a list, set, or string is empty or not.
 //!
-//!   - Input parameters: a list value
-//!   - Output value: a boolean indicating whether the list is empty or not
+//!   - Input parameters: a list, set, or string value
+//!   - Output value: a boolean indicating whether the value is empty or not
 //!
 //! ## `join`
 //!
@@ -139,8 +330,119 @@
 //!   - Input parameters: a list value
 //!   - Output value: an integer indicating the length of the list
 //!
+//! ## `reverse`
+//!
+//! Reverses a list.
+//!
+//!   - Input parameters: a list value
+//!   - Output value: a new list containing the elements of the input list in reverse order
+//!
+//! ## `sort`
+//!
+//! Sorts a list.
+//!
+//!   - Input parameters: a list value
+//!   - Output value: a new list containing the elements of the input list in ascending order
+//!
+//! All elements of the list must have the same type.  Equal elements keep their relative order.
+//!
+//! ## `sort-by`
+//!
+//! Sorts a list by a key that is computed for each element by calling another function.
+//!
+//!   - Input parameters:
+//!     - `list`: a list value
+//!     - `function`: the name of a function, as a string, that is called with each element of
+//!       `list` as its only parameter, and returns the element's sort key
+//!   - Output value: a new list containing the elements of `list`, ordered by ascending key
+//!
+//! All keys must have the same type.  Elements with equal keys keep their relative order.  Fails
+//! if there is no function called `function`.
+//!
+//! # Set functions
+//!
+//! ## `union`
+//!
+//! Computes the union of two sets.
+//!
+//!   - Input parameters: two set values
+//!   - Output value: a set containing the elements that are in either input set
+//!
+//! ## `intersect`
+//!
+//! Computes the intersection of two sets.
+//!
+//!   - Input parameters: two set values
+//!   - Output value: a set containing the elements that are in both input sets
+//!
+//! ## `difference`
+//!
+//! Computes the difference of two sets.
+//!
+//!   - Input parameters: two set values
+//!   - Output value: a set containing the elements of the first set that are not in the second
+//!
+//! # Match functions
+//!
+//! These functions describe the match that a stanza is executed for.  They are built into the
+//! executor, and are available even when the standard library is not used.  Their names are
+//! reserved, so functions with the same names in the function library are never called.  Calls
+//! with invalid parameters, such as `(capture-name "name")`, are reported when the file is
+//! parsed.
+//!
+//! ## `match-index`
+//!
+//! Returns the index of the stanza that matched.
+//!
+//!   - Input parameters: none
+//!   - Output value:
+//!     - The zero-based index of the current stanza, in the order in which the stanzas appear in
+//!       the graph DSL file
+//!
+//! ## `capture-name`
+//!
+//! Returns the name of a capture.
+//!
+//!   - Input parameters:
+//!     - `capture`: A query capture, such as `@name`
+//!   - Output value:
+//!     - A string containing the name of the capture, without the leading `@`
+//!
+//! ## `quantifier`
+//!
+//! Returns the quantifier of a capture, which determines whether its value is a syntax node, an
+//! optional syntax node, or a list of syntax nodes.
+//!
+//!   - Input parameters:
+//!     - `capture`: A query capture, such as `@name`
+//!   - Output value:
+//!     - A string describing the quantifier of the capture: `"one"` for a capture of a single
+//!       syntax node, `"zero-or-one"` for an optional capture such as `@name?`, `"zero-or-more"`
+//!       and `"one-or-more"` for list captures such as `@name*` and `@name+`, and `"zero"` for a
+//!       capture that can never match anything
+//!
+//! ## `full-match`
+//!
+//! Returns the syntax node that the whole query of the current stanza matched, without having to
+//! add a capture for it.
+//!
+//!   - Input parameters: none
+//!   - Output value:
+//!     - The outermost syntax node of the current match
+//!
 //! # Syntax manipulation functions
 //!
+//! ## `ancestor-of-kind`
+//!
+//! Returns the nearest ancestor of a syntax node that has a particular type.
+//!
+//!   - Input parameters:
+//!     - `node`: A syntax node
+//!     - `kind`: A string containing the node type to look for
+//!   - Output value:
+//!     - The closest proper ancestor of `node` whose type is `kind`, or `#null` if there is no
+//!       such ancestor
+//!
 //! ## `named-child-index`
 //!
 //! Returns the index of a "named child" within its parent.
@@ -160,14 +462,59 @@
 //!   - Output value:
 //!     - The number of _named_ children in `node`
 //!
+//! ## `field-name`
+//!
+//! Returns the name of the field that a syntax node occupies in its parent, such as `name` or
+//! `body` for the children of a Python `function_definition`.
+//!
+//!   - Input parameters:
+//!     - `node`: A syntax node
+//!   - Output value:
+//!     - The field name of `node` as a string, or `#null` if `node` has no parent or is not
+//!       stored in a field of its parent
+//!
 //! ## `source-text`
 //!
-//! Returns the source text represented by a syntax node.
+//! Returns the source text represented by a syntax node, or by each of a list of syntax nodes.
+//!
+//!   - Input parameters:
+//!     - `node`: A syntax node, or a list of syntax nodes
+//!   - Output value:
+//!     - A string containing the source text represented by `node`, or a list of such strings, one
+//!       for each syntax node in the list
+//!
+//! ## `source-text-dedent`
+//!
+//! Returns the source text represented by a syntax node, with the leading whitespace that all of
+//! its lines have in common removed.  If the syntax node is only preceded by whitespace on its
+//! first line, that whitespace counts as the indentation of the first line.  Otherwise, the first
+//! line is returned as is, and only the remaining lines are dedented.  Lines that only contain
+//! whitespace are ignored when determining the common indentation.
+//!
+//!   - Input parameters:
+//!     - `node`: A syntax node
+//!   - Output value:
+//!     - A string containing the dedented source text represented by `node`
+//!
+//! ## `next-named-sibling`
+//!
+//! Returns the next "named sibling" of a syntax node.
+//!
+//!   - Input parameters:
+//!     - `node`: A syntax node
+//!   - Output value:
+//!     - The _named_ sibling that immediately follows `node` within its parent, or `#null` if
+//!       `node` is the last named child of its parent
+//!
+//! ## `prev-named-sibling`
+//!
+//! Returns the previous "named sibling" of a syntax node.
 //!
 //!   - Input parameters:
 //!     - `node`: A syntax node
 //!   - Output value:
-//!     - A string containing the source text represented by `node`
+//!     - The _named_ sibling that immediately precedes `node` within its parent, or `#null` if
+//!       `node` is the first named child of its parent
 //!
 //! ## `node-type`
 //!
@@ -83,7 +83,53 @@
 //!
 //! [quantification]: https://tree-sitter.github.io/tree-sitter/using-parsers#quantification-operators
 //!
-//! Comments start with a semicolon, and extend to the end of the line.
+//! Comments start with a semicolon, and extend to the end of the line.  The comment lines
+//! directly preceding a stanza, without a blank line in between, are kept as the stanza's
+//! documentation, which tools can read from the [`doc`][crate::ast::Stanza::doc] field of the
+//! parsed stanza.
+//!
+//! A stanza can be given a name by putting a `stanza` label in front of its query pattern.  Names
+//! do not affect how a file is executed, but let you execute just the stanzas with a particular
+//! name, for instance while working on them, using
+//! [`File::execute_stanza`][crate::ast::File::execute_stanza]:
+//!
+//! ``` tsg
+//! stanza identifiers
+//! (identifier) @id
+//! {
+//!   node @id.node
+//! }
+//! ```
+//!
+//! A stanza can also target another language than the rest of the file, such as a language that
+//! is embedded in string literals, by putting a `language` label in front of its query pattern,
+//! either before or after its `stanza` label.  The query is then matched against the syntax tree
+//! of that language.  The available languages and their names are chosen by the process parsing
+//! the graph DSL file, using
+//! [`File::from_str_with_languages`][crate::ast::File::from_str_with_languages], and the trees
+//! of each language are passed to
+//! [`File::execute_with_languages`][crate::ast::File::execute_with_languages].  Since stanzas
+//! for different languages match different syntax trees, they cannot share scoped variables.
+//!
+//! ``` tsg
+//! language sql
+//! (select_statement) @select
+//! {
+//!   node @select.node
+//! }
+//! ```
+//!
+//! A graph DSL file can include the stanzas, global declarations, and attribute shorthands of
+//! other graph DSL files using an `include` directive at the top level of the file:
+//!
+//! ``` tsg
+//! include "common.tsg"
+//! ```
+//!
+//! The included content is spliced into the file at the position of the directive.  How the
+//! included path is resolved is up to the process executing the graph DSL file; the
+//! `tree-sitter-graph` program reads it relative to the directory of the file it was given.  It
+//! is an error for a file to (directly or indirectly) include itself.
 //!
 //! Identifiers start with either an ASCII letter or underscore, and all remaining characters are
 //! ASCII letters, numbers, underscores, or hyphens.  (More precisely, they satisfy the regular
@@ -141,11 +187,18 @@
 //!   - `"a string with\na newline"`
 //!   - `"a string with\\a backslash"`
 //!
-//! Integer constants are encoded in ASCII decimal:
+//! Integer constants are encoded in ASCII decimal, or in hexadecimal, binary, or octal with a
+//! `0x`, `0b`, or `0o` prefix:
 //!
 //!   - `0`
 //!   - `10`
 //!   - `42`
+//!   - `0xff`
+//!   - `0b1010`
+//!   - `0o755`
+//!
+//! Since integers are unsigned, there are no negative integer constants, and any constant that
+//! does not fit in 32 bits is an error.
 //!
 //! Lists consist of zero or more expressions, separated by commas, enclosed in square brackets.
 //! The elements of a list do not have to have the same type:
@@ -191,11 +244,15 @@
 //! value that is iterated over must be local.  It is therefore not possible to iterator over the value
 //! of a scoped variable. Using scoped variables in the element expression however is no problem.
 //!
+//! Expressions can be nested at most 64 levels deep.  Deeper expressions are reported as an error
+//! when the file is parsed.
+//!
 //! # Syntax nodes
 //!
 //! Syntax nodes are identified by tree-sitter query captures (`@name`).  For instance, in our
 //! example stanza, whose query is `(identifier) @id`, `@id` would refer to the `identifier` syntax
-//! node that the stanza matched against.
+//! node that the stanza matched against.  Anonymous nodes, such as keywords and punctuation, can
+//! be captured too: in `(return_statement "return" @kw)`, `@kw` refers to the `return` keyword.
 //!
 //! Unused query captures are considered errors, unless they start with an underscode. For example,
 //! a capture `@id` must be used within the stanza, but `@_id` does not.
@@ -217,10 +274,11 @@
 //!     query capture) and a variable name, separated by a period: `@node.variable`.
 //!
 //! Global variables are declared using a `global` declaration.  The external process that executes the
-//! graph DSL file must provide values for all declared global variables.  (It is not possible to define
-//! a global variable and give it a value from within the graph DSL.) The name of the global variable can
-//! be suffixed by a quantifier: '*' and '+' for lists, and '?' for optional values, which allows them to
-//! be used in iteration and conditional statements, respectively.
+//! graph DSL file must provide values for all declared global variables, unless the declaration provides
+//! a default string value, as in `global pkgname = ""`.  Execution fails before any stanza is executed if
+//! a required global variable is missing.  The name of the global variable can be suffixed by a
+//! quantifier: '*' and '+' for lists, and '?' for optional values, which allows them to be used in
+//! iteration and conditional statements, respectively.
 //!
 //! Local and scoped variables are created using `var` or `let` statements.  A `let` statement
 //! creates an **_immutable variable_**, whose value cannot be changed.  A `var` statement creates
@@ -255,6 +313,17 @@
 //! }
 //! ```
 //!
+//! A `let` statement can also unpack a list into several immutable variables, by listing them in
+//! square brackets.  It's an error if the list does not have exactly one element for each
+//! variable:
+//!
+//! ``` tsg
+//! (identifier) @id
+//! {
+//!   let [first, @id.second] = ["a", "b"]
+//! }
+//! ```
+//!
 //! Variables can be referenced anywhere that you can provide an expression.  It's an error if you
 //! try to reference a variable that hasn't been defined.
 //!
@@ -282,6 +351,53 @@
 //!
 //! [standard library]: functions/index.html
 //!
+//! # Comparisons
+//!
+//! Two expressions can be compared using the infix operators `==`, `!=`, `<`, `<=`, `>`, and
+//! `>=`, which produce a boolean:
+//!
+//! ``` tsg
+//! (function_definition name: (identifier) @name)
+//! {
+//!   if (source-text @name) == "__init__" {
+//!     ; ...
+//!   }
+//! }
+//! ```
+//!
+//! `==` and `!=` follow the same rules as the [`eq`][] function: null can be compared with any
+//! value, but other values can only be compared with values of the same type.  The ordering
+//! operators can only compare integers with integers, and strings with strings.  In particular,
+//! a capture is a syntax node, so use `(source-text @name)` to compare its text with a string.
+//!
+//! Comparisons are not associative, so `a == b == c` is an error.  Inside a function call, each
+//! parameter can be a comparison, so `(not (source-text @x) == "foo")` negates the result of the
+//! comparison.
+//!
+//! # Boolean operators
+//!
+//! Boolean values can be combined using the infix operators `&&` (and) and `||` (or), and negated
+//! using the prefix operator `!`.  The right-hand side of `&&` is only evaluated if the left-hand
+//! side is true, and the right-hand side of `||` only if the left-hand side is false.  All
+//! operands must be booleans.
+//!
+//! ``` tsg
+//! (function_definition name: (identifier) @name body: (block (_)* @stmts))
+//! {
+//!   let name = (source-text @name)
+//!   if name == "__init__" || (name != "__new__" && !(is-empty @stmts)) {
+//!     ; ...
+//!   }
+//! }
+//! ```
+//!
+//! From tightest to loosest binding, the operators are `!`, the comparison operators, `&&`, and
+//! `||`.  `&&` and `||` are left-associative.  Use parentheses to group expressions differently, as
+//! in `!(a == b)`.  A parenthesized expression cannot start with a bare variable name followed by
+//! something other than an operator, since that is a function call.
+//!
+//! [`eq`]: functions/index.html#eq
+//!
 //! # Graph nodes
 //!
 //! You can use this graph DSL to create any graph structure that you want.  There are no
@@ -362,6 +478,25 @@
 //! graph.  If multiple stanzas create edges between the same graph nodes, those are "collapsed"
 //! into a single edge.
 //!
+//! An edge can connect a graph node to itself.  If your graph model does not allow such self
+//! edges, you can reject them with the [`reject_self_edges`][crate::ExecutionConfig::reject_self_edges]
+//! execution option, which causes creating a self edge to fail.
+//!
+//! You can bind the edge that an `edge` statement creates to a variable, by giving the variable
+//! name before the source node.  The variable's value refers to the edge, and can be stored in
+//! attributes or compared with other edges.  The `edge-source` and `edge-sink` functions return
+//! its endpoints:
+//!
+//! ``` tsg
+//! (import_statement name: (_) @name)
+//! {
+//!   node @name.source
+//!   node @name.sink
+//!   edge @name.import = @name.source -> @name.sink
+//!   attr (@name.sink) imported_by = (edge-source @name.import)
+//! }
+//! ```
+//!
 //! # Attributes
 //!
 //! Graph nodes and edges have an associated set of **_attributes_**.  Each attribute has a name
@@ -382,6 +517,59 @@
 //! Note that you have to have already created the graph node or edge, and the graph node or edge
 //! must not already have an attribute with the same name.
 //!
+//! If you do want to replace the value of an existing attribute, use a `set-attr` statement
+//! instead.  It has the same syntax as `attr`, but overwrites any existing attribute with the same
+//! name, instead of causing an error:
+//!
+//! ``` tsg
+//! (import_statement name: (_) @name)
+//! {
+//!   set-attr (@name.sink) kind = "import"
+//! }
+//! ```
+//!
+//! When several statements set the same attribute, the value set last wins.  In lazy execution
+//! mode, statements from different stanzas are not necessarily executed in stanza order, so you
+//! should only rely on this within a single stanza.
+//!
+//! If an attribute value might be null, and you only want to add the attribute when it is not,
+//! add a `?` after the `attr` or `set-attr` keyword.  Any attribute whose value is null is then
+//! skipped, instead of being added with a null value:
+//!
+//! ``` tsg
+//! (function_definition return_type: (_)? @type) @func
+//! {
+//!   attr? (@func.node) return_type = @type
+//! }
+//! ```
+//!
+//! You can also add several attributes to a graph node at once from a single value, by writing
+//! `..` followed by an expression, among the attributes of an `attr` or `set-attr` statement.
+//! The value must be a list or set of pairs, each of which is a two-element list of an attribute
+//! name (as a string) and the attribute's value.  The pairs are added after the other attributes
+//! of the statement, following the same rules for existing attributes.  Attribute shorthands are
+//! not applied to names that come from such a value.
+//!
+//! ``` tsg
+//! (import_statement name: (_) @name)
+//! {
+//!   let attrs = [["kind", "module"], ["name", (source-text @name)]]
+//!   attr (@name.sink) ..attrs
+//! }
+//! ```
+//!
+//! You remove attributes from a graph node or edge using a `del-attr` statement, which takes a
+//! comma-separated list of attribute names.  Removing an attribute that does not exist is not an
+//! error:
+//!
+//! ``` tsg
+//! (import_statement name: (_) @name)
+//! {
+//!   del-attr (@name.sink) kind
+//!   del-attr (@name.source -> @name.sink) precedence
+//! }
+//! ```
+//!
 //! (Attributes might seem similar to scoped variables, but they are quite different.  Attributes
 //! are attached to graph nodes and edges, while scoped variables are attached to syntax nodes.
 //! More importantly, scoped variables only exist while executing the graph DSL file.  Once the
@@ -421,7 +609,30 @@
 //! string, or none of the regular expressions match.
 //!
 //! Within each regular expression's block, you can use `$0`, `$1`, etc., to refer to any capture
-//! groups in the regular expression.
+//! groups in the regular expression.  Named capture groups, such as `(?P<key>\w+)`, can also be
+//! referred to by name, as in `$key`.  It is an error to refer to a name that is not defined by the
+//! regular expression of the enclosing block.
+//!
+//! The last arm of a `scan` statement can be an `else` block, which is executed for any text that
+//! none of the regular expressions match: once for the text before each match that doesn't start
+//! at the current position, and once for any remaining text after the last match.  Within the
+//! `else` block, `$0` refers to the unmatched text, and it is an error to use any other regex
+//! capture.  Without an `else` block, unmatched text is skipped, and scanning stops when none of
+//! the regular expressions match the rest of the string.
+//!
+//! ``` tsg
+//! (identifier) @id
+//! {
+//!   scan (source-text @id) {
+//!     "[a-z]+" {
+//!       ; ...
+//!     }
+//!     else {
+//!       print "unexpected text: ", $0
+//!     }
+//!   }
+//! }
+//! ```
 //!
 //! The value being scanned must be local, which means it cannot be derived from scoped variables.
 //!
@@ -526,5 +737,18 @@
 //!    print "Hi! x = ", x
 //! }
 //! ```
+//!
+//! Strings are printed as they are, while other values are printed in a debugging representation,
+//! which puts quotes around the strings inside a list, for instance.  To control how a line is
+//! laid out, pass a call to the [`format`][] function, whose result is a string:
+//!
+//! ``` tsg
+//! (identifier) @id
+//! {
+//!    print (format "{} at row {}" (source-text @id) (start-row @id))
+//! }
+//! ```
+//!
+//! [`format`]: functions/index.html#format
 
 pub mod functions;
@@ -10,6 +10,8 @@ use std::collections::hash_map::Entry::Vacant;
 use std::collections::HashMap;
 use thiserror::Error;
 
+use crate::execution::error::ExecutionError;
+use crate::graph::ExpectedType;
 use crate::graph::Value;
 use crate::Identifier;
 
@@ -120,8 +122,10 @@ impl<V> MutVariables<V> for VariableMap<'_, V> {
 
 /// Environment of immutable variables
 pub struct Globals<'a> {
-    context: Option<&'a dyn Variables<Value>>,
+    context: Option<&'a Globals<'a>>,
     values: HashMap<Identifier, Value>,
+    defaults: HashMap<Identifier, Value>,
+    types: HashMap<Identifier, ExpectedType>,
 }
 
 impl<'a> Globals<'a> {
@@ -130,6 +134,8 @@ impl<'a> Globals<'a> {
         Self {
             context: None,
             values: HashMap::new(),
+            defaults: HashMap::new(),
+            types: HashMap::new(),
         }
     }
 
@@ -139,6 +145,8 @@ impl<'a> Globals<'a> {
         Self {
             context: Some(context),
             values: HashMap::new(),
+            defaults: HashMap::new(),
+            types: HashMap::new(),
         }
     }
 
@@ -154,11 +162,70 @@ impl<'a> Globals<'a> {
         }
     }
 
-    /// Returns the value of a variable, if it exists in this environment.
+    /// Adds a default value for a variable, returning an error if the variable already has a
+    /// default value in this environment.  The default value is only used if no value is
+    /// added for the variable, in this environment or any of its context environments.
+    pub fn add_default(&mut self, name: Identifier, value: Value) -> Result<(), VariableError> {
+        match self.defaults.entry(name) {
+            Vacant(v) => {
+                v.insert(value);
+                Ok(())
+            }
+            Occupied(o) => Err(VariableError::VariableAlreadyDefined(o.key().to_string())),
+        }
+    }
+
+    /// Returns the value of a variable, if it exists in this environment.  Values that were
+    /// added explicitly take precedence over default values.
     pub fn get(&self, name: &Identifier) -> Option<&Value> {
+        self.get_value(name).or_else(|| self.get_default(name))
+    }
+
+    fn get_value(&self, name: &Identifier) -> Option<&Value> {
         self.values
             .get(name)
-            .or_else(|| self.context.as_ref().map(|p| p.get(name)).flatten())
+            .or_else(|| self.context.and_then(|p| p.get_value(name)))
+    }
+
+    fn get_default(&self, name: &Identifier) -> Option<&Value> {
+        self.defaults
+            .get(name)
+            .or_else(|| self.context.and_then(|p| p.get_default(name)))
+    }
+
+    /// Declares the expected type of a variable.  Executing a file fails with
+    /// [`ExecutionError::GlobalTypeMismatch`][] if the variable is provided with a value of a
+    /// different type.  Variables without a declared type are not validated.
+    pub fn declare(&mut self, name: Identifier, expected_type: ExpectedType) {
+        self.types.insert(name, expected_type);
+    }
+
+    /// Returns the declared type of a variable, if it has one in this environment.
+    pub fn declared_type(&self, name: &Identifier) -> Option<ExpectedType> {
+        self.types
+            .get(name)
+            .copied()
+            .or_else(|| self.context.and_then(|p| p.declared_type(name)))
+    }
+
+    /// Checks that the values of all variables with a declared type match that type.
+    pub(crate) fn check_declared_types(&self) -> Result<(), ExecutionError> {
+        let mut globals = Some(self);
+        while let Some(current) = globals {
+            for (name, expected_type) in &current.types {
+                if let Some(value) = self.get(name) {
+                    if !expected_type.matches(value) {
+                        return Err(ExecutionError::GlobalTypeMismatch(
+                            name.to_string(),
+                            *expected_type,
+                            ExpectedType::of(value),
+                        ));
+                    }
+                }
+            }
+            globals = current.context;
+        }
+        Ok(())
     }
 
     /// Remove a variable from this enviroment, if it exists.
@@ -170,17 +237,21 @@ impl<'a> Globals<'a> {
         self.values.is_empty()
     }
 
+    /// Returns an iterator over the variables that were added to this environment, ordered by
+    /// name.  Variables from context environments and default values are not included.
     pub fn iter<'b>(&'b self) -> Iter<'b> {
-        Iter(self.values.iter())
+        let mut values = self.values.iter().collect::<Vec<_>>();
+        values.sort_unstable_by_key(|(name, _)| *name);
+        Iter(values.into_iter())
     }
 
-    /// Clears this enviroment.
+    /// Clears the values of this enviroment.  Default values and declared types are kept.
     pub fn clear(&mut self) {
         self.values.clear();
     }
 }
 
-pub struct Iter<'a>(std::collections::hash_map::Iter<'a, Identifier, Value>);
+pub struct Iter<'a>(std::vec::IntoIter<(&'a Identifier, &'a Value)>);
 
 impl<'a> std::iter::Iterator for Iter<'a> {
     type Item = (&'a Identifier, &'a Value);
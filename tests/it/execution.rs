@@ -5,14 +5,25 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::collections::HashMap;
+use std::sync::Mutex;
+
 use indoc::indoc;
 use tree_sitter::Parser;
+use tree_sitter::Point;
+use tree_sitter::QueryCursor;
+use tree_sitter::Range;
 use tree_sitter_graph::ast::File;
 use tree_sitter_graph::functions::Functions;
+use tree_sitter_graph::graph::ExpectedType;
+use tree_sitter_graph::graph::Graph;
+use tree_sitter_graph::graph::Value;
 use tree_sitter_graph::ExecutionConfig;
 use tree_sitter_graph::ExecutionError;
 use tree_sitter_graph::Identifier;
+use tree_sitter_graph::Location;
 use tree_sitter_graph::NoCancellation;
+use tree_sitter_graph::ParseError;
 use tree_sitter_graph::Variables;
 
 fn init_log() {
@@ -25,6 +36,31 @@ fn init_log() {
 }
 
 fn execute(python_source: &str, dsl_source: &str) -> Result<String, ExecutionError> {
+    execute_with_config(python_source, dsl_source, false, |config| config)
+}
+
+/// Executes `dsl_source` against `python_source` with the lazy executor if `lazy` is set, and with
+/// the execution options set by `configure`.
+fn execute_with_config(
+    python_source: &str,
+    dsl_source: &str,
+    lazy: bool,
+    configure: impl for<'a, 'g> FnOnce(ExecutionConfig<'a, 'g>) -> ExecutionConfig<'a, 'g>,
+) -> Result<String, ExecutionError> {
+    let mut globals = Variables::new();
+    globals
+        .add(Identifier::from("filename"), "test.py".into())
+        .map_err(|_| ExecutionError::DuplicateVariable("filename".into()))?;
+    execute_with_globals(python_source, dsl_source, &globals, lazy, configure)
+}
+
+fn execute_with_globals(
+    python_source: &str,
+    dsl_source: &str,
+    globals: &Variables,
+    lazy: bool,
+    configure: impl for<'a, 'g> FnOnce(ExecutionConfig<'a, 'g>) -> ExecutionConfig<'a, 'g>,
+) -> Result<String, ExecutionError> {
     init_log();
     let mut parser = Parser::new();
     parser.set_language(tree_sitter_python::language()).unwrap();
@@ -32,57 +68,905 @@ fn execute(python_source: &str, dsl_source: &str) -> Result<String, ExecutionErr
     let file =
         File::from_str(tree_sitter_python::language(), dsl_source).expect("Cannot parse file");
     let functions = Functions::stdlib();
-    let mut globals = Variables::new();
-    globals
-        .add(Identifier::from("filename"), "test.py".into())
-        .map_err(|_| ExecutionError::DuplicateVariable("filename".into()))?;
-    let mut config = ExecutionConfig::new(&functions, &globals);
-    let graph = file.execute(&tree, python_source, &mut config, &NoCancellation)?;
+    let config = configure(ExecutionConfig::new(&functions, globals).lazy(lazy));
+    let graph = file.execute(&tree, python_source, &config, &NoCancellation)?;
     let result = graph.pretty_print().to_string();
     Ok(result)
 }
 
 fn check_execution(python_source: &str, dsl_source: &str, expected_graph: &str) {
-    match execute(python_source, dsl_source) {
+    check_execution_with_config(
+        python_source,
+        dsl_source,
+        false,
+        |config| config,
+        expected_graph,
+    )
+}
+
+fn check_execution_with_config(
+    python_source: &str,
+    dsl_source: &str,
+    lazy: bool,
+    configure: impl for<'a, 'g> FnOnce(ExecutionConfig<'a, 'g>) -> ExecutionConfig<'a, 'g>,
+    expected_graph: &str,
+) {
+    match execute_with_config(python_source, dsl_source, lazy, configure) {
         Ok(actual_graph) => assert_eq!(actual_graph, expected_graph),
         Err(e) => panic!("Could not execute file: {}", e),
     }
 }
 
-fn fail_execution(python_source: &str, dsl_source: &str) {
-    if let Ok(_) = execute(python_source, dsl_source) {
-        panic!("Execution succeeded unexpectedly");
-    }
+fn fail_execution(python_source: &str, dsl_source: &str) {
+    if let Ok(_) = execute(python_source, dsl_source) {
+        panic!("Execution succeeded unexpectedly");
+    }
+}
+
+#[test]
+fn can_build_simple_graph() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module) @root
+          {
+            node node0
+            attr (node0) name = "node0", source = @root
+            var node1 = (node)
+            attr (node1) name = "node1"
+            edge node0 -> node1
+            attr (node0 -> node1) precedence = 14
+            node node2
+            attr (node2) name = "node2", parent = node1
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            name: "node0"
+            source: [syntax node module (1, 1)]
+          edge 0 -> 1
+            precedence: 14
+          node 1
+            name: "node1"
+          node 2
+            name: "node2"
+            parent: [graph node 1]
+        "#},
+    );
+}
+
+#[test]
+fn can_overwrite_attributes() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            node m
+            edge n -> m
+            attr (n) name = "first"
+            set-attr (n) name = "second"
+            set-attr (n) name = "third", kind = "module"
+            attr (n -> m) precedence = 1
+            set-attr (n -> m) precedence = 2
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            kind: "module"
+            name: "third"
+          edge 0 -> 1
+            precedence: 2
+          node 1
+        "#},
+    );
+}
+
+#[test]
+fn can_print_formatted_values() {
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module) @m
+          {
+            node n
+            print "plain: ", (node-type @m), " ", [(start-row @m), "x"], " ", n
+            print (format "formatted: {} at {}" (node-type @m) (start-row @m))
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("pass", None).unwrap();
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let output = Mutex::new(Vec::new());
+    let print_to = |line: &str| output.lock().unwrap().push(line.to_string());
+    let config = ExecutionConfig::new(&functions, &globals).print_to(&print_to);
+    file.execute(&tree, "pass", &config, &NoCancellation)
+        .expect("Could not execute file");
+    assert_eq!(
+        output.into_inner().unwrap(),
+        vec![
+            "plain: module [0, \"x\"] [graph node 0]",
+            "formatted: module at 0",
+        ]
+    );
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            print (format "missing: {}")
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_skip_null_attributes() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            node m
+            edge n -> m
+            attr? (n) missing = #null, name = "n"
+            set-attr? (n) name = #null
+            attr? (n -> m) precedence = #null, kind = "child"
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            name: "n"
+          edge 0 -> 1
+            kind: "child"
+          node 1
+        "#},
+    );
+}
+
+#[test]
+fn can_spread_attributes() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            let attrs = [["kind", "module"], ["name", "n"]]
+            attr (n) ..attrs, line = 1
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            kind: "module"
+            line: 1
+            name: "n"
+        "#},
+    );
+}
+
+#[test]
+fn cannot_spread_existing_attribute() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) name = "n", ..[["name", "m"]]
+          }
+        "#},
+    );
+}
+
+#[test]
+fn cannot_add_existing_attribute() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) name = "first"
+            attr (n) name = "second"
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_let_later_attributes_win() {
+    let dsl_source = indoc! {r#"
+      (module) @m
+      {
+        node @m.node
+        attr (@m.node) name = "first"
+      }
+
+      (module) @m
+      {
+        attr (@m.node) name = "second"
+      }
+    "#};
+    for lazy in [false, true] {
+        assert!(execute_with_config("pass", dsl_source, lazy, |config| config).is_err());
+        check_execution_with_config(
+            "pass",
+            dsl_source,
+            lazy,
+            |config| config.overwrite_attributes(true),
+            indoc! {r#"
+              node 0
+                name: "second"
+            "#},
+        );
+    }
+}
+
+#[test]
+fn can_reject_self_edges() {
+    let dsl_source = indoc! {r#"
+      (module)
+      {
+        node n
+        edge n -> n
+      }
+    "#};
+    for lazy in [false, true] {
+        check_execution_with_config(
+            "pass",
+            dsl_source,
+            lazy,
+            |config| config,
+            indoc! {r#"
+              node 0
+              edge 0 -> 0
+            "#},
+        );
+        match execute_with_config("pass", dsl_source, lazy, |config| {
+            config.reject_self_edges(true)
+        }) {
+            Err(ExecutionError::InContext(_, cause)) => {
+                assert!(matches!(*cause, ExecutionError::SelfEdge(_)), "{}", cause)
+            }
+            result => panic!("Expected a self edge error, got {:?}", result),
+        }
+    }
+}
+
+#[test]
+fn cannot_use_scoped_variables_before_they_are_defined() {
+    let dsl_source = indoc! {r#"
+      (identifier) @id
+      {
+        attr (@id.node) name = (source-text @id)
+      }
+
+      (identifier) @id
+      {
+        let @id.node = (node)
+      }
+    "#};
+    match execute("a", dsl_source) {
+        Err(ExecutionError::InContext(_, cause)) => match *cause {
+            ExecutionError::UndefinedScopedVariable(message) => {
+                assert!(message.contains("lazy execution"), "{}", message)
+            }
+            cause => panic!("Expected an undefined scoped variable, got {}", cause),
+        },
+        result => panic!("Expected an undefined scoped variable, got {:?}", result),
+    }
+    execute_with_config("a", dsl_source, true, |config| config)
+        .expect("Could not execute file lazily");
+}
+
+#[test]
+fn can_bind_edges_to_variables() {
+    for lazy in [false, true] {
+        check_execution_with_config(
+            "pass",
+            indoc! {r#"
+              (module)
+              {
+                node a
+                node b
+                edge e = a -> b
+                attr (a) edge = e, source = (edge-source e), sink = (edge-sink e)
+              }
+            "#},
+            lazy,
+            |config| config,
+            indoc! {r#"
+              node 0
+                edge: ([graph node 0] -> [graph node 1])
+                sink: [graph node 1]
+                source: [graph node 0]
+              edge 0 -> 1
+              node 1
+            "#},
+        );
+    }
+}
+
+#[test]
+fn can_read_back_attributes_of_graph_nodes() {
+    for lazy in [false, true] {
+        check_execution_with_config(
+            "a",
+            indoc! {r#"
+              (identifier) @id
+              {
+                node @id.node
+                attr (@id.node) name = (source-text @id)
+              }
+
+              (identifier) @id
+              {
+                node n
+                attr (n) name = (get-attr @id.node "name"), kind = (get-attr @id.node "kind")
+              }
+            "#},
+            lazy,
+            |config| config,
+            indoc! {r#"
+              node 0
+                name: "a"
+              node 1
+                kind: #null
+                name: "a"
+            "#},
+        );
+    }
+}
+
+#[test]
+fn can_execute_at_subtree() {
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (identifier) @id
+          {
+            node n
+            attr (n) name = (source-text @id)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let source = indoc! {r#"
+      def f(a):
+          pass
+      def g(b):
+          pass
+    "#};
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    let function = tree.root_node().named_child(1).unwrap();
+    assert_eq!(function.kind(), "function_definition");
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals).lazy(lazy);
+        let graph = file
+            .execute_at(function, source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        assert_eq!(
+            graph.pretty_print().to_string(),
+            indoc! {r#"
+              node 0
+                name: "g"
+              node 1
+                name: "b"
+            "#}
+        );
+    }
+}
+
+#[test]
+fn cannot_read_attributes_of_graph_nodes_eagerly_when_lazy() {
+    let dsl_source = indoc! {r#"
+      (module)
+      {
+        node n
+        attr (n) name = "a"
+        if (eq (get-attr n "name") "a") {
+          attr (n) found = #true
+        }
+      }
+    "#};
+    check_execution(
+        "pass",
+        dsl_source,
+        "node 0\n  found: #true\n  name: \"a\"\n",
+    );
+    match execute_with_config("pass", dsl_source, true, |config| config) {
+        Err(ExecutionError::InContext(_, cause)) => {
+            assert!(matches!(*cause, ExecutionError::ExpectedLocalValue(_)))
+        }
+        Err(e) => panic!("Unexpected error: {}", e),
+        Ok(_) => panic!("Execution succeeded unexpectedly"),
+    }
+}
+
+#[test]
+fn can_execute_selected_stanzas() {
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) stanza = "first"
+          }
+
+          stanza second
+          (module)
+          {
+            node n
+            attr (n) stanza = "second"
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    assert_eq!(file.stanzas[0].name, None);
+    assert_eq!(file.stanzas[1].name, Some(Identifier::from("second")));
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("pass", None).unwrap();
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals).lazy(lazy);
+        let expected = indoc! {r#"
+          node 0
+            stanza: "second"
+        "#};
+        let graph = file
+            .execute_stanza(1, &tree, "pass", &config, &NoCancellation)
+            .expect("Could not execute file");
+        assert_eq!(graph.pretty_print().to_string(), expected);
+        let graph = file
+            .execute_stanza("second", &tree, "pass", &config, &NoCancellation)
+            .expect("Could not execute file");
+        assert_eq!(graph.pretty_print().to_string(), expected);
+        assert!(matches!(
+            file.execute_stanza("third", &tree, "pass", &config, &NoCancellation),
+            Err(ExecutionError::UndefinedStanza(_))
+        ));
+        assert!(matches!(
+            file.execute_stanza(2, &tree, "pass", &config, &NoCancellation),
+            Err(ExecutionError::UndefinedStanza(_))
+        ));
+    }
+}
+
+#[test]
+fn can_execute_stanzas_in_parallel() {
+    let python_source = indoc! {r#"
+      import a
+      from b import c
+      print(a.d.f)
+    "#};
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (identifier) @id
+          {
+            node n
+            attr (n) name = (source-text @id), syntax = @id
+            print "identifier", (source-text @id)
+          }
+
+          (import_statement name: (_) @name)
+          {
+            node import
+            node name
+            edge import -> name
+            attr (name) syntax = @name
+            print "import", (source-text @name)
+          }
+
+          (call function: (_) @function)
+          {
+            node call
+            attr (call) function = @function, line = (start-row @function)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(python_source, None).unwrap();
+    assert!(file.is_stanza_local(&functions));
+    for collect_errors in [false, true] {
+        let output = Mutex::new(Vec::new());
+        let print_to = |line: &str| output.lock().unwrap().push(line.to_string());
+        let config = ExecutionConfig::new(&functions, &globals)
+            .collect_errors(collect_errors)
+            .print_to(&print_to);
+        let sequential = file
+            .execute(&tree, python_source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        let sequential_output = std::mem::take(&mut *output.lock().unwrap());
+        let parallel = file
+            .execute_parallel(&tree, python_source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        assert_eq!(
+            parallel.pretty_print().to_string(),
+            sequential.pretty_print().to_string()
+        );
+        assert!(parallel.structurally_eq(&sequential));
+        assert_eq!(output.into_inner().unwrap(), sequential_output);
+    }
+}
+
+#[test]
+fn can_execute_stanzas_sharing_scoped_variables_in_parallel() {
+    let python_source = indoc! {r#"
+      import a
+      from b import c
+    "#};
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (identifier) @id
+          {
+            let @id.node = (node)
+          }
+
+          (identifier) @id
+          {
+            attr (@id.node) name = (source-text @id)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(python_source, None).unwrap();
+    assert!(!file.is_stanza_local(&functions));
+    let config = ExecutionConfig::new(&functions, &globals);
+    let graph = file
+        .execute_parallel(&tree, python_source, &config, &NoCancellation)
+        .expect("Could not execute file");
+    assert_eq!(
+        graph.pretty_print().to_string(),
+        indoc! {r#"
+          node 0
+            name: "a"
+          node 1
+            name: "b"
+          node 2
+            name: "c"
+        "#}
+    );
+}
+
+#[test]
+fn parallel_execution_reports_errors_in_stanza_order() {
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module)
+          {
+            node n
+          }
+
+          (module)
+          {
+            let x = (plus "a")
+          }
+
+          (pass_statement)
+          {
+            let x = (plus 1 "a")
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("pass", None).unwrap();
+    for collect_errors in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals).collect_errors(collect_errors);
+        let sequential = match file.execute(&tree, "pass", &config, &NoCancellation) {
+            Ok(_) => panic!("Execution should fail"),
+            Err(err) => err.to_string(),
+        };
+        let parallel = match file.execute_parallel(&tree, "pass", &config, &NoCancellation) {
+            Ok(_) => panic!("Execution should fail"),
+            Err(err) => err.to_string(),
+        };
+        assert_eq!(parallel, sequential);
+    }
+}
+
+#[test]
+fn can_execute_stanzas_for_several_languages() {
+    let python_source = "settings = load('settings.json')\n";
+    let json_source = r#"{"debug": true, "level": 3}"#;
+    let file = File::from_str_with_languages(
+        tree_sitter_python::language(),
+        &[("json", tree_sitter_json::language())],
+        indoc! {r#"
+          (identifier) @id
+          {
+            node n
+            attr (n) python = (source-text @id)
+          }
+
+          language json
+          (pair key: (_) @key)
+          {
+            node n
+            attr (n) json = (source-text @key)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let python_tree = parser.parse(python_source, None).unwrap();
+    parser.set_language(tree_sitter_json::language()).unwrap();
+    let json_tree = parser.parse(json_source, None).unwrap();
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals).lazy(lazy);
+        let language_trees = HashMap::from([("json", (&json_tree, json_source))]);
+        let graph = file
+            .execute_with_languages(
+                &python_tree,
+                python_source,
+                &language_trees,
+                &config,
+                &NoCancellation,
+            )
+            .expect("Could not execute file");
+        assert_eq!(
+            graph.pretty_print().to_string(),
+            indoc! {r#"
+              node 0
+                python: "settings"
+              node 1
+                python: "load"
+              node 2
+                json: "\"debug\""
+              node 3
+                json: "\"level\""
+            "#}
+        );
+        let graph = file
+            .execute_with_languages(
+                &python_tree,
+                python_source,
+                &HashMap::new(),
+                &config,
+                &NoCancellation,
+            )
+            .expect("Could not execute file");
+        assert_eq!(graph.node_count(), 2);
+    }
+}
+
+#[test]
+fn can_execute_injected_trees() {
+    let source = "print(\"start\")\ns = \"x = y\"\n";
+    let start = source.find("x = y").unwrap();
+    let end = start + "x = y".len();
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (identifier) @id
+          {
+            node n
+            attr (n) name = (source-text @id), row = (start-row @id), column = (start-column @id)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals);
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    parser
+        .set_included_ranges(&[Range {
+            start_byte: start,
+            end_byte: end,
+            start_point: Point::new(1, 5),
+            end_point: Point::new(1, 10),
+        }])
+        .unwrap();
+    let injected_tree = parser.parse(source, None).unwrap();
+    let mut graph = Graph::new();
+    file.execute_into(&mut graph, &tree, source, &config, &NoCancellation)
+        .expect("Could not execute file");
+    file.execute_injected_into(&mut graph, &injected_tree, source, &config, &NoCancellation)
+        .expect("Could not execute file");
+    assert_eq!(
+        graph.pretty_print().to_string(),
+        indoc! {r#"
+          node 0
+            column: 0
+            name: "print"
+            row: 0
+          node 1
+            column: 0
+            name: "s"
+            row: 1
+          node 2
+            column: 5
+            name: "x"
+            row: 1
+          node 3
+            column: 9
+            name: "y"
+            row: 1
+        "#}
+    );
+}
+
+#[test]
+fn can_skip_matches_with_syntax_errors() {
+    let python_source = "a = 1\nb = (c +)\nd = 2\n";
+    let dsl_source = indoc! {r#"
+      (expression_statement (assignment left: (identifier) @name)) @_stmt
+      {
+        node n
+        attr (n) name = (source-text @name)
+      }
+    "#};
+    for lazy in [false, true] {
+        check_execution_with_config(
+            python_source,
+            dsl_source,
+            lazy,
+            |config| config,
+            indoc! {r#"
+              node 0
+                name: "a"
+              node 1
+                name: "b"
+              node 2
+                name: "d"
+            "#},
+        );
+        check_execution_with_config(
+            python_source,
+            dsl_source,
+            lazy,
+            |config| config.skip_matches_with_errors(true),
+            indoc! {r#"
+              node 0
+                name: "a"
+              node 1
+                name: "d"
+            "#},
+        );
+    }
+}
+
+#[test]
+fn can_collect_errors_of_several_statements() {
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) name = "first"
+            attr (n) name = "second"
+            attr (n) kind = (plus "module" 1)
+            attr (n) valid = #true
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("pass", None).unwrap();
+    let config = ExecutionConfig::new(&functions, &globals).collect_errors(true);
+    let mut graph = Graph::new();
+    let errors = match file.execute_into(&mut graph, &tree, "pass", &config, &NoCancellation) {
+        Err(ExecutionError::Multiple(errors)) => errors,
+        Err(e) => panic!("Unexpected error: {}", e),
+        Ok(_) => panic!("Execution succeeded unexpectedly"),
+    };
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(
+        &errors[0],
+        ExecutionError::InContext(_, cause) if matches!(**cause, ExecutionError::DuplicateAttribute(_))
+    ));
+    assert!(matches!(
+        &errors[1],
+        ExecutionError::InContext(_, cause) if matches!(**cause, ExecutionError::ExpectedInteger(_))
+    ));
+    assert_eq!(
+        graph.pretty_print().to_string(),
+        indoc! {r#"
+          node 0
+            name: "second"
+            valid: #true
+        "#}
+    );
+}
+
+#[test]
+fn can_display_multiple_errors() {
+    assert_eq!(
+        ExecutionError::Multiple(vec![]).to_string(),
+        "No errors during execution"
+    );
+    assert_eq!(
+        ExecutionError::Multiple(vec![
+            ExecutionError::UndefinedVariable("x".into()),
+            ExecutionError::UndefinedVariable("y".into()),
+        ])
+        .to_string(),
+        "2 errors during execution, the first being: Undefined variable x"
+    );
 }
 
 #[test]
-fn can_build_simple_graph() {
+fn can_use_integer_constants_with_prefixes() {
     check_execution(
         "pass",
         indoc! {r#"
-          (module) @root
+          (module)
           {
-            node node0
-            attr (node0) name = "node0", source = @root
-            var node1 = (node)
-            attr (node1) name = "node1"
-            edge node0 -> node1
-            attr (node0 -> node1) precedence = 14
-            node node2
-            attr (node2) name = "node2", parent = node1
+            node n
+            attr (n) bin = 0b1010, dec = 42, hex = 0xff, oct = 0o17
           }
         "#},
         indoc! {r#"
           node 0
-            name: "node0"
-            source: [syntax node module (1, 1)]
+            bin: 10
+            dec: 42
+            hex: 255
+            oct: 15
+        "#},
+    );
+}
+
+#[test]
+fn can_delete_attributes() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            node m
+            edge n -> m
+            attr (n) name = "n", scratch = 1
+            attr (n -> m) precedence = 1, scratch = 2
+            del-attr (n) scratch
+            del-attr (n -> m) scratch, missing
+            del-attr (m) missing
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            name: "n"
           edge 0 -> 1
-            precedence: 14
+            precedence: 1
           node 1
-            name: "node1"
-          node 2
-            name: "node2"
-            parent: [graph node 1]
         "#},
     );
 }
@@ -216,6 +1100,77 @@ fn scoped_variables_carry_across_stanzas() {
     );
 }
 
+#[test]
+fn can_count_graph_nodes_and_syntax_nodes() {
+    let python_source = indoc! {r#"
+      import a
+      from b import c
+      print(a.d.f)
+    "#};
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (identifier) @id
+          {
+            let @id.node = (node)
+          }
+
+          (identifier) @id
+          {
+            attr (@id.node) name = (source-text @id)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(python_source, None).unwrap();
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals).lazy(lazy);
+        let graph = file
+            .execute(&tree, python_source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        assert_eq!(graph.node_count(), 7);
+        assert_eq!(graph.syntax_node_count(), 7);
+    }
+}
+
+#[test]
+fn can_get_graph_node_for_syntax_node() {
+    check_execution(
+        indoc! {r#"
+          import a
+          from b import c
+        "#},
+        indoc! {r#"
+          (identifier) @id
+          {
+            let n = (node-for-syntax @id)
+            attr (n) name = (source-text @id)
+          }
+
+          (identifier) @id
+          {
+            let n = (node-for-syntax @id)
+            attr (n) same = (eq n (node-for-syntax @id))
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            name: "a"
+            same: #true
+          node 1
+            name: "b"
+            same: #true
+          node 2
+            name: "c"
+            same: #true
+        "#},
+    );
+}
+
 #[test]
 fn can_match_stanza_multiple_times() {
     check_execution(
@@ -305,6 +1260,162 @@ fn cannot_omit_global_variable() {
     );
 }
 
+#[test]
+fn omitted_global_variable_is_reported_before_execution() {
+    match execute(
+        "pass",
+        indoc! {r#"
+          global root
+        "#},
+    ) {
+        Err(ExecutionError::MissingGlobalVariable(name)) => assert_eq!(name, "root"),
+        Err(e) => panic!("Unexpected error: {}", e),
+        Ok(_) => panic!("Execution succeeded unexpectedly"),
+    }
+}
+
+#[test]
+fn can_use_list_and_set_global_variables() {
+    let mut globals = Variables::new();
+    globals
+        .add(
+            Identifier::from("numbers"),
+            (0..1000u32).map(Value::from).collect::<Vec<_>>().into(),
+        )
+        .expect("Cannot add global");
+    globals
+        .add(
+            Identifier::from("names"),
+            Value::Set(vec!["b".into(), "a".into()].into_iter().collect()),
+        )
+        .expect("Cannot add global");
+    let graph = execute_with_globals(
+        "pass",
+        indoc! {r#"
+          global numbers*
+          global names
+
+          (module) {
+            node n
+            for number in numbers {
+              if number == 999 {
+                attr (n) last = number
+              }
+            }
+            attr (n) count = (length numbers)
+            attr (n) names = names
+          }
+        "#},
+        &globals,
+        false,
+        |config| config,
+    )
+    .expect("Cannot execute file");
+    assert_eq!(
+        graph,
+        indoc! {r#"
+          node 0
+            count: 1000
+            last: 999
+            names: {"a", "b"}
+        "#}
+    );
+}
+
+#[test]
+fn can_use_default_global_value() {
+    let mut globals = Variables::new();
+    globals
+        .add_default(Identifier::from("root_path"), ".".into())
+        .expect("Cannot add default");
+    let graph = execute_with_globals(
+        "pass",
+        indoc! {r#"
+          global root_path
+
+          (module) {
+            node n
+            attr (n) path = root_path
+          }
+        "#},
+        &globals,
+        false,
+        |config| config,
+    )
+    .expect("Cannot execute file");
+    assert_eq!(
+        graph,
+        indoc! {r#"
+          node 0
+            path: "."
+        "#}
+    );
+}
+
+#[test]
+fn explicit_global_value_overrides_default() {
+    let mut globals = Variables::new();
+    globals
+        .add_default(Identifier::from("root_path"), ".".into())
+        .expect("Cannot add default");
+    globals
+        .add(Identifier::from("root_path"), "/src".into())
+        .expect("Cannot add global");
+    let graph = execute_with_globals(
+        "pass",
+        indoc! {r#"
+          global root_path
+
+          (module) {
+            node n
+            attr (n) path = root_path
+          }
+        "#},
+        &globals,
+        false,
+        |config| config,
+    )
+    .expect("Cannot execute file");
+    assert_eq!(
+        graph,
+        indoc! {r#"
+          node 0
+            path: "/src"
+        "#}
+    );
+}
+
+#[test]
+fn cannot_pass_global_variable_of_wrong_declared_type() {
+    let mut globals = Variables::new();
+    globals.declare(Identifier::from("filename"), ExpectedType::String);
+    globals
+        .add(Identifier::from("filename"), 42.into())
+        .expect("Cannot add global");
+    match execute_with_globals(
+        "pass",
+        indoc! {r#"
+          global filename
+
+          (module) {
+            node n
+            attr (n) name = (replace filename "\\.py$" "")
+          }
+        "#},
+        &globals,
+        false,
+        |config| config,
+    ) {
+        Err(ExecutionError::GlobalTypeMismatch(name, expected, actual)) => {
+            assert_eq!(name, "filename");
+            assert_eq!(expected, ExpectedType::String);
+            assert_eq!(actual, ExpectedType::Integer);
+        }
+        Err(e) => panic!("Unexpected error: {}", e),
+        Ok(_) => panic!("Execution succeeded unexpectedly"),
+    }
+}
+
 #[test]
 fn cannot_pass_string_to_global_list_variable() {
     fail_execution(
@@ -315,6 +1426,42 @@ fn cannot_pass_string_to_global_list_variable() {
     );
 }
 
+#[test]
+fn can_destructure_lists() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module) @m
+          {
+            node n
+            let [name, @m.kind] = ["a", (node)]
+            attr (n) name = name, kind = @m.kind
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            kind: [graph node 1]
+            name: "a"
+          node 1
+        "#},
+    );
+}
+
+#[test]
+fn cannot_destructure_list_of_wrong_length() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            let [a, b] = ["a", "b", "c"]
+            attr (n) a = a
+          }
+        "#},
+    );
+}
+
 #[test]
 fn can_use_variable_multiple_times() {
     check_execution(
@@ -549,8 +1696,128 @@ fn can_execute_else() {
           }
         "#},
         indoc! {r#"
-          node 0
-            val: 1
+          node 0
+            val: 1
+        "#},
+    );
+}
+
+#[test]
+fn can_execute_if_comparison() {
+    check_execution(
+        indoc! {r#"
+          def foo():
+            pass
+          def bar():
+            pass
+        "#},
+        indoc! {r#"
+          (function_definition name: (identifier) @x)
+          {
+            node n
+            if (source-text @x) == "foo" {
+              attr (n) foo = #true
+            }
+            attr (n) ne = (source-text @x) != "foo", lt = 1 < 2, ge = "a" >= "b"
+            attr (n) not_foo = (not (source-text @x) == "foo")
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            foo: #true
+            ge: #false
+            lt: #true
+            ne: #false
+            not_foo: #false
+          node 1
+            ge: #false
+            lt: #true
+            ne: #true
+            not_foo: #true
+        "#},
+    );
+}
+
+#[test]
+fn cannot_compare_values_of_different_types() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) lt = 1 < "2"
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_execute_if_boolean_operators() {
+    check_execution(
+        indoc! {r#"
+          def foo():
+            pass
+          def bar():
+            pass
+        "#},
+        indoc! {r#"
+          (function_definition name: (identifier) @x)
+          {
+            node n
+            let name = (source-text @x)
+            if name == "foo" && (#false || !(name == "bar")) {
+              attr (n) foo = #true
+            }
+            attr (n) and = #true && !#true, or = #false || #true
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            and: #false
+            foo: #true
+            or: #true
+          node 1
+            and: #false
+            or: #true
+        "#},
+    );
+}
+
+#[test]
+fn boolean_operators_short_circuit() {
+    // the right-hand sides would fail if evaluated, since they compare values of different types
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            if #false && (eq 1 "1") {
+              attr (n) and = #true
+            }
+            if #true || 1 < "1" {
+              attr (n) or = #true
+            }
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            or: #true
+        "#},
+    );
+}
+
+#[test]
+fn cannot_apply_boolean_operators_to_non_booleans() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) and = #true && 1
+          }
         "#},
     );
 }
@@ -888,6 +2155,91 @@ fn can_execute_scan_of_local_call_expression() {
     );
 }
 
+#[test]
+fn can_execute_scan_with_named_regex_captures() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            scan "key=value" {
+              "(?P<key>\\w+)=(\\w+)" {
+                node n
+                attr (n) key = $key, value = $2
+              }
+            }
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            key: "key"
+            value: "value"
+        "#},
+    );
+}
+
+#[test]
+fn can_execute_scan_with_default_arm() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            scan "abc=1 ??? def=2!" {
+              "(\\w+)=\\d+" {
+                node n
+                attr (n) key = $1
+              }
+              else {
+                node n
+                attr (n) unmatched = $0
+              }
+            }
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            key: "abc"
+          node 1
+            unmatched: " ??? "
+          node 2
+            key: "def"
+          node 3
+            unmatched: "!"
+        "#},
+    );
+}
+
+#[test]
+fn can_execute_scan_of_multibyte_text() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            scan "café=1 🦀 naïve=2" {
+              "\\w+=\\d+" {
+                node n
+                attr (n) match = $0
+              }
+              else {
+                node n
+                attr (n) unmatched = $0
+              }
+            }
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            match: "café=1"
+          node 1
+            unmatched: " 🦀 "
+          node 2
+            match: "naïve=2"
+        "#},
+    );
+}
+
 #[test]
 fn can_execute_scan_of_local_variable() {
     check_execution(
@@ -936,3 +2288,648 @@ fn can_execute_shorthand() {
         "#},
     );
 }
+
+#[test]
+fn can_execute_included_stanzas() {
+    let python_source = indoc! { r#"
+      def f():
+        pass
+    "#};
+    let common = indoc! {r#"
+      (function_definition name: (identifier) @name) {
+        node n
+        attr (n) name = (source-text @name)
+      }
+    "#};
+    let dsl_source = indoc! {r#"
+      include "common.tsg"
+      (module) {
+        node n
+        attr (n) module = #true
+      }
+    "#};
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(python_source, None).unwrap();
+    let file = File::from_str_with_resolver(tree_sitter_python::language(), dsl_source, |_| {
+        Ok(common.to_string())
+    })
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals);
+    let graph = file
+        .execute(&tree, python_source, &config, &NoCancellation)
+        .expect("Could not execute file");
+    assert_eq!(
+        graph.pretty_print().to_string(),
+        indoc! {r#"
+          node 0
+            name: "f"
+          node 1
+            module: #true
+        "#}
+    );
+
+    let file = File::from_str_with_resolver(tree_sitter_python::language(), dsl_source, |_| {
+        Ok("(function_definition) {\n  node n\n  attr (n) x = (plus \"a\" 1)\n}\n".to_string())
+    })
+    .expect("Cannot parse file");
+    match file.execute(&tree, python_source, &config, &NoCancellation) {
+        Err(ExecutionError::InContext(context, _)) => assert!(
+            context
+                .to_string()
+                .contains("in stanza at (1, 1) in included file \"common.tsg\""),
+            "{}",
+            context
+        ),
+        result => panic!("Expected an error in context, got {:?}", result.map(|_| ())),
+    }
+}
+
+#[test]
+fn repeated_execution_produces_identical_output() {
+    let python_source = indoc! { r#"
+      import a
+      from b import c
+      def f(x, y):
+        pass
+    "#};
+    let dsl_source = indoc! {r#"
+      (identifier) @id {
+        node n
+        attr (n) name = (source-text @id), source = @id, type = (node-type @id)
+        attr (n) a = 1, b = 2, c = 3, d = 4, e = 5, f = 6, g = 7, h = 8
+      }
+    "#};
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(python_source, None).unwrap();
+    let file =
+        File::from_str(tree_sitter_python::language(), dsl_source).expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals);
+    let mut outputs = Vec::new();
+    for _ in 0..2 {
+        let graph = file
+            .execute(&tree, python_source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        outputs.push((
+            graph.pretty_print().to_string(),
+            serde_json::to_string(&graph).unwrap(),
+        ));
+    }
+    assert_eq!(outputs[0], outputs[1]);
+}
+
+#[test]
+fn can_execute_reusing_query_cursor() {
+    let python_source = indoc! { r#"
+      def f(x):
+        pass
+      def g(y):
+        pass
+    "#};
+    let dsl_source = indoc! {r#"
+      (function_definition name: (identifier) @name) {
+        node n
+        attr (n) name = (source-text @name)
+      }
+      (parameters (identifier) @param) {
+        node n
+        attr (n) param = (source-text @param)
+      }
+    "#};
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(python_source, None).unwrap();
+    let file =
+        File::from_str(tree_sitter_python::language(), dsl_source).expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let mut cursor = QueryCursor::new();
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals).lazy(lazy);
+        let expected = file
+            .execute(&tree, python_source, &config, &NoCancellation)
+            .expect("Could not execute file")
+            .pretty_print()
+            .to_string();
+        for _ in 0..2 {
+            let graph = file
+                .execute_reusing(&mut cursor, &tree, python_source, &config, &NoCancellation)
+                .expect("Could not execute file");
+            assert_eq!(graph.pretty_print().to_string(), expected);
+        }
+    }
+}
+
+#[test]
+fn can_execute_large_list_capture() {
+    let count = 5000;
+    let python_source = (0..count).map(|i| format!("x{}\n", i)).collect::<String>();
+    let dsl_source = indoc! {r#"
+      (module (_)* @elements) {
+        node n
+        attr (n) count = (length @elements)
+        for element in @elements {
+          node m
+          edge n -> m
+        }
+      }
+    "#};
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(&python_source, None).unwrap();
+    let file =
+        File::from_str(tree_sitter_python::language(), dsl_source).expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals);
+    let start = std::time::Instant::now();
+    let graph = file
+        .execute(&tree, &python_source, &config, &NoCancellation)
+        .expect("Could not execute file");
+    log::info!(
+        "executed {} element capture in {:?}",
+        count,
+        start.elapsed()
+    );
+    assert_eq!(graph.node_count(), count + 1);
+    assert_eq!(
+        graph.iter_nodes().next().map(|n| graph[n].edge_count()),
+        Some(count)
+    );
+}
+
+#[test]
+fn can_execute_into_graph_with_capacity() {
+    let python_source = indoc! { r#"
+      def f(x):
+        pass
+    "#};
+    let dsl_source = indoc! {r#"
+      (function_definition name: (identifier) @name) @def {
+        node n
+        attr (n) name = (source-text @name), def = @def
+      }
+    "#};
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(python_source, None).unwrap();
+    let file =
+        File::from_str(tree_sitter_python::language(), dsl_source).expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals);
+    let expected = file
+        .execute(&tree, python_source, &config, &NoCancellation)
+        .expect("Could not execute file");
+    let mut graph = Graph::with_capacity(16, 16);
+    file.execute_into(&mut graph, &tree, python_source, &config, &NoCancellation)
+        .expect("Could not execute file");
+    assert_eq!(
+        graph.pretty_print().to_string(),
+        expected.pretty_print().to_string()
+    );
+}
+
+#[test]
+fn can_record_creation_locations() {
+    let python_source = indoc! { r#"
+      pass
+    "#};
+    let dsl_source = indoc! {r#"
+      (module) {
+        node a
+
+        node b
+        edge a -> b
+      }
+    "#};
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(python_source, None).unwrap();
+    let file =
+        File::from_str(tree_sitter_python::language(), dsl_source).expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(lazy)
+            .record_locations(true);
+        let graph = file
+            .execute(&tree, python_source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        let nodes = graph.iter_nodes().collect::<Vec<_>>();
+        let a = &graph[nodes[0]];
+        let b = &graph[nodes[1]];
+        let a_loc = a.created_at().expect("Missing location for node a");
+        let b_loc = b.created_at().expect("Missing location for node b");
+        assert_eq!((a_loc.row, a_loc.column), (1, 2));
+        assert_eq!((b_loc.row, b_loc.column), (3, 2));
+        let edge = a.get_edge(nodes[1]).expect("Missing edge");
+        let edge_loc = edge.created_at().expect("Missing location for edge");
+        assert_eq!((edge_loc.row, edge_loc.column), (4, 2));
+    }
+
+    let config = ExecutionConfig::new(&functions, &globals);
+    let graph = file
+        .execute(&tree, python_source, &config, &NoCancellation)
+        .expect("Could not execute file");
+    for node in graph.iter_nodes() {
+        assert!(graph[node].created_at().is_none());
+    }
+}
+
+#[test]
+fn can_execute_files_with_shared_functions() {
+    let python_source = "pass";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(python_source, None).unwrap();
+    let file1 = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module) {
+            node n
+            attr (n) matches = (matches "pass" "^p")
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let file2 = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (pass_statement) {
+            node n
+            attr (n) matches = (matches "pass" "s$")
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+
+    let functions = Functions::stdlib();
+    let shared = &functions;
+    let globals = Variables::new();
+    let config1 = ExecutionConfig::new(shared, &globals);
+    let config2 = ExecutionConfig::new(shared, &globals).lazy(true);
+    for _ in 0..2 {
+        for (file, config) in [(&file1, &config1), (&file2, &config2)] {
+            let graph = file
+                .execute(&tree, python_source, config, &NoCancellation)
+                .expect("Could not execute file");
+            assert_eq!(
+                graph.pretty_print().to_string(),
+                "node 0\n  matches: #true\n"
+            );
+        }
+    }
+}
+
+#[test]
+fn can_run_execution_against_many_trees() {
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          global filename
+
+          (identifier) @id {
+            node n
+            attr (n) file = filename, name = (source-text @id)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let empty = Variables::new();
+    let config = ExecutionConfig::new(&functions, &empty);
+    let mut execution = file.execution(&config);
+
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let mut graphs = Vec::new();
+    for (filename, python_source) in [("a.py", "x"), ("b.py", "y = z")] {
+        let tree = parser.parse(python_source, None).unwrap();
+        let mut globals = Variables::new();
+        globals
+            .add(Identifier::from("filename"), filename.into())
+            .expect("Cannot add global");
+        let mut graph = Graph::new();
+        execution
+            .run(&tree, python_source, &mut graph, &globals, &NoCancellation)
+            .expect("Could not execute file");
+        graphs.push(graph.pretty_print().to_string());
+    }
+    assert_eq!(
+        graphs,
+        vec![
+            indoc! {r#"
+              node 0
+                file: "a.py"
+                name: "x"
+            "#},
+            indoc! {r#"
+              node 0
+                file: "b.py"
+                name: "y"
+              node 1
+                file: "b.py"
+                name: "z"
+            "#},
+        ]
+    );
+}
+
+#[test]
+fn cloned_files_share_compiled_queries() {
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (identifier) @id {
+            node n
+            attr (n) name = (source-text @id)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let reloaded = file.clone();
+    assert!(std::ptr::eq(
+        file.combined_query().unwrap(),
+        reloaded.combined_query().unwrap(),
+    ));
+    assert!(std::ptr::eq(
+        file.stanzas()[0].query(),
+        reloaded.stanzas()[0].query(),
+    ));
+
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("x", None).unwrap();
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals);
+    let graph = reloaded
+        .execute(&tree, "x", &config, &NoCancellation)
+        .expect("Could not execute file");
+    assert_eq!(
+        graph.pretty_print().to_string(),
+        indoc! {r#"
+          node 0
+            name: "x"
+        "#}
+    );
+}
+
+#[test]
+fn can_execute_one_match_at_a_time() {
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (identifier) @id {
+            node n
+            attr (n) name = (source-text @id)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals);
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let python_source = "x = y + z";
+    let tree = parser.parse(python_source, None).unwrap();
+    let mut names = Vec::new();
+    file.execute_per_match(&tree, python_source, &config, &NoCancellation, |graph| {
+        assert_eq!(graph.node_count(), 1);
+        names.push(graph.pretty_print().to_string());
+        Ok(())
+    })
+    .expect("Could not execute file");
+    assert_eq!(
+        names,
+        vec![
+            "node 0\n  name: \"x\"\n",
+            "node 0\n  name: \"y\"\n",
+            "node 0\n  name: \"z\"\n",
+        ]
+    );
+}
+
+#[test]
+fn cannot_evaluate_too_deeply_nested_expressions() {
+    let nested = |depth: usize| {
+        format!(
+            "(module) {{\n  node n\n  attr (n) value = {}#true{}\n}}\n",
+            "(not ".repeat(depth),
+            ")".repeat(depth),
+        )
+    };
+    match File::from_str(tree_sitter_python::language(), &nested(5000)) {
+        Err(ParseError::ExpressionTooDeep(_, _)) => {}
+        Err(e) => panic!("Unexpected error: {}", e),
+        Ok(_) => panic!("Parse succeeded unexpectedly"),
+    }
+
+    let depth = 50;
+    for lazy in [false, true] {
+        match execute_with_config("pass", &nested(depth), lazy, |config| {
+            config.max_expression_depth(depth - 1)
+        }) {
+            Err(ExecutionError::InContext(_, cause)) => {
+                assert!(matches!(*cause, ExecutionError::ExceededRecursionDepth(_)))
+            }
+            Err(e) => panic!("Unexpected error: {}", e),
+            Ok(_) => panic!("Execution succeeded unexpectedly"),
+        }
+        check_execution_with_config(
+            "pass",
+            &nested(depth),
+            lazy,
+            |config| config.max_expression_depth(depth + 1),
+            "node 0\n  value: #true\n",
+        );
+    }
+}
+
+#[test]
+fn can_report_unmatched_stanzas() {
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (identifier) {
+            node n
+          }
+
+          (class_definition) {
+            node n
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let python_source = "x = 1";
+    let tree = parser.parse(python_source, None).unwrap();
+    for lazy in [false, true] {
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(lazy)
+            .report_unmatched_stanzas(true);
+        let mut execution = file.execution(&config);
+        let mut graph = Graph::new();
+        execution
+            .run(&tree, python_source, &mut graph, &globals, &NoCancellation)
+            .expect("Could not execute file");
+        assert_eq!(
+            execution.unmatched_stanzas(),
+            &[Location { row: 4, column: 0 }]
+        );
+    }
+}
+
+#[test]
+fn can_report_unmatched_stanzas_to_callback() {
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (class_definition) {
+            node n
+          }
+
+          (identifier) {
+            node n
+          }
+
+          (function_definition) {
+            node n
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let python_source = "x = 1";
+    let tree = parser.parse(python_source, None).unwrap();
+    let expected = vec![
+        Location { row: 0, column: 0 },
+        Location { row: 8, column: 0 },
+    ];
+    for lazy in [false, true] {
+        let unmatched = Mutex::new(Vec::new());
+        let unmatched_stanzas_to = |location: Location| unmatched.lock().unwrap().push(location);
+        let config = ExecutionConfig::new(&functions, &globals)
+            .lazy(lazy)
+            .report_unmatched_stanzas_to(&unmatched_stanzas_to);
+        file.execute(&tree, python_source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        assert_eq!(std::mem::take(&mut *unmatched.lock().unwrap()), expected);
+        let mut graph = Graph::new();
+        file.execute_into(&mut graph, &tree, python_source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        assert_eq!(std::mem::take(&mut *unmatched.lock().unwrap()), expected);
+        file.execute_parallel(&tree, python_source, &config, &NoCancellation)
+            .expect("Could not execute file");
+        assert_eq!(unmatched.into_inner().unwrap(), expected);
+    }
+}
+
+#[test]
+fn can_execute_several_files_into_one_graph() {
+    let module_source = "import foo";
+    let embedded_source = "x = 1";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let module_tree = parser.parse(module_source, None).unwrap();
+    let embedded_tree = parser.parse(embedded_source, None).unwrap();
+    let module_file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (import_statement name: (_) @name) {
+            node n
+            attr (n) import = (source-text @name)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let embedded_file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (assignment left: (_) @left) {
+            node n
+            attr (n) assign = (source-text @left)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals);
+    let mut graph = Graph::new();
+    module_file
+        .execute_into(
+            &mut graph,
+            &module_tree,
+            module_source,
+            &config,
+            &NoCancellation,
+        )
+        .expect("Could not execute file");
+    embedded_file
+        .execute_into(
+            &mut graph,
+            &embedded_tree,
+            embedded_source,
+            &config,
+            &NoCancellation,
+        )
+        .expect("Could not execute file");
+    assert_eq!(
+        graph.pretty_print().to_string(),
+        indoc! {r#"
+          node 0
+            import: "foo"
+          node 1
+            assign: "x"
+        "#}
+    );
+}
+
+#[test]
+fn cannot_exceed_match_limit() {
+    let python_source = indoc! {r#"
+      x = 1
+      y = 2
+    "#};
+    let dsl_source = indoc! {r#"
+      (module) {
+        node m
+      }
+
+      (_) @x {
+        node n
+        attr (n) kind = (node-type @x)
+      }
+    "#};
+    for lazy in [false, true] {
+        match execute_with_config(python_source, dsl_source, lazy, |config| {
+            config.match_limit(3)
+        }) {
+            Err(ExecutionError::ExceededMatchLimit(message)) => {
+                assert_eq!(message, "of 3 matches for stanza at (5, 1)")
+            }
+            Err(e) => panic!("Unexpected error {}", e),
+            Ok(_) => panic!("Execution succeeded unexpectedly"),
+        }
+        execute_with_config(python_source, dsl_source, lazy, |config| {
+            config.match_limit(100)
+        })
+        .expect("Could not execute file");
+    }
+}
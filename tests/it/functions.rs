@@ -5,10 +5,20 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
 use indoc::indoc;
 use tree_sitter::Parser;
 use tree_sitter_graph::ast::File;
+use tree_sitter_graph::functions::stdlib;
+use tree_sitter_graph::functions::Function;
 use tree_sitter_graph::functions::Functions;
+use tree_sitter_graph::functions::Parameters;
+use tree_sitter_graph::graph::Graph;
+use tree_sitter_graph::graph::Value;
+use tree_sitter_graph::CancellationError;
 use tree_sitter_graph::ExecutionConfig;
 use tree_sitter_graph::ExecutionError;
 use tree_sitter_graph::Identifier;
@@ -25,19 +35,29 @@ fn init_log() {
 }
 
 fn execute(python_source: &str, dsl_source: &str) -> Result<String, ExecutionError> {
+    execute_with_functions(python_source, dsl_source, &Functions::stdlib(), false)
+}
+
+/// Executes `dsl_source` against `python_source` using the functions in `functions`, with the lazy
+/// executor if `lazy` is set.
+fn execute_with_functions(
+    python_source: &str,
+    dsl_source: &str,
+    functions: &Functions,
+    lazy: bool,
+) -> Result<String, ExecutionError> {
     init_log();
     let mut parser = Parser::new();
     parser.set_language(tree_sitter_python::language()).unwrap();
     let tree = parser.parse(python_source, None).unwrap();
     let file =
         File::from_str(tree_sitter_python::language(), dsl_source).expect("Cannot parse file");
-    let functions = Functions::stdlib();
     let mut globals = Variables::new();
     globals
         .add(Identifier::from("filename"), "test.py".into())
         .map_err(|_| ExecutionError::DuplicateVariable("filename".into()))?;
-    let mut config = ExecutionConfig::new(&functions, &globals);
-    let graph = file.execute(&tree, python_source, &mut config, &NoCancellation)?;
+    let config = ExecutionConfig::new(functions, &globals).lazy(lazy);
+    let graph = file.execute(&tree, python_source, &config, &NoCancellation)?;
     let result = graph.pretty_print().to_string();
     Ok(result)
 }
@@ -91,6 +111,25 @@ fn can_eq_nonequal_bools() {
     );
 }
 
+#[test]
+fn can_eq_syntax_nodes_by_identity() {
+    check_execution(
+        "x = x",
+        indoc! {r#"
+          (assignment left: (identifier) @a @b right: (identifier) @c)
+          {
+            node n
+            attr (n) same = (eq @a @b), distinct = (eq @a @c)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            distinct: #false
+            same: #true
+        "#},
+    );
+}
+
 #[test]
 fn cannot_eq_bool_and_string() {
     fail_execution(
@@ -180,55 +219,1081 @@ fn cannot_format_with_unexpected_closing_brace() {
 }
 
 #[test]
-fn can_concat_lists() {
+fn can_hash_values() {
     check_execution(
         "pass",
         indoc! {r#"
           (module)
           {
             node n
-            attr (n) xs = (concat [1, 2] [] [3, 4, 5])
+            attr (n) hello = (hash "hello"), empty = (hash ""), list = (hash [1, 2])
           }
         "#},
         indoc! {r#"
           node 0
-            xs: [1, 2, 3, 4, 5]
+            empty: 2166136261
+            hello: 1335831723
+            list: 3640095416
         "#},
     );
 }
 
 #[test]
-fn can_join_list_with_separator() {
+fn can_match_regex_in_condition() {
+    check_execution(
+        indoc! { r#"
+          _private = 1
+          public = 2
+        "#},
+        indoc! {r#"
+          (identifier) @id
+          {
+            node n
+            if (matches (source-text @id) "^_") {
+              attr (n) private = (source-text @id)
+            } else {
+              attr (n) public = (source-text @id)
+            }
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            private: "_private"
+          node 1
+            public: "public"
+        "#},
+    );
+}
+
+#[test]
+fn cannot_match_invalid_regex() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) m = (matches "foo" "(")
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_replace_regex_with_backreferences() {
     check_execution(
         "pass",
         indoc! {r#"
           (module)
           {
             node n
-            attr (n) str = (join [1, 2, 3] ".")
+            attr (n) swapped = (regex-replace "foo123" "([a-z]+)(?P<digits>[0-9]+)" "${digits}$1")
           }
         "#},
         indoc! {r#"
           node 0
-            str: "1.2.3"
+            swapped: "123foo"
         "#},
     );
 }
 
 #[test]
-fn can_join_list_without_separator() {
+fn cannot_replace_invalid_regex() {
+    match execute(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) r = (regex-replace "foo" "(" "")
+          }
+        "#},
+    ) {
+        Err(ExecutionError::InContext(_, cause)) => assert!(
+            matches!(*cause, ExecutionError::InvalidParameters(_)),
+            "{}",
+            cause
+        ),
+        result => panic!("Expected invalid parameters, got {:?}", result),
+    }
+}
+
+#[test]
+fn can_convert_values_to_string() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module) @root
+          {
+            node n
+            attr (n) int = (to-string 14), node = (to-string @root), str = (to-string "foo")
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            int: "14"
+            node: "[syntax node module (1, 1)]"
+            str: "foo"
+        "#},
+    );
+}
+
+#[test]
+fn can_parse_int_from_scanned_text() {
     check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            scan "line 41" {
+              "\\d+" {
+                node n
+                attr (n) next = (plus (parse-int $0) 1), hex = (parse-int "ff" 16)
+              }
+            }
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            hex: 255
+            next: 42
+        "#},
+    );
+}
+
+#[test]
+fn cannot_parse_int_from_non_numeric_text() {
+    fail_execution(
         "pass",
         indoc! {r#"
           (module)
           {
             node n
-            attr (n) str = (join [1, 2, 3])
+            attr (n) value = (parse-int "forty-two")
+          }
+        "#},
+    );
+}
+
+#[test]
+fn parse_int_reports_errors_of_optional_radix() {
+    /// Parameters whose radix cannot be read, as opposed to being missing
+    struct FailingRadix(Option<Value>);
+
+    impl Parameters for FailingRadix {
+        fn param(&mut self) -> Result<Value, ExecutionError> {
+            self.optional_param()?
+                .ok_or_else(|| ExecutionError::InvalidParameters("expected more parameters".into()))
+        }
+
+        fn optional_param(&mut self) -> Result<Option<Value>, ExecutionError> {
+            match self.0.take() {
+                Some(text) => Ok(Some(text)),
+                None => Err(ExecutionError::Cancelled(CancellationError(
+                    "reading radix",
+                ))),
+            }
+        }
+
+        fn finish(&mut self) -> Result<(), ExecutionError> {
+            Ok(())
+        }
+    }
+
+    let mut graph = Graph::new();
+    let mut parameters = FailingRadix(Some("42".into()));
+    let result = stdlib::string::ParseInt.call(&mut graph, "", &mut parameters);
+    assert!(
+        matches!(result, Err(ExecutionError::Cancelled(_))),
+        "{:?}",
+        result
+    );
+}
+
+#[test]
+fn can_inspect_strings() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) length = (string-length "naïve")
+            attr (n) starts = (starts-with "foobar" "foo"), not_starts = (starts-with "foobar" "bar")
+            attr (n) ends = (ends-with "foobar" "bar"), not_ends = (ends-with "foobar" "foo")
+            attr (n) index = (index-of "naïve" "v"), missing = (index-of "abc" "z")
           }
         "#},
         indoc! {r#"
           node 0
-            str: "123"
+            ends: #true
+            index: 3
+            length: 5
+            missing: #null
+            not_ends: #false
+            not_starts: #false
+            starts: #true
+        "#},
+    );
+}
+
+#[test]
+fn can_trim_strings() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) trim = (trim "  hi  "), start = (trim-start "  hi  "), end = (trim-end "  hi  ")
+            attr (n) xs = (concat [(trim " a\t")] [(trim "\nb ")])
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            end: "  hi"
+            start: "hi  "
+            trim: "hi"
+            xs: ["a", "b"]
+        "#},
+    );
+}
+
+#[test]
+fn cannot_trim_non_string() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) trim = (trim 42)
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_repeat_strings() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) sep = (repeat "-" 3), empty = (repeat "ab" 0)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            empty: ""
+            sep: "---"
+        "#},
+    );
+}
+
+#[test]
+fn cannot_repeat_strings_too_often() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) huge = (repeat "abc" 4000000000)
+          }
+        "#},
+    );
+}
+
+#[test]
+fn cannot_inspect_non_string() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) starts = (starts-with 42 "4")
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_compute_min_and_max() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) max = (max 1 5 3), min = (min 4 2 7), single = (max 9)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            max: 5
+            min: 2
+            single: 9
         "#},
     );
 }
+
+#[test]
+fn can_multiply_integers() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) product = (times 6 7), empty = (times)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            empty: 1
+            product: 42
+        "#},
+    );
+}
+
+#[test]
+fn cannot_overflow_integers() {
+    for expression in ["(times 65536 65536)", "(plus 4294967295 1)"] {
+        let dsl_source = format!(
+            "(module)\n{{\n  node n\n  attr (n) value = {}\n}}\n",
+            expression
+        );
+        match execute("pass", &dsl_source) {
+            Err(ExecutionError::InContext(_, cause)) => {
+                assert!(
+                    matches!(*cause, ExecutionError::IntegerOverflow(_)),
+                    "{}",
+                    cause
+                )
+            }
+            result => panic!("Expected an integer overflow, got {:?}", result),
+        }
+    }
+}
+
+#[test]
+fn cannot_compute_max_of_nothing() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) max = (max)
+          }
+        "#},
+    );
+}
+
+#[test]
+fn cannot_compute_min_of_non_integers() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) min = (min 1 "2")
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_concat_lists() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) xs = (concat [1, 2] [] [3, 4, 5])
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            xs: [1, 2, 3, 4, 5]
+        "#},
+    );
+}
+
+#[test]
+fn can_get_source_text_of_list_capture() {
+    check_execution(
+        indoc! {r#"
+          x = 1
+          pass
+          y = 2
+        "#},
+        indoc! {r#"
+          (module (_)+ @stmts)
+          {
+            node n
+            attr (n) text = (source-text @stmts)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            text: ["x = 1", "pass", "y = 2"]
+        "#},
+    );
+}
+
+#[test]
+fn can_get_field_name() {
+    check_execution(
+        indoc! {r#"
+          def f(x):
+            pass
+        "#},
+        indoc! {r#"
+          (function_definition name: (identifier) @name parameters: (_) @params body: (_) @body) @func
+          {
+            node n
+            attr (n) name = (field-name @name)
+            attr (n) parameters = (field-name @params)
+            attr (n) body = (field-name @body)
+            attr (n) func = (field-name @func)
+          }
+
+          (parameters (identifier) @param)
+          {
+            node n
+            attr (n) param = (field-name @param)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            body: "body"
+            func: #null
+            name: "name"
+            parameters: "parameters"
+          node 1
+            param: #null
+        "#},
+    );
+}
+
+#[test]
+fn can_find_ancestor_of_kind() {
+    check_execution(
+        indoc! {r#"
+          def f():
+            x = 1
+          y = 2
+        "#},
+        indoc! {r#"
+          (assignment left: (identifier) @id)
+          {
+            node n
+            attr (n) name = (source-text @id)
+            attr (n) in_function = (not (is-null (ancestor-of-kind @id "function_definition")))
+          }
+
+          (function_definition body: (block (expression_statement (assignment left: (identifier) @id))))
+          {
+            node n
+            attr (n) function = (start-row (ancestor-of-kind @id "function_definition"))
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            in_function: #true
+            name: "x"
+          node 1
+            in_function: #false
+            name: "y"
+          node 2
+            function: 0
+        "#},
+    );
+}
+
+#[test]
+fn can_link_consecutive_statements_via_siblings() {
+    check_execution(
+        indoc! {r#"
+          x = 1
+          y = 2
+        "#},
+        indoc! {r#"
+          (module (_) @stmt)
+          {
+            node @stmt.node
+            attr (@stmt.node) text = (source-text @stmt)
+          }
+
+          (module (_) @stmt)
+          {
+            let next = (next-named-sibling @stmt)
+            if (not (is-null next)) {
+              edge @stmt.node -> next.node
+            }
+            attr (@stmt.node) first = (is-null (prev-named-sibling @stmt))
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            first: #true
+            text: "x = 1"
+          edge 0 -> 1
+          node 1
+            first: #false
+            text: "y = 2"
+        "#},
+    );
+}
+
+#[test]
+fn can_get_source_text_of_anonymous_node() {
+    check_execution(
+        indoc! {r#"
+          def f():
+            return 1
+        "#},
+        indoc! {r#"
+          (return_statement "return" @kw)
+          {
+            node n
+            attr (n) kind = (node-type @kw), text = (source-text @kw)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            kind: "return"
+            text: "return"
+        "#},
+    );
+}
+
+#[test]
+fn can_get_match_metadata() {
+    check_execution(
+        indoc! {r#"
+          pass
+        "#},
+        indoc! {r#"
+          (module) @root
+          {
+            node n
+            attr (n) capture = (capture-name @root), stanza = (match-index)
+          }
+
+          (pass_statement) @stmt
+          {
+            node n
+            attr (n) capture = (capture-name @stmt), stanza = (match-index)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            capture: "root"
+            stanza: 0
+          node 1
+            capture: "stmt"
+            stanza: 1
+        "#},
+    );
+}
+
+#[test]
+fn can_get_capture_quantifiers() {
+    check_execution(
+        indoc! {r#"
+          def f() -> int:
+            pass
+          def g():
+            pass
+        "#},
+        indoc! {r#"
+          (function_definition
+            name: (identifier) @name
+            return_type: (_)? @type
+            body: (block (_)+ @stmts))
+          {
+            node n
+            attr (n) name = (source-text @name), name_quantifier = (quantifier @name)
+            attr (n) stmts_quantifier = (quantifier @stmts)
+            if (eq (quantifier @type) "zero-or-one") {
+              attr (n) optional_type = #true, has_type = (not (is-null @type))
+            }
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            has_type: #true
+            name: "f"
+            name_quantifier: "one"
+            optional_type: #true
+            stmts_quantifier: "one-or-more"
+          node 1
+            has_type: #false
+            name: "g"
+            name_quantifier: "one"
+            optional_type: #true
+            stmts_quantifier: "one-or-more"
+        "#},
+    );
+}
+
+#[test]
+fn can_get_dedented_source_text() {
+    check_execution(
+        indoc! {r#"
+          class C:
+              def f(self):
+                  if x:
+                      return 1
+                  return 2
+        "#},
+        indoc! {r#"
+          (function_definition body: (block) @body) @func
+          {
+            node n
+            attr (n) func = (source-text-dedent @func), body = (source-text-dedent @body)
+          }
+
+          (return_statement) @ret
+          {
+            node n
+            attr (n) ret = (source-text-dedent @ret)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            body: "if x:\n    return 1\nreturn 2"
+            func: "def f(self):\n    if x:\n        return 1\n    return 2"
+          node 1
+            ret: "return 1"
+          node 2
+            ret: "return 2"
+        "#},
+    );
+}
+
+#[test]
+fn can_get_full_match() {
+    check_execution(
+        indoc! {r#"
+          x = f(1) + g
+        "#},
+        indoc! {r#"
+          (call function: (_) @_function)
+          {
+            node n
+            attr (n) text = (source-text (full-match)), kind = (node-type (full-match))
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            kind: "call"
+            text: "f(1)"
+        "#},
+    );
+}
+
+#[test]
+fn can_test_list_captures_for_emptiness() {
+    check_execution(
+        indoc! {r#"
+          def f():
+            pass
+          class C:
+            pass
+        "#},
+        indoc! {r#"
+          (function_definition name: (identifier) @name (parameters (_)* @params))
+          {
+            node n
+            attr (n) name = (source-text @name)
+            if (is-empty @params) {
+              attr (n) no_params = #true
+            }
+          }
+
+          (class_definition name: (identifier) @name body: (block (_)* @stmts))
+          {
+            node n
+            attr (n) name = (source-text @name), empty = (is-empty @stmts)
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            name: "f"
+            no_params: #true
+          node 1
+            empty: #false
+            name: "C"
+        "#},
+    );
+}
+
+#[test]
+fn can_test_sets_and_strings_for_emptiness() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) empty_set = (is-empty {}), set = (is-empty {1})
+            attr (n) empty_string = (is-empty ""), string = (is-empty "a")
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            empty_set: #true
+            empty_string: #true
+            set: #false
+            string: #false
+        "#},
+    );
+}
+
+#[test]
+fn cannot_test_integer_for_emptiness() {
+    match execute(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) empty = (is-empty 0)
+          }
+        "#},
+    ) {
+        Err(ExecutionError::InContext(_, cause)) => assert!(
+            matches!(*cause, ExecutionError::ExpectedList(_)),
+            "{}",
+            cause
+        ),
+        result => panic!("Expected a list error, got {:?}", result),
+    }
+}
+
+#[test]
+fn can_join_list_with_separator() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) str = (join [1, 2, 3] ".")
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            str: "1.2.3"
+        "#},
+    );
+}
+
+#[test]
+fn can_join_list_without_separator() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) str = (join [1, 2, 3])
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            str: "123"
+        "#},
+    );
+}
+
+#[test]
+fn can_reverse_list() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) xs = (reverse [1, 2, 3]), empty = (reverse [])
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            empty: []
+            xs: [3, 2, 1]
+        "#},
+    );
+}
+
+#[test]
+fn can_sort_list() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) xs = (sort [3, 1, 2]), strs = (sort ["b", "c", "a"])
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            strs: ["a", "b", "c"]
+            xs: [1, 2, 3]
+        "#},
+    );
+}
+
+#[test]
+fn cannot_sort_mixed_list() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) xs = (sort [1, "2"])
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_union_sets() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) xs = (union {3, 1} {2, 1})
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            xs: {1, 2, 3}
+        "#},
+    );
+}
+
+#[test]
+fn can_intersect_sets() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) xs = (intersect {"c", "a", "b"} {"b", "c", "d"})
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            xs: {"b", "c"}
+        "#},
+    );
+}
+
+#[test]
+fn can_compute_set_difference() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) xs = (difference {4, 3, 2, 1} {2, 5})
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            xs: {1, 3, 4}
+        "#},
+    );
+}
+
+#[test]
+fn cannot_union_set_and_list() {
+    let result = execute(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) xs = (union {1} [2])
+          }
+        "#},
+    );
+    match result {
+        Err(e) => assert!(
+            e.to_string().ends_with("Expected a set got [2]"),
+            "unexpected error: {}",
+            e
+        ),
+        Ok(_) => panic!("Execution succeeded unexpectedly"),
+    }
+}
+
+#[test]
+fn can_sort_by_function() {
+    struct StartByte;
+
+    impl Function for StartByte {
+        fn call(
+            &self,
+            graph: &mut Graph,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            let node = graph[parameters.param()?.into_syntax_node_ref()?];
+            parameters.finish()?;
+            Ok(Value::Integer(node.start_byte() as u32))
+        }
+    }
+
+    let python_source = "a = 1\nbb = 2\nc = 3\n";
+    let dsl_source = indoc! {r#"
+      (module (_)+ @stmts)
+      {
+        node n
+        attr (n) sorted = (source-text (sort-by (reverse @stmts) "start-byte"))
+      }
+    "#};
+    let expected = indoc! {r#"
+      node 0
+        sorted: ["a = 1", "bb = 2", "c = 3"]
+    "#};
+    let mut functions = Functions::stdlib();
+    functions.add(Identifier::from("start-byte"), StartByte);
+    for lazy in [false, true] {
+        let graph = execute_with_functions(python_source, dsl_source, &functions, lazy)
+            .expect("Could not execute file");
+        assert_eq!(graph, expected);
+    }
+
+    match execute(python_source, dsl_source) {
+        Err(ExecutionError::InContext(_, cause)) => {
+            assert!(
+                matches!(*cause, ExecutionError::UndefinedFunction(_)),
+                "{}",
+                cause
+            )
+        }
+        result => panic!("Expected an undefined function, got {:?}", result),
+    }
+
+    // sort-by is an ordinary library function, which calls the key function from its own library
+    let mut functions = Functions::new();
+    functions.add(Identifier::from("reverse"), stdlib::list::Reverse);
+    functions.add(Identifier::from("sort-by"), stdlib::list::SortBy);
+    functions.add(Identifier::from("source-text"), stdlib::syntax::SourceText);
+    functions.add(Identifier::from("start-byte"), StartByte);
+    let graph = execute_with_functions(python_source, dsl_source, &functions, false)
+        .expect("Could not execute file");
+    assert_eq!(graph, expected);
+}
+
+#[test]
+fn can_resolve_functions_with_fallback() {
+    struct Echo;
+
+    impl Function for Echo {
+        fn call(
+            &self,
+            _graph: &mut Graph,
+            _source: &str,
+            parameters: &mut dyn Parameters,
+        ) -> Result<Value, ExecutionError> {
+            let value = parameters.param()?;
+            parameters.finish()?;
+            Ok(value)
+        }
+    }
+
+    let resolutions = Arc::new(AtomicUsize::new(0));
+    let mut functions = Functions::stdlib();
+    let counter = resolutions.clone();
+    functions.set_fallback(move |name| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        match name.as_str() {
+            "echo" => Some(Box::new(Echo)),
+            _ => None,
+        }
+    });
+    match execute_with_functions(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) first = (echo "hello"), second = (echo 42)
+          }
+
+          (pass_statement)
+          {
+            node n
+            attr (n) missing = (unknown)
+          }
+        "#},
+        &functions,
+        false,
+    ) {
+        Err(ExecutionError::InContext(_, cause)) => {
+            assert!(matches!(*cause, ExecutionError::UndefinedFunction(_)))
+        }
+        result => panic!("Expected an undefined function, got {:?}", result),
+    }
+    // echo is resolved once, and then cached; unknown is not found
+    assert_eq!(resolutions.load(Ordering::SeqCst), 2);
+
+    let graph = execute_with_functions(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) first = (echo "hello"), second = (echo 42)
+          }
+        "#},
+        &functions,
+        false,
+    )
+    .expect("Could not execute file");
+    assert_eq!(
+        graph,
+        indoc! {r#"
+          node 0
+            first: "hello"
+            second: 42
+        "#}
+    );
+    assert_eq!(resolutions.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn fallback_is_called_once_per_function_across_threads() {
+    let resolutions = Arc::new(AtomicUsize::new(0));
+    let mut functions = Functions::new();
+    let counter = resolutions.clone();
+    functions.set_fallback(move |_| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        Some(Box::new(stdlib::graph::GetAttr))
+    });
+    let name = Identifier::from("get-attr");
+    std::thread::scope(|scope| {
+        for _ in 0..8 {
+            scope.spawn(|| assert!(functions.reads_graph(&name).unwrap()));
+        }
+    });
+    assert_eq!(resolutions.load(Ordering::SeqCst), 1);
+}
@@ -5,10 +5,21 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::Arc;
+
 use indoc::indoc;
+use tree_sitter::CaptureQuantifier;
 use tree_sitter::Parser;
+use tree_sitter_graph::graph::CycleError;
 use tree_sitter_graph::graph::Graph;
+use tree_sitter_graph::graph::GraphNodeRef;
+use tree_sitter_graph::graph::JsonStringRenderer;
+use tree_sitter_graph::graph::NodeRenderer;
 use tree_sitter_graph::graph::Value;
+use tree_sitter_graph::ExecutionError;
 use tree_sitter_graph::Identifier;
 
 #[test]
@@ -22,6 +33,168 @@ fn can_overwrite_attributes() {
     assert_eq!(*attrs.get(&name).unwrap(), Value::from("overwritten"));
 }
 
+#[test]
+fn interned_strings_share_storage() {
+    let mut graph = Graph::new();
+    let name = Identifier::from("name");
+    let interned: Arc<str> = Arc::from("shared");
+    let node0 = graph.add_graph_node();
+    graph[node0]
+        .attributes
+        .add(name.clone(), Value::from_interned(interned.clone()))
+        .unwrap();
+    let node1 = graph.add_graph_node();
+    graph[node1]
+        .attributes
+        .add(name.clone(), Value::from_interned(interned.clone()))
+        .unwrap();
+    let value0 = graph[node0].attributes.get(&name).unwrap();
+    let value1 = graph[node1].attributes.get(&name).unwrap();
+    assert_eq!(
+        value0.as_str().unwrap().as_ptr(),
+        value1.as_str().unwrap().as_ptr()
+    );
+    assert_eq!(*value0, Value::from("shared"));
+    assert_eq!(value0.clone().into_string().unwrap(), "shared");
+    match value0 {
+        Value::String(value) => {
+            assert_eq!(value, "shared");
+            assert_eq!(value.clone().into_string(), "shared".to_string());
+        }
+        _ => panic!("Expected a string value"),
+    }
+}
+
+#[test]
+fn can_create_values_from_integers() {
+    assert_eq!(Value::from(14u32), Value::Integer(14));
+    assert_eq!(Value::try_from(14i32), Ok(Value::Integer(14)));
+    assert_eq!(Value::try_from(14i64), Ok(Value::Integer(14)));
+    assert_eq!(Value::try_from(14usize), Ok(Value::Integer(14)));
+    assert_eq!(
+        Value::try_from(u32::MAX as i64),
+        Ok(Value::Integer(u32::MAX))
+    );
+}
+
+#[test]
+fn cannot_create_values_from_out_of_range_integers() {
+    assert!(Value::try_from(-1i32).is_err());
+    assert!(Value::try_from(-1i64).is_err());
+    assert!(Value::try_from(u32::MAX as i64 + 1).is_err());
+}
+
+#[test]
+fn can_create_values_from_node_refs() {
+    let mut graph = Graph::new();
+    let node = graph.add_graph_node();
+    assert_eq!(Value::from(node), Value::GraphNode(node));
+
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("pass", None).unwrap();
+    let root = graph.add_syntax_node(tree.root_node());
+    assert_eq!(Value::from(root), Value::SyntaxNode(root));
+}
+
+#[test]
+fn can_display_sets_independent_of_insertion_order() {
+    let elements = [
+        Value::from("pear"),
+        Value::from(vec![Value::from("b"), Value::from("a")]),
+        Value::from("apple"),
+        Value::from(Value::from("fig").to_string()),
+        Value::from(vec![Value::from("a")]),
+    ];
+    let forward = Value::from(elements.iter().cloned().collect::<BTreeSet<_>>());
+    let backward = Value::from(elements.iter().rev().cloned().collect::<BTreeSet<_>>());
+    assert_eq!(forward.to_string(), backward.to_string());
+    assert_eq!(format!("{:?}", forward), format!("{:?}", backward));
+    assert_eq!(
+        format!("{:?}", forward),
+        r#"{"apple", "fig", "pear", ["a"], ["b", "a"]}"#
+    );
+}
+
+#[test]
+fn can_display_sets_of_syntax_nodes_in_source_order() {
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("a\nb\nc\n", None).unwrap();
+    let mut graph = Graph::new();
+    let root = tree.root_node();
+    let statements = (0..root.named_child_count())
+        .rev()
+        .map(|i| Value::from(graph.add_syntax_node(root.named_child(i).unwrap())))
+        .collect::<BTreeSet<_>>();
+    assert_eq!(
+        Value::from(statements).to_string(),
+        "{[syntax node expression_statement (1, 1)], [syntax node expression_statement (2, 1)], [syntax node expression_statement (3, 1)]}"
+    );
+}
+
+#[test]
+fn can_coerce_values_into_sets() {
+    let set = vec![Value::from(2u32), Value::from(1u32)]
+        .into_iter()
+        .collect::<BTreeSet<_>>();
+    let value = Value::from(set.clone());
+    assert_eq!(value.as_set().unwrap(), &set);
+    assert_eq!(value.into_set().unwrap(), set);
+
+    let list = Value::from(vec![Value::from(1u32)]);
+    assert!(matches!(list.as_set(), Err(ExecutionError::ExpectedSet(_))));
+    assert!(matches!(
+        list.into_set(),
+        Err(ExecutionError::ExpectedSet(_))
+    ));
+}
+
+#[test]
+fn can_coerce_values_into_graph_nodes() {
+    let mut graph = Graph::new();
+    let node = graph.add_graph_node();
+    graph[node]
+        .attributes
+        .add(Identifier::from("name"), "n")
+        .unwrap();
+    let value = Value::from(node);
+    let graph_node = value.into_graph_node(&graph).unwrap();
+    assert_eq!(graph_node.attributes.get("name"), Some(&Value::from("n")));
+
+    assert!(matches!(
+        Value::from(1u32).into_graph_node(&graph),
+        Err(ExecutionError::ExpectedGraphNode(_))
+    ));
+}
+
+#[test]
+fn can_iterate_attributes() {
+    let mut graph = Graph::new();
+    let node = graph.add_graph_node();
+    assert!(graph[node].attributes.is_empty());
+    graph[node]
+        .attributes
+        .add(Identifier::from("name"), "node0")
+        .unwrap();
+    graph[node]
+        .attributes
+        .add(Identifier::from("index"), 0)
+        .unwrap();
+    assert_eq!(graph[node].attributes.len(), 2);
+    let mut attrs = Vec::new();
+    for (name, value) in &graph[node].attributes {
+        attrs.push((name.to_string(), value.clone()));
+    }
+    assert_eq!(
+        attrs,
+        vec![
+            ("index".to_string(), Value::from(0)),
+            ("name".to_string(), Value::from("node0")),
+        ]
+    );
+}
+
 #[test]
 fn can_iterate_graph_nodes() {
     let mut graph = Graph::new();
@@ -47,6 +220,100 @@ fn can_iterate_graph_edges() {
     assert_eq!(edges, vec![node1, node2]);
 }
 
+#[test]
+fn can_find_incoming_edges() {
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    let node1 = graph.add_graph_node();
+    let node2 = graph.add_graph_node();
+    let _ = graph[node0].add_edge(node1);
+    let _ = graph[node1].add_edge(node2);
+    let _ = graph[node2].add_edge(node0);
+    let sources = |graph: &Graph, node| {
+        graph
+            .incoming_edges(node)
+            .into_iter()
+            .map(|(source, _)| source)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(sources(&graph, node2), vec![node1]);
+    let _ = graph[node0].add_edge(node2);
+    assert_eq!(sources(&graph, node2), vec![node0, node1]);
+    assert_eq!(sources(&graph, node0), vec![node2]);
+    graph.remove_node(node1);
+    assert_eq!(sources(&graph, node2), vec![node0]);
+}
+
+#[test]
+fn can_find_graph_nodes_by_attribute() {
+    let mut graph = Graph::new();
+    let name = Identifier::from("name");
+    let node0 = graph.add_graph_node();
+    graph[node0].attributes.add(name.clone(), "x").unwrap();
+    let node1 = graph.add_graph_node();
+    graph[node1].attributes.add(name.clone(), "y").unwrap();
+    let node2 = graph.add_graph_node();
+    graph[node2].attributes.add(name.clone(), "x").unwrap();
+    let nodes = graph.find_nodes_by_attribute(name, &Value::from("x"));
+    assert_eq!(nodes, vec![node0, node2]);
+}
+
+#[test]
+fn can_remove_graph_nodes() {
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    let node1 = graph.add_graph_node();
+    let node2 = graph.add_graph_node();
+    let _ = graph[node0].add_edge(node1);
+    let _ = graph[node0].add_edge(node2);
+    let _ = graph[node1].add_edge(node2);
+    graph.remove_node(node1);
+    assert_eq!(graph.iter_nodes().collect::<Vec<_>>(), vec![node0, node2]);
+    assert_eq!(graph.node_count(), 2);
+    graph.remove_node(node1);
+    assert_eq!(graph.node_count(), 2);
+    assert!(graph[node0].get_edge(node1).is_none());
+    assert!(graph[node0].get_edge(node2).is_some());
+    assert_eq!(graph[node1].edge_count(), 0);
+    assert!(graph[node1].is_removed());
+    assert!(!graph[node2].is_removed());
+    assert_eq!(
+        graph.pretty_print().to_string(),
+        indoc! {r#"
+          node 0
+          edge 0 -> 2
+          node 2
+        "#}
+    );
+}
+
+#[test]
+fn removed_graph_nodes_are_tombstones() {
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    let node1 = graph.add_graph_node();
+    graph.remove_node(node1);
+    // The tombstone can still be modified, but nothing added to it is output.
+    assert!(graph[node1].add_edge(node0).is_ok());
+    graph[node1]
+        .attributes
+        .add(Identifier::from("name"), "gone")
+        .unwrap();
+    assert!(graph[node1].is_removed());
+    assert_eq!(graph.node_count(), 1);
+    assert_eq!(graph.pretty_print().to_string(), "node 0\n");
+    // Edges to the tombstone are output, which is why callers must check for it.
+    assert!(graph[node0].add_edge(node1).is_ok());
+    assert_eq!(graph.pretty_print().to_string(), "node 0\nedge 0 -> 1\n");
+}
+
+#[test]
+fn zero_quantified_capture_is_null() {
+    let mut graph = Graph::new();
+    let value = Value::from_nodes(&mut graph, std::iter::empty(), CaptureQuantifier::Zero);
+    assert_eq!(value, Value::Null);
+}
+
 #[test]
 fn can_display_graph() {
     let python_source = "pass";
@@ -102,3 +369,361 @@ fn can_display_graph() {
         "#}
     );
 }
+
+#[test]
+fn can_merge_graphs() {
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    graph[node0]
+        .attributes
+        .add(Identifier::from("name"), "node0")
+        .unwrap();
+
+    let mut other = Graph::new();
+    let other0 = other.add_graph_node();
+    other[other0]
+        .attributes
+        .add(Identifier::from("self"), other0)
+        .unwrap();
+    let _ = other[other0].add_edge(other0);
+
+    graph.merge(other);
+    assert_eq!(graph.node_count(), 2);
+    assert_eq!(
+        graph.pretty_print().to_string(),
+        indoc! {r#"
+          node 0
+            name: "node0"
+          node 1
+            self: [graph node 1]
+          edge 1 -> 1
+        "#}
+    );
+}
+
+#[test]
+fn can_compare_graphs_structurally() {
+    let python_source = "x = 1";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree1 = parser.parse(python_source, None).unwrap();
+    let tree2 = parser.parse(python_source, None).unwrap();
+
+    fn build<'tree>(tree: &'tree tree_sitter::Tree, name: &str) -> Graph<'tree> {
+        let mut graph = Graph::new();
+        let root = graph.add_syntax_node(tree.root_node());
+        let child = graph.add_syntax_node(tree.root_node().child(0).unwrap());
+        let node0 = graph.add_graph_node();
+        let node1 = graph.add_graph_node();
+        graph[node0]
+            .attributes
+            .add(Identifier::from("name"), name)
+            .unwrap();
+        graph[node0]
+            .attributes
+            .add(
+                Identifier::from("nodes"),
+                vec![Value::from(root), Value::from(child)]
+                    .into_iter()
+                    .collect::<BTreeSet<_>>(),
+            )
+            .unwrap();
+        graph[node1]
+            .attributes
+            .add(Identifier::from("source"), child)
+            .unwrap();
+        let edge = graph[node0].add_edge(node1).ok().unwrap();
+        edge.attributes
+            .add(Identifier::from("target"), node1)
+            .unwrap();
+        graph
+    }
+
+    let graph1 = build(&tree1, "node0");
+    let graph2 = build(&tree2, "node0");
+    let graph3 = build(&tree2, "other");
+    assert!(graph1.structurally_eq(&graph2));
+    assert!(graph2.structurally_eq(&graph1));
+    assert!(!graph1.structurally_eq(&graph3));
+}
+
+#[test]
+fn can_keep_owned_graph_after_dropping_tree() {
+    let owned = {
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_python::language()).unwrap();
+        let tree = parser.parse("x = 1", None).unwrap();
+        let mut graph = Graph::new();
+        let root = graph.add_syntax_node(tree.root_node());
+        let node = graph.add_graph_node();
+        graph[node]
+            .attributes
+            .add(Identifier::from("name"), "root")
+            .unwrap();
+        graph[node]
+            .attributes
+            .add(Identifier::from("source"), root)
+            .unwrap();
+        graph.into_owned()
+    };
+
+    assert_eq!(owned.node_count(), 1);
+    let node = owned.iter_nodes().next().unwrap();
+    let attributes = &owned[node].attributes;
+    assert_eq!(attributes.get("name"), Some(&Value::from("root")));
+    let source = attributes
+        .get("source")
+        .unwrap()
+        .as_syntax_node_ref()
+        .unwrap();
+    let descriptor = &owned[source];
+    assert_eq!(descriptor.kind, "module");
+    assert_eq!(descriptor.byte_range, 0..5);
+    assert_eq!(
+        owned.pretty_print().to_string(),
+        indoc! {r#"
+          node 0
+            name: "root"
+            source: [syntax node module (1, 1)]
+        "#}
+    );
+}
+
+#[test]
+fn can_order_graph_nodes_topologically() {
+    let mut graph = Graph::new();
+    let a = graph.add_graph_node();
+    let b = graph.add_graph_node();
+    let c = graph.add_graph_node();
+    let d = graph.add_graph_node();
+    graph[c].add_edge(b).ok().unwrap();
+    graph[b].add_edge(a).ok().unwrap();
+    graph[c].add_edge(a).ok().unwrap();
+    graph[d].add_edge(b).ok().unwrap();
+    let order = graph.topological_order().expect("Graph is not acyclic");
+    assert_eq!(order, vec![c, d, b, a]);
+}
+
+#[test]
+fn cannot_order_graph_nodes_with_cycle() {
+    let mut graph = Graph::new();
+    let a = graph.add_graph_node();
+    let b = graph.add_graph_node();
+    let c = graph.add_graph_node();
+    graph[a].add_edge(b).ok().unwrap();
+    graph[b].add_edge(a).ok().unwrap();
+    graph[c].add_edge(a).ok().unwrap();
+    match graph.topological_order() {
+        Err(CycleError(node)) => assert!(node == a || node == b),
+        Ok(order) => panic!("Unexpected order {:?}", order),
+    }
+}
+
+#[test]
+fn can_display_graph_with_custom_renderer() {
+    struct HashRenderer;
+
+    impl NodeRenderer for HashRenderer {
+        fn fmt_graph_node_id(&self, f: &mut fmt::Formatter, node: GraphNodeRef) -> fmt::Result {
+            write!(f, "#{}", node.index())
+        }
+
+        fn fmt_graph_node_ref(&self, f: &mut fmt::Formatter, node: GraphNodeRef) -> fmt::Result {
+            write!(f, "#{}", node.index())
+        }
+    }
+
+    let python_source = "pass";
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(python_source, None).unwrap();
+
+    let mut graph = Graph::new();
+    let root = graph.add_syntax_node(tree.root_node());
+    let node0 = graph.add_graph_node();
+    let node1 = graph.add_graph_node();
+    graph[node0]
+        .attributes
+        .add(Identifier::from("source"), root)
+        .unwrap();
+    graph[node1]
+        .attributes
+        .add(Identifier::from("parents"), vec![Value::from(node0)])
+        .unwrap();
+    let edge = graph[node1].add_edge(node0).ok().unwrap();
+    edge.attributes
+        .add(Identifier::from("target"), node0)
+        .unwrap();
+    assert_eq!(
+        graph.pretty_print().renderer(&HashRenderer).to_string(),
+        indoc! {r#"
+          node #0
+            source: [syntax node module (1, 1)]
+          node #1
+            parents: [#0]
+          edge #1 -> #0
+            target: #0
+        "#}
+    );
+}
+
+#[test]
+fn can_display_graph_with_json_string_escaping() {
+    let mut graph = Graph::new();
+    let node = graph.add_graph_node();
+    graph[node]
+        .attributes
+        .add(Identifier::from("text"), "say \"hi\"\n\u{1b}")
+        .unwrap();
+    assert_eq!(
+        graph.pretty_print().to_string(),
+        "node 0\n  text: \"say \\\"hi\\\"\\n\\u{1b}\"\n"
+    );
+    assert_eq!(
+        graph
+            .pretty_print()
+            .renderer(&JsonStringRenderer)
+            .to_string(),
+        "node 0\n  text: \"say \\\"hi\\\"\\n\\u001b\"\n"
+    );
+}
+
+#[test]
+fn can_display_graph_sorted_by_attribute() {
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    let node1 = graph.add_graph_node();
+    let node2 = graph.add_graph_node();
+    let node3 = graph.add_graph_node();
+    graph[node0]
+        .attributes
+        .add(Identifier::from("name"), "zeta")
+        .unwrap();
+    graph[node1]
+        .attributes
+        .add(Identifier::from("name"), "alpha")
+        .unwrap();
+    graph[node3]
+        .attributes
+        .add(Identifier::from("name"), "mu")
+        .unwrap();
+    graph[node0].add_edge(node1).ok().unwrap();
+    graph[node1].add_edge(node2).ok().unwrap();
+    assert_eq!(
+        graph
+            .pretty_print()
+            .sort_by(&Identifier::from("name"))
+            .to_string(),
+        indoc! {r#"
+          node 1
+            name: "alpha"
+          edge 1 -> 2
+          node 3
+            name: "mu"
+          node 0
+            name: "zeta"
+          edge 0 -> 1
+          node 2
+        "#}
+    );
+}
+
+#[test]
+fn can_display_graph_sorted_by_attribute_and_by_first_reference() {
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    let node1 = graph.add_graph_node();
+    let node2 = graph.add_graph_node();
+    let node3 = graph.add_graph_node();
+    graph[node1]
+        .attributes
+        .add(Identifier::from("name"), "b")
+        .unwrap();
+    graph[node1]
+        .attributes
+        .add(Identifier::from("next"), node3)
+        .unwrap();
+    graph[node2]
+        .attributes
+        .add(Identifier::from("name"), "a")
+        .unwrap();
+    graph[node2]
+        .attributes
+        .add(Identifier::from("gone"), node0)
+        .unwrap();
+    graph.remove_node(node0);
+    // The reference to the removed node is numbered after every node in the output, so that it
+    // cannot be mistaken for one of them.
+    assert_eq!(
+        graph
+            .pretty_print()
+            .sort_by(&Identifier::from("name"))
+            .by_first_reference(true)
+            .to_string(),
+        indoc! {r#"
+          node 0
+            gone: [graph node 3]
+            name: "a"
+          node 1
+            name: "b"
+            next: [graph node 2]
+          node 2
+        "#}
+    );
+}
+
+#[test]
+fn can_display_graph_with_custom_layout() {
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    let node1 = graph.add_graph_node();
+    graph[node0]
+        .attributes
+        .add(Identifier::from("name"), "a")
+        .unwrap();
+    let edge = graph[node0].add_edge(node1).ok().unwrap();
+    edge.attributes
+        .add(Identifier::from("precedence"), 1u32)
+        .unwrap();
+    assert_eq!(
+        graph
+            .pretty_print()
+            .indent(2)
+            .separate_nodes(false)
+            .to_string(),
+        graph.pretty_print().to_string()
+    );
+    assert_eq!(
+        graph
+            .pretty_print()
+            .indent(4)
+            .separate_nodes(true)
+            .to_string(),
+        indoc! {r#"
+          node 0
+              name: "a"
+          edge 0 -> 1
+              precedence: 1
+
+          node 1
+        "#}
+    );
+}
+
+#[test]
+fn can_write_graph_to_writer() {
+    let mut graph = Graph::new();
+    let node0 = graph.add_graph_node();
+    let node1 = graph.add_graph_node();
+    graph[node0]
+        .attributes
+        .add(Identifier::from("name"), "a")
+        .unwrap();
+    graph[node0].add_edge(node1).ok().unwrap();
+    let mut output = Vec::new();
+    graph.write_to(&mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        graph.pretty_print().to_string()
+    );
+}
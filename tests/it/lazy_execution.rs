@@ -5,12 +5,17 @@
 // Please see the LICENSE-APACHE or LICENSE-MIT files in this distribution for license details.
 // ------------------------------------------------------------------------------------------------
 
+use std::sync::Mutex;
+
 use indoc::indoc;
 use tree_sitter::Parser;
 use tree_sitter_graph::ast::File;
 use tree_sitter_graph::functions::Functions;
+use tree_sitter_graph::graph::Graph;
+use tree_sitter_graph::graph::Value;
 use tree_sitter_graph::ExecutionConfig;
 use tree_sitter_graph::ExecutionError;
+use tree_sitter_graph::Identifier;
 use tree_sitter_graph::NoCancellation;
 use tree_sitter_graph::Variables;
 
@@ -24,6 +29,18 @@ fn init_log() {
 }
 
 fn execute(python_source: &str, dsl_source: &str) -> Result<String, ExecutionError> {
+    let mut globals = Variables::new();
+    globals
+        .add("filename".into(), "test.py".into())
+        .map_err(|_| ExecutionError::DuplicateVariable("filename".into()))?;
+    execute_with_globals(python_source, dsl_source, &globals)
+}
+
+fn execute_with_globals(
+    python_source: &str,
+    dsl_source: &str,
+    globals: &Variables,
+) -> Result<String, ExecutionError> {
     init_log();
     let mut parser = Parser::new();
     parser.set_language(tree_sitter_python::language()).unwrap();
@@ -31,11 +48,7 @@ fn execute(python_source: &str, dsl_source: &str) -> Result<String, ExecutionErr
     let file =
         File::from_str(tree_sitter_python::language(), dsl_source).expect("Cannot parse file");
     let functions = Functions::stdlib();
-    let mut globals = Variables::new();
-    globals
-        .add("filename".into(), "test.py".into())
-        .map_err(|_| ExecutionError::DuplicateVariable("filename".into()))?;
-    let mut config = ExecutionConfig::new(&functions, &globals).lazy(true);
+    let mut config = ExecutionConfig::new(&functions, globals).lazy(true);
     let graph = file.execute(&tree, python_source, &mut config, &NoCancellation)?;
     let result = graph.pretty_print().to_string();
     Ok(result)
@@ -86,6 +99,201 @@ fn can_build_simple_graph() {
     );
 }
 
+#[test]
+fn can_overwrite_attributes() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            node m
+            edge n -> m
+            attr (n) name = "first"
+            set-attr (n) name = "second"
+            set-attr (n) name = "third", kind = "module"
+            attr (n -> m) precedence = 1
+            set-attr (n -> m) precedence = 2
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            kind: "module"
+            name: "third"
+          edge 0 -> 1
+            precedence: 2
+          node 1
+        "#},
+    );
+}
+
+#[test]
+fn can_print_formatted_values() {
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (module) @m
+          {
+            node n
+            print "plain: ", (node-type @m), " ", [(start-row @m), "x"], " ", n
+            print (format "formatted: {} at {}" (node-type @m) (start-row @m))
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse("pass", None).unwrap();
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let output = Mutex::new(Vec::new());
+    let print_to = |line: &str| output.lock().unwrap().push(line.to_string());
+    let config = ExecutionConfig::new(&functions, &globals)
+        .lazy(true)
+        .print_to(&print_to);
+    file.execute(&tree, "pass", &config, &NoCancellation)
+        .expect("Could not execute file");
+    assert_eq!(
+        output.into_inner().unwrap(),
+        vec![
+            "plain: module [0, \"x\"] [graph node 0]",
+            "formatted: module at 0",
+        ]
+    );
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            print (format "missing: {}")
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_get_full_match() {
+    check_execution(
+        indoc! {r#"
+          x = f(1) + g
+        "#},
+        indoc! {r#"
+          (call function: (_) @_function)
+          {
+            node n
+            attr (n) text = (source-text (full-match)), kind = (node-type (full-match))
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            kind: "call"
+            text: "f(1)"
+        "#},
+    );
+}
+
+#[test]
+fn can_skip_null_attributes() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            node m
+            edge n -> m
+            attr? (n) missing = #null, name = "n"
+            set-attr? (n) name = #null
+            attr? (n -> m) precedence = #null, kind = "child"
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            name: "n"
+          edge 0 -> 1
+            kind: "child"
+          node 1
+        "#},
+    );
+}
+
+#[test]
+fn can_spread_attributes() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            let attrs = [["kind", "module"], ["name", "n"]]
+            attr (n) ..attrs, line = 1
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            kind: "module"
+            line: 1
+            name: "n"
+        "#},
+    );
+}
+
+#[test]
+fn cannot_spread_existing_attribute() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) name = "n", ..[["name", "m"]]
+          }
+        "#},
+    );
+}
+
+#[test]
+fn cannot_add_existing_attribute() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) name = "first"
+            attr (n) name = "second"
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_delete_attributes() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            node m
+            edge n -> m
+            attr (n) name = "n", scratch = 1
+            attr (n -> m) precedence = 1, scratch = 2
+            del-attr (n) scratch
+            del-attr (n -> m) scratch, missing
+            del-attr (m) missing
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            name: "n"
+          edge 0 -> 1
+            precedence: 1
+          node 1
+        "#},
+    );
+}
+
 #[test]
 fn can_scan_strings() {
     check_execution(
@@ -132,6 +340,117 @@ fn can_scan_strings() {
     );
 }
 
+#[test]
+fn can_scan_strings_with_named_regex_captures() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            scan "key=value" {
+              "(?P<key>\\w+)=(\\w+)" {
+                node n
+                attr (n) key = $key, value = $2
+              }
+            }
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            key: "key"
+            value: "value"
+        "#},
+    );
+}
+
+#[test]
+fn can_scan_strings_with_default_arm() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            scan "abc=1 ??? def=2!" {
+              "(\\w+)=\\d+" {
+                node n
+                attr (n) key = $1
+              }
+              else {
+                node n
+                attr (n) unmatched = $0
+              }
+            }
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            key: "abc"
+          node 1
+            unmatched: " ??? "
+          node 2
+            key: "def"
+          node 3
+            unmatched: "!"
+        "#},
+    );
+}
+
+#[test]
+fn cannot_use_undefined_numbered_regex_captures() {
+    let result = execute(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            scan "xy" {
+              "x(y)" {
+                node n
+                attr (n) value = $2
+              }
+            }
+          }
+        "#},
+    );
+    match result {
+        Err(e) => assert!(
+            e.to_string().ends_with("Undefined regex capture $2"),
+            "unexpected error: {}",
+            e
+        ),
+        Ok(_) => panic!("Execution succeeded unexpectedly"),
+    }
+}
+
+#[test]
+fn can_scan_multibyte_text() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            scan "café=1 🦀 naïve=2" {
+              "\\w+=\\d+" {
+                node n
+                attr (n) match = $0
+              }
+              else {
+                node n
+                attr (n) unmatched = $0
+              }
+            }
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            match: "café=1"
+          node 1
+            unmatched: " 🦀 "
+          node 2
+            match: "naïve=2"
+        "#},
+    );
+}
+
 #[test]
 fn variables_in_scan_arms_are_local() {
     check_execution(
@@ -217,6 +536,40 @@ fn scoped_variables_carry_across_stanzas() {
     );
 }
 
+#[test]
+fn can_get_graph_node_for_syntax_node() {
+    check_execution(
+        indoc! {r#"
+          import a
+          from b import c
+        "#},
+        indoc! {r#"
+          (identifier) @id
+          {
+            let n = (node-for-syntax @id)
+            attr (n) name = (source-text @id)
+          }
+
+          (identifier) @id
+          {
+            let n = (node-for-syntax @id)
+            attr (n) same = (eq n (node-for-syntax @id))
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            name: "a"
+            same: #true
+          node 1
+            name: "b"
+            same: #true
+          node 2
+            name: "c"
+            same: #true
+        "#},
+    );
+}
+
 #[test]
 fn can_match_stanza_multiple_times() {
     check_execution(
@@ -271,6 +624,52 @@ fn can_use_global_variable() {
     );
 }
 
+#[test]
+fn can_use_list_and_set_global_variables() {
+    let mut globals = Variables::new();
+    globals
+        .add(
+            Identifier::from("numbers"),
+            (0..1000u32).map(Value::from).collect::<Vec<_>>().into(),
+        )
+        .expect("Cannot add global");
+    globals
+        .add(
+            Identifier::from("names"),
+            Value::Set(vec!["b".into(), "a".into()].into_iter().collect()),
+        )
+        .expect("Cannot add global");
+    let graph = execute_with_globals(
+        "pass",
+        indoc! {r#"
+          global numbers*
+          global names
+
+          (module) {
+            node n
+            for number in numbers {
+              if number == 999 {
+                attr (n) last = number
+              }
+            }
+            attr (n) count = (length numbers)
+            attr (n) names = names
+          }
+        "#},
+        &globals,
+    )
+    .expect("Cannot execute file");
+    assert_eq!(
+        graph,
+        indoc! {r#"
+          node 0
+            count: 1000
+            last: 999
+            names: {"a", "b"}
+        "#}
+    );
+}
+
 #[test]
 fn can_omit_global_variable_with_default() {
     check_execution(
@@ -306,6 +705,42 @@ fn cannot_omit_global_variable() {
     );
 }
 
+#[test]
+fn can_destructure_lists() {
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module) @m
+          {
+            node n
+            let [name, @m.kind] = ["a", (node)]
+            attr (n) name = name, kind = @m.kind
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            kind: [graph node 1]
+            name: "a"
+          node 1
+        "#},
+    );
+}
+
+#[test]
+fn cannot_destructure_list_of_wrong_length() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            let [a, b] = ["a", "b", "c"]
+            attr (n) a = a
+          }
+        "#},
+    );
+}
+
 #[test]
 fn can_use_variable_multiple_times() {
     check_execution(
@@ -551,6 +986,123 @@ fn can_execute_else() {
     );
 }
 
+#[test]
+fn can_execute_if_comparison() {
+    check_execution(
+        indoc! {r#"
+          def foo():
+            pass
+          def bar():
+            pass
+        "#},
+        indoc! {r#"
+          (function_definition name: (identifier) @x)
+          {
+            node n
+            if (source-text @x) == "foo" {
+              attr (n) foo = #true
+            }
+            attr (n) ne = (source-text @x) != "foo", lt = 1 < 2, ge = "a" >= "b"
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            foo: #true
+            ge: #false
+            lt: #true
+            ne: #false
+          node 1
+            ge: #false
+            lt: #true
+            ne: #true
+        "#},
+    );
+}
+
+#[test]
+fn cannot_compare_values_of_different_types() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) lt = 1 < "2"
+          }
+        "#},
+    );
+}
+
+#[test]
+fn can_execute_if_boolean_operators() {
+    check_execution(
+        indoc! {r#"
+          def foo():
+            pass
+          def bar():
+            pass
+        "#},
+        indoc! {r#"
+          (function_definition name: (identifier) @x)
+          {
+            node n
+            let name = (source-text @x)
+            if name == "foo" && (#false || !(name == "bar")) {
+              attr (n) foo = #true
+            }
+            attr (n) and = #true && !#true, or = #false || #true
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            and: #false
+            foo: #true
+            or: #true
+          node 1
+            and: #false
+            or: #true
+        "#},
+    );
+}
+
+#[test]
+fn boolean_operators_short_circuit() {
+    // the right-hand sides would fail if evaluated, since they compare values of different types
+    check_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            if #false && (eq 1 "1") {
+              attr (n) and = #true
+            }
+            if #true || 1 < "1" {
+              attr (n) or = #true
+            }
+          }
+        "#},
+        indoc! {r#"
+          node 0
+            or: #true
+        "#},
+    );
+}
+
+#[test]
+fn cannot_apply_boolean_operators_to_non_booleans() {
+    fail_execution(
+        "pass",
+        indoc! {r#"
+          (module)
+          {
+            node n
+            attr (n) and = #true && 1
+          }
+        "#},
+    );
+}
+
 #[test]
 fn can_execute_if_literal() {
     check_execution(
@@ -1054,6 +1606,87 @@ fn scoped_variables_can_appear_out_of_order() {
     );
 }
 
+#[test]
+fn can_print_graph_nodes_by_first_reference() {
+    let python_source = indoc! {r#"
+      import a
+      from b import c
+      print(a.d.f)
+    "#};
+    let dsl_source = indoc! {r#"
+      (identifier) @id
+      {
+        attr (@id.node) name = (source-text @id)
+      }
+
+      (identifier) @id
+      {
+        let @id.node = (node)
+      }
+
+      (call
+        function: (identifier) @fn
+        arguments: (argument_list (attribute attribute: (identifier) @last)))
+      {
+        edge @fn.node -> @last.node
+        attr (@fn.node) last = @last.node
+      }
+    "#};
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(python_source, None).unwrap();
+    let file =
+        File::from_str(tree_sitter_python::language(), dsl_source).expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals).lazy(true);
+    let graph = file
+        .execute(&tree, python_source, &config, &NoCancellation)
+        .expect("Could not execute file");
+    assert_eq!(
+        graph.pretty_print().to_string(),
+        indoc! {r#"
+          node 0
+            name: "a"
+          node 1
+            name: "b"
+          node 2
+            name: "c"
+          node 3
+            last: [graph node 6]
+            name: "print"
+          edge 3 -> 6
+          node 4
+            name: "a"
+          node 5
+            name: "d"
+          node 6
+            name: "f"
+        "#}
+    );
+    assert_eq!(
+        graph.pretty_print().by_first_reference(true).to_string(),
+        indoc! {r#"
+          node 0
+            name: "a"
+          node 1
+            name: "b"
+          node 2
+            name: "c"
+          node 3
+            last: [graph node 4]
+            name: "print"
+          edge 3 -> 4
+          node 4
+            name: "f"
+          node 5
+            name: "a"
+          node 6
+            name: "d"
+        "#}
+    );
+}
+
 #[test]
 fn variables_can_be_scoped_in_arbitrary_expressions() {
     check_execution(
@@ -1455,3 +2088,219 @@ fn can_execute_shorthand() {
         "#},
     );
 }
+
+#[test]
+fn can_dump_lazy_graph() {
+    init_log();
+    let python_source = indoc! {r#"
+      import a
+    "#};
+    let dsl_source = indoc! {r#"
+      (identifier) @id
+      {
+        attr (@id.node) name = (source-text @id)
+        edge @id.node -> @id.node
+        attr (@id.node -> @id.node) precedence = 1
+        print "visited", @id.node
+      }
+
+      (identifier) @id
+      {
+        let @id.node = (node)
+      }
+    "#};
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(python_source, None).unwrap();
+    let file =
+        File::from_str(tree_sitter_python::language(), dsl_source).expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals);
+    let dump = file
+        .build_lazy_graph(&tree, python_source, &config, &NoCancellation)
+        .expect("Could not build lazy graph");
+    let lines = dump.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("attr ("));
+    assert!(lines[0].contains("name = "));
+    assert!(lines[1].starts_with("edge "));
+    assert!(lines[1].contains("scoped"));
+    assert!(lines[2].starts_with("attr ("));
+    assert!(lines[2].contains("precedence = "));
+    assert!(lines[3].starts_with("print "));
+}
+
+#[test]
+fn can_dry_run_against_seeded_graph() {
+    init_log();
+    let python_source = indoc! {r#"
+      import a
+    "#};
+    let dsl_source = indoc! {r#"
+      global root
+
+      (identifier) @id
+      {
+        node n
+        attr (n) name = (source-text @id)
+        edge root -> n
+        attr (root -> n) precedence = 1
+        del-attr (root) kind
+      }
+    "#};
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(python_source, None).unwrap();
+    let file =
+        File::from_str(tree_sitter_python::language(), dsl_source).expect("Cannot parse file");
+    let mut graph = Graph::new();
+    let root = graph.add_graph_node();
+    graph[root]
+        .attributes
+        .add(Identifier::from("kind"), "root")
+        .unwrap();
+    let functions = Functions::stdlib();
+    let mut globals = Variables::new();
+    globals.add("root".into(), root.into()).unwrap();
+    let config = ExecutionConfig::new(&functions, &globals);
+    let events = file
+        .dry_run(&graph, &tree, python_source, &config, &NoCancellation)
+        .expect("Could not dry run");
+    let events = events.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        [
+            "node [graph node 1]",
+            "attr ([graph node 1]) name = a",
+            "edge [graph node 0] -> [graph node 1]",
+            "attr ([graph node 0] -> [graph node 1]) precedence = 1",
+            "del-attr ([graph node 0]) kind",
+        ]
+    );
+    assert_eq!(graph.node_count(), 1);
+    assert_eq!(
+        graph[root].attributes.get("kind"),
+        Some(&Value::from("root"))
+    );
+}
+
+#[test]
+fn cannot_dry_run_statements_that_duplicate_seeded_attributes() {
+    init_log();
+    let python_source = indoc! {r#"
+      pass
+    "#};
+    let dsl_source = indoc! {r#"
+      global root
+
+      (module)
+      {
+        attr (root) x = 2
+      }
+    "#};
+    let mut parser = Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    let tree = parser.parse(python_source, None).unwrap();
+    let file =
+        File::from_str(tree_sitter_python::language(), dsl_source).expect("Cannot parse file");
+    let mut graph = Graph::new();
+    let root = graph.add_graph_node();
+    graph[root]
+        .attributes
+        .add(Identifier::from("x"), 1)
+        .unwrap();
+    let functions = Functions::stdlib();
+    let mut globals = Variables::new();
+    globals.add("root".into(), root.into()).unwrap();
+    let config = ExecutionConfig::new(&functions, &globals);
+    let result = file.dry_run(&graph, &tree, python_source, &config, &NoCancellation);
+    match result {
+        Err(e) => assert!(
+            e.to_string()
+                .ends_with("Duplicate attribute x on [graph node 0] at (5, 3)"),
+            "unexpected error: {}",
+            e
+        ),
+        Ok(_) => panic!("Dry run succeeded unexpectedly"),
+    }
+}
+
+#[test]
+fn cannot_define_mutually_recursive_scoped_variables() {
+    let result = execute(
+        indoc! {r#"
+          pass
+        "#},
+        indoc! {r#"
+          (module) @m
+          {
+            let @m.a = @m.b
+            let @m.b = @m.a
+            node n
+            attr (n) value = @m.a
+          }
+        "#},
+    );
+    match result {
+        Err(e) => assert!(
+            e.to_string()
+                .ends_with("Recursively defined scoped variable @m.a at (3, 3)"),
+            "unexpected error: {}",
+            e
+        ),
+        Ok(_) => panic!("Execution succeeded unexpectedly"),
+    }
+}
+
+/// A file with a chain of scoped variables, one per `pass` statement, each defined in terms of
+/// the previous one
+const SCOPED_VARIABLE_CHAIN: &str = indoc! {r#"
+  (module . (pass_statement) @first)
+  {
+    let @first.v = 0
+  }
+
+  ((pass_statement) @a . (pass_statement) @b)
+  {
+    let @b.v = (plus @a.v 1)
+  }
+
+  (module (pass_statement) @last .)
+  {
+    node n
+    attr (n) v = @last.v
+  }
+"#};
+
+/// Executes a chain of scoped variables with the given number of links.  Each link takes several
+/// KiB of stack in debug builds, so this runs on a thread with a larger stack than the 2 MiB that
+/// test threads get.
+fn execute_scoped_variable_chain(links: usize) -> Result<String, ExecutionError> {
+    let python_source = "pass\n".repeat(links);
+    std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn_scoped(scope, || execute(&python_source, SCOPED_VARIABLE_CHAIN))
+            .unwrap()
+            .join()
+            .unwrap()
+    })
+}
+
+#[test]
+fn can_evaluate_long_chains_of_scoped_variables() {
+    let graph = execute_scoped_variable_chain(500).expect("Could not execute file");
+    assert_eq!(graph, "node 0\n  v: 499\n");
+}
+
+#[test]
+fn cannot_evaluate_too_long_chains_of_scoped_variables() {
+    match execute_scoped_variable_chain(5000) {
+        Err(ExecutionError::ExceededRecursionDepth(_)) => {}
+        Err(ExecutionError::InContext(_, cause))
+            if matches!(*cause, ExecutionError::ExceededRecursionDepth(_)) => {}
+        Err(e) => panic!("Unexpected error: {}", e),
+        Ok(_) => panic!("Execution succeeded unexpectedly"),
+    }
+}
@@ -9,7 +9,12 @@ use indoc::indoc;
 use tree_sitter::Parser;
 use tree_sitter::Point;
 use tree_sitter::Tree;
+use tree_sitter_graph::ast::File;
+use tree_sitter_graph::functions::Functions;
 use tree_sitter_graph::parse_error::ParseError;
+use tree_sitter_graph::ExecutionConfig;
+use tree_sitter_graph::NoCancellation;
+use tree_sitter_graph::Variables;
 
 fn init_log() {
     let _ = env_logger::builder()
@@ -92,3 +97,27 @@ fn can_move_tree_with_errors() {
     assert_eq!(positions, vec![Point::new(1, 4), Point::new(3, 4)]);
     let _recovered_tree = moved_parse_errors.into_tree();
 }
+
+#[test]
+fn can_execute_tree_with_error() {
+    let source = "a = 1\nb = (c +)\n";
+    let tree = parse(source);
+    assert!(ParseError::first(&tree).is_some());
+    let file = File::from_str(
+        tree_sitter_python::language(),
+        indoc! {r#"
+          (identifier) @id {
+            node n
+            attr (n) name = (source-text @id)
+          }
+        "#},
+    )
+    .expect("Cannot parse file");
+    let functions = Functions::stdlib();
+    let globals = Variables::new();
+    let config = ExecutionConfig::new(&functions, &globals);
+    let graph = file
+        .execute(&tree, source, &config, &NoCancellation)
+        .expect("Cannot execute file");
+    assert!(graph.node_count() >= 2);
+}
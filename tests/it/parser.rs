@@ -6,6 +6,7 @@
 // ------------------------------------------------------------------------------------------------
 
 use tree_sitter::CaptureQuantifier::*;
+use tree_sitter::QueryErrorKind;
 
 use tree_sitter_graph::ast::*;
 use tree_sitter_graph::Identifier;
@@ -74,6 +75,7 @@ fn can_parse_blocks() {
             }
             .into(),
             CreateEdge {
+                edge: None,
                 source: ScopedVariable {
                     scope: Box::new(
                         Capture {
@@ -122,6 +124,8 @@ fn can_parse_blocks() {
                     name: precedence,
                     value: Expression::TrueLiteral
                 }],
+                overwrite: false,
+                skip_null: false,
                 location: Location { row: 7, column: 10 },
             }
             .into(),
@@ -151,6 +155,9 @@ fn can_parse_blocks() {
                         value: Expression::TrueLiteral,
                     },
                 ],
+                spreads: vec![],
+                overwrite: false,
+                skip_null: false,
                 location: Location { row: 8, column: 10 },
             }
             .into(),
@@ -510,6 +517,177 @@ fn cannot_parse_nullable_regex() {
     }
 }
 
+#[test]
+fn cannot_parse_undefined_named_regex_capture() {
+    let source = r#"
+        (module) @root
+        {
+          scan "abc" {
+            "(?P<first>a)" {
+              node n
+              attr (n) name = $second
+            }
+          }
+        }
+    "#;
+    let err = match File::from_str(tree_sitter_python::language(), source) {
+        Ok(_) => panic!("Parse succeeded unexpectedly"),
+        Err(e) => e,
+    };
+    assert_eq!(
+        err.to_string(),
+        "Undefined regex capture $second at (7, 31)"
+    );
+}
+
+#[test]
+fn cannot_parse_numbered_regex_capture_in_default_scan_arm() {
+    let source = r#"
+        (module) @root
+        {
+          scan (source-text @root) {
+            "x(y)" {
+              node n
+            }
+            else {
+              node m
+              attr (m) w = $1
+            }
+          }
+        }
+    "#;
+    let err = match File::from_str(tree_sitter_python::language(), source) {
+        Ok(_) => panic!("Parse succeeded unexpectedly"),
+        Err(e) => e,
+    };
+    assert_eq!(err.to_string(), "Undefined regex capture $1 at (10, 28)");
+}
+
+#[test]
+fn cannot_parse_undefined_syntax_capture_in_untaken_branch() {
+    let source = r#"
+        (module) @root
+        {
+          node n
+          attr (n) root = (node-type @root)
+          if #false {
+            attr (n) missing = (node-type @missing)
+          }
+        }
+    "#;
+    let err = match File::from_str(tree_sitter_python::language(), source) {
+        Ok(_) => panic!("Parse succeeded unexpectedly"),
+        Err(e) => e,
+    };
+    assert_eq!(
+        err.to_string(),
+        "Undefined syntax capture @missing at (7, 43)"
+    );
+}
+
+#[test]
+fn can_parse_comparison() {
+    let source = r#"
+        (identifier) @x
+        {
+          if @x == "foo" {
+            node n
+          }
+          let y = (not 1 < 2)
+        }
+    "#;
+    let file = File::from_str(tree_sitter_python::language(), source).expect("Cannot parse file");
+    let statements = &file.stanzas[0].statements;
+    let condition = match &statements[0] {
+        Statement::If(stmt) => &stmt.arms[0].conditions[0],
+        stmt => panic!("Unexpected statement {}", stmt),
+    };
+    assert_eq!(condition.to_string(), "@x == \"foo\"");
+    match &statements[1] {
+        Statement::DeclareImmutable(stmt) => assert_eq!(stmt.value.to_string(), "(not 1 < 2)"),
+        stmt => panic!("Unexpected statement {}", stmt),
+    }
+}
+
+#[test]
+fn can_parse_attribute_statements() {
+    let source = r#"
+        (module)
+        {
+          node n
+          attr (n) name = "n"
+          set-attr (n) name = "m"
+          attr? (n) kind = #null
+          del-attr (n) name, kind
+          del-attr (n -> n) precedence
+        }
+    "#;
+    let file = File::from_str(tree_sitter_python::language(), source).expect("Cannot parse file");
+    let statements = file.stanzas[0]
+        .statements
+        .iter()
+        .map(|stmt| stmt.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        statements,
+        vec![
+            "node n at (4, 11)",
+            "attr (n) name = \"n\" at (5, 11)",
+            "set-attr (n) name = \"m\" at (6, 11)",
+            "attr? (n) kind = #null at (7, 11)",
+            "del-attr (n) name kind at (8, 11)",
+            "del-attr (n -> n) precedence at (9, 11)",
+        ]
+    );
+}
+
+#[test]
+fn can_parse_boolean_operators() {
+    let source = r#"
+        (identifier) @x
+        {
+          let a = #true || #false && !#true
+          let b = (#true || #false) && !(@x == 1)
+          let c = (source-text @x) && (node)
+        }
+    "#;
+    let file = File::from_str(tree_sitter_python::language(), source).expect("Cannot parse file");
+    let values = file.stanzas[0]
+        .statements
+        .iter()
+        .map(|stmt| match stmt {
+            Statement::DeclareImmutable(stmt) => stmt.value.to_string(),
+            stmt => panic!("Unexpected statement {}", stmt),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        values,
+        vec![
+            "true || false && !true",
+            "(true || false) && !(@x == 1)",
+            "(source-text @x) && (node)"
+        ]
+    );
+}
+
+#[test]
+fn cannot_parse_chained_comparison() {
+    let source = r#"
+        (identifier) @x
+        {
+          let y = 1 < 2 < 3
+        }
+    "#;
+    let err = match File::from_str(tree_sitter_python::language(), source) {
+        Ok(_) => panic!("Parse succeeded unexpectedly"),
+        Err(e) => e,
+    };
+    assert_eq!(
+        err.to_string(),
+        "Unexpected character '<' in comparison at (4, 25)"
+    );
+}
+
 #[test]
 fn can_parse_star_capture() {
     let source = r#"
@@ -1222,6 +1400,7 @@ fn can_parse_global() {
             }
             .into(),
             CreateEdge {
+                edge: None,
                 source: UnscopedVariable {
                     name: "n".into(),
                     location: Location { row: 5, column: 15 },
@@ -1279,6 +1458,58 @@ fn can_parse_global_with_default() {
     );
 }
 
+#[test]
+fn can_access_compiled_queries() {
+    let source = r#"
+        (module) {}
+        (identifier) {}
+        (pass_statement) {}
+    "#;
+    let file = File::from_str(tree_sitter_python::language(), source).expect("Cannot parse file");
+    let query = file.combined_query().expect("Missing combined query");
+    assert_eq!(query.pattern_count(), file.stanzas().len());
+    for stanza in file.stanzas() {
+        assert_eq!(stanza.query().pattern_count(), 1);
+    }
+
+    let file = File::new(tree_sitter_python::language());
+    assert!(file.combined_query().is_none());
+}
+
+#[test]
+fn can_list_required_globals() {
+    let source = r#"
+        global FILENAME
+        global PKG_NAME = ""
+        global ROOTS*
+    "#;
+    let file = File::from_str(tree_sitter_python::language(), source).expect("Cannot parse file");
+    let required = file.required_globals().collect::<Vec<_>>();
+    assert_eq!(required, vec!["FILENAME", "ROOTS"]);
+}
+
+#[test]
+fn can_list_referenced_globals() {
+    let source = r#"
+        global filename
+        global unused = ""
+        (module) @m
+        {
+          let @m.name = (replace filename "\\.py$" "")
+          if (eq filename "") {
+            let local = filename
+            print local
+          }
+        }
+    "#;
+    let file = File::from_str(tree_sitter_python::language(), source).expect("Cannot parse file");
+    let referenced = file.referenced_globals();
+    assert_eq!(
+        referenced,
+        std::collections::HashSet::from([Identifier::from("filename")])
+    );
+}
+
 #[test]
 fn cannot_parse_undeclared_global() {
     let source = r#"
@@ -1345,6 +1576,7 @@ fn can_parse_list_global() {
                 }
                 .into(),
                 CreateEdge {
+                    edge: None,
                     source: UnscopedVariable {
                         name: "n".into(),
                         location: Location { row: 6, column: 17 },
@@ -1417,6 +1649,7 @@ fn can_parse_optional_global() {
                     }
                     .into(),
                     CreateEdge {
+                        edge: None,
                         source: UnscopedVariable {
                             name: "n".into(),
                             location: Location { row: 6, column: 17 },
@@ -1550,7 +1783,7 @@ fn query_parse_errors_have_file_location() {
     "#;
     let err = match File::from_str(tree_sitter_python::language(), source) {
         Ok(_) => panic!("Parse succeeded unexpectedly"),
-        Err(ParseError::QueryError(e)) => e,
+        Err(ParseError::QueryError(e, _, _)) => e,
         Err(e) => panic!("Unexpected error: {}", e),
     };
     assert_eq!(err.row, 2, "expected row 2, got {}", err.row);
@@ -1558,6 +1791,85 @@ fn query_parse_errors_have_file_location() {
     assert_eq!(err.offset, 48, "expected offset 48, got {}", err.offset);
 }
 
+#[test]
+fn query_parse_errors_have_stanza_location_and_query() {
+    let source = r#"
+        (module) {}
+
+        (function_definition
+          name: (nonexistent_kind) @name) {
+          node @name.def
+        }
+    "#;
+    match File::from_str(tree_sitter_python::language(), source) {
+        Ok(_) => panic!("Parse succeeded unexpectedly"),
+        Err(ParseError::QueryError(e, location, query)) => {
+            assert_eq!(e.kind, QueryErrorKind::NodeType);
+            assert_eq!(location, Location { row: 3, column: 8 });
+            assert_eq!(
+                query,
+                "(function_definition\n          name: (nonexistent_kind) @name)"
+            );
+        }
+        Err(e) => panic!("Unexpected error: {}", e),
+    };
+}
+
+#[test]
+fn integer_constant_errors_have_location() {
+    for (constant, column, out_of_range) in [
+        ("4294967296", 31, true),
+        ("0x100000000", 31, true),
+        ("0xfg", 31, false),
+        ("0b", 31, false),
+    ] {
+        let source = format!("(module) {{ node n attr (n) v = {} }}", constant);
+        match File::from_str(tree_sitter_python::language(), &source) {
+            Ok(_) => panic!("Parse of {} succeeded unexpectedly", constant),
+            Err(ParseError::IntegerOutOfRange(text, location)) if out_of_range => {
+                assert_eq!(text, constant);
+                assert_eq!(location, Location { row: 0, column });
+            }
+            Err(ParseError::InvalidInteger(text, location)) if !out_of_range => {
+                assert_eq!(text, constant);
+                assert_eq!(location, Location { row: 0, column });
+            }
+            Err(e) => panic!("Unexpected error for {}: {}", constant, e),
+        }
+    }
+}
+
+#[test]
+fn negative_integer_constants_are_parse_errors() {
+    let source = "(module) { node n attr (n) v = -5 }";
+    match File::from_str(tree_sitter_python::language(), source) {
+        Ok(_) => panic!("Parse succeeded unexpectedly"),
+        Err(ParseError::UnexpectedCharacter('-', _, location)) => {
+            assert_eq!(location, Location { row: 0, column: 31 });
+        }
+        Err(e) => panic!("Unexpected error: {}", e),
+    }
+}
+
+#[test]
+fn too_deeply_nested_expressions_are_parse_errors() {
+    let depth = 5000;
+    for value in [
+        format!("{}#true{}", "(not ".repeat(depth), ")".repeat(depth)),
+        format!("{}#true", "!".repeat(depth)),
+        format!("{}#true{}", "[".repeat(depth), "]".repeat(depth)),
+        format!("@m{}", ".v".repeat(depth)),
+        vec!["#true"; depth].join(" || "),
+    ] {
+        let source = format!("(module) @m\n{{\n  node n\n  attr (n) v = {}\n}}\n", value);
+        match File::from_str(tree_sitter_python::language(), &source) {
+            Err(ParseError::ExpressionTooDeep(_, location)) => assert_eq!(location.row, 3),
+            Err(e) => panic!("Unexpected error: {}", e),
+            Ok(_) => panic!("Parse succeeded unexpectedly"),
+        }
+    }
+}
+
 #[test]
 fn multiline_query_parse_errors_have_file_location() {
     let source = r#"
@@ -1572,7 +1884,7 @@ fn multiline_query_parse_errors_have_file_location() {
     "#;
     let err = match File::from_str(tree_sitter_python::language(), source) {
         Ok(_) => panic!("Parse succeeded unexpectedly"),
-        Err(ParseError::QueryError(e)) => e,
+        Err(ParseError::QueryError(e, _, _)) => e,
         Err(e) => panic!("Unexpected error: {}", e),
     };
     assert_eq!(err.row, 6, "expected row 6, got {}", err.row);
@@ -1591,6 +1903,60 @@ fn cannot_parse_unused_capture() {
     }
 }
 
+#[test]
+fn can_parse_match_functions() {
+    let source = r#"
+        (module) @root
+        {
+          node n
+          attr (n) name = (capture-name @root), index = (match-index)
+        }
+    "#;
+    let file = File::from_str(tree_sitter_python::language(), source).expect("Cannot parse file");
+    let statement = &file.stanzas[0].statements[1];
+    assert_eq!(
+        statement.to_string(),
+        "attr (n) name = (capture-name @root) index = (match-index) at (5, 11)"
+    );
+    match statement {
+        Statement::AddGraphNodeAttribute(stmt) => {
+            assert!(matches!(
+                &stmt.attributes[0].value,
+                Expression::MatchFunction(MatchFunction::CaptureName(capture))
+                    if capture.name.as_str() == "root"
+            ));
+            assert_eq!(
+                stmt.attributes[1].value,
+                Expression::MatchFunction(MatchFunction::MatchIndex)
+            );
+        }
+        _ => panic!("Unexpected statement {}", statement),
+    }
+}
+
+#[test]
+fn cannot_parse_match_functions_with_invalid_parameters() {
+    for call in [
+        "(match-index 1)",
+        "(full-match @root)",
+        "(capture-name \"root\")",
+        "(quantifier)",
+    ] {
+        let source = format!(
+            "(module) @root\n{{\n  node n\n  attr (n) v = {}, root = @root\n}}\n",
+            call
+        );
+        match File::from_str(tree_sitter_python::language(), &source) {
+            Ok(_) => panic!("Parse of {} succeeded unexpectedly", call),
+            Err(ParseError::Check(e)) => assert_eq!(
+                e.to_string(),
+                format!("Invalid parameters in {} at (4, 3)", call)
+            ),
+            Err(e) => panic!("Unexpected error for {}: {}", call, e),
+        }
+    }
+}
+
 #[test]
 fn can_parse_explicitly_unused_capture() {
     let source = r#"
@@ -1599,3 +1965,205 @@ fn can_parse_explicitly_unused_capture() {
     "#;
     File::from_str(tree_sitter_python::language(), source).expect("parse to succeed");
 }
+
+#[test]
+fn can_parse_included_file() {
+    let common = r#"
+        global filename
+
+        (function_definition name: (identifier) @name) {
+          node @name.def
+        }
+    "#;
+    let source = r#"
+        include "common.tsg"
+
+        (call function: (identifier) @name) {
+          node @name.ref
+        }
+    "#;
+    let file = File::from_str_with_resolver(tree_sitter_python::language(), source, |path| {
+        assert_eq!(path, "common.tsg");
+        Ok(common.to_string())
+    })
+    .expect("Cannot parse file");
+    assert_eq!(file.globals.len(), 1);
+    assert_eq!(file.globals[0].name, "filename");
+    assert_eq!(file.stanzas.len(), 2);
+    assert_eq!(file.stanzas[0].file.as_deref(), Some("common.tsg"));
+    assert_eq!(file.stanzas[1].file, None);
+    assert_eq!(file.combined_query().unwrap().pattern_count(), 2);
+}
+
+#[test]
+fn errors_in_included_files_name_the_file() {
+    let source = r#"
+        include "common.tsg"
+    "#;
+    for (common, message) in [
+        (
+            "(module) { node }",
+            "In included file \"common.tsg\": Unexpected character '}' in expression at (1, 17)",
+        ),
+        (
+            "(module) { attr (n) name = 1 }",
+            "In included file \"common.tsg\": Undefined variable n at (1, 18)",
+        ),
+    ] {
+        match File::from_str_with_resolver(tree_sitter_python::language(), source, |_| {
+            Ok(common.to_string())
+        }) {
+            Err(e) => assert_eq!(e.to_string(), message),
+            Ok(_) => panic!("Parse succeeded unexpectedly"),
+        }
+    }
+}
+
+#[test]
+fn cannot_parse_cyclic_include() {
+    let source = r#"
+        include "a.tsg"
+    "#;
+    let result = File::from_str_with_resolver(tree_sitter_python::language(), source, |path| {
+        Ok(match path {
+            "a.tsg" => r#"include "b.tsg""#.to_string(),
+            _ => r#"include "a.tsg""#.to_string(),
+        })
+    });
+    match result {
+        Err(ParseError::InIncludedFile(outer, cause)) => match *cause {
+            ParseError::InIncludedFile(inner, cause) => {
+                assert_eq!((outer.as_str(), inner.as_str()), ("a.tsg", "b.tsg"));
+                assert!(matches!(*cause, ParseError::CyclicInclude(path, _) if path == "a.tsg"));
+            }
+            e => panic!("Unexpected error: {}", e),
+        },
+        Err(e) => panic!("Unexpected error: {}", e),
+        Ok(_) => panic!("Parse succeeded unexpectedly"),
+    }
+}
+
+#[test]
+fn cannot_parse_include_without_resolver() {
+    let source = r#"
+        include "common.tsg"
+    "#;
+    if File::from_str(tree_sitter_python::language(), source).is_ok() {
+        panic!("Parse succeeded unexpectedly");
+    }
+}
+
+#[test]
+fn can_introspect_stanzas() {
+    let source = r#"
+        (function_definition name: (identifier) @_name) {
+        }
+
+        ; a comment between stanzas
+        (call) @_call
+        {
+        }
+    "#;
+    let file = File::from_str(tree_sitter_python::language(), source).expect("Cannot parse file");
+    let stanzas = file.stanzas();
+    assert_eq!(stanzas.len(), 2);
+    assert_eq!(
+        stanzas[0].query_source,
+        "(function_definition name: (identifier) @_name)"
+    );
+    assert_eq!(stanzas[0].location(), Location { row: 1, column: 8 });
+    assert_eq!(stanzas[1].query_source, "(call) @_call");
+    assert_eq!(stanzas[1].location(), Location { row: 5, column: 8 });
+}
+
+#[test]
+fn can_get_stanza_doc_comments() {
+    let source = r#"
+        ; Defines a graph node for each function.
+        ;; The node is stored in a scoped variable.
+        (function_definition) @func {
+          node @func.node
+        } ; not part of the next comment
+        ; Calls are matched anywhere.
+        stanza calls
+        (call) @_call
+        {
+        }
+
+        ; separated by a blank line
+
+        (module) @_module {
+        }
+    "#;
+    let file = File::from_str(tree_sitter_python::language(), source).expect("Cannot parse file");
+    let stanzas = file.stanzas();
+    assert_eq!(
+        stanzas[0].doc.as_deref(),
+        Some("Defines a graph node for each function.\nThe node is stored in a scoped variable.")
+    );
+    assert_eq!(
+        stanzas[1].doc.as_deref(),
+        Some("Calls are matched anywhere.")
+    );
+    assert_eq!(stanzas[2].doc, None);
+}
+
+#[test]
+fn can_parse_stanzas_for_other_languages() {
+    let source = r#"
+        global root
+
+        (module) @_module {
+        }
+
+        language json
+        (pair) @pair {
+          node n
+          attr (n) key = (source-text @pair), root = root
+        }
+
+        stanza values language json
+        (number) @_number {
+        }
+    "#;
+    let file = File::from_str_with_languages(
+        tree_sitter_python::language(),
+        &[("json", tree_sitter_json::language())],
+        source,
+    )
+    .expect("Cannot parse file");
+    assert_eq!(file.stanzas().len(), 1);
+    assert_eq!(file.stanzas()[0].language, None);
+    let json = &file.languages["json"];
+    assert_eq!(json.stanzas().len(), 2);
+    assert_eq!(json.stanzas()[0].language, Some(Identifier::from("json")));
+    assert_eq!(json.stanzas()[0].query_source, "(pair) @pair");
+    assert_eq!(json.stanzas()[1].name, Some(Identifier::from("values")));
+    assert_eq!(
+        json.stanzas()[1].location(),
+        Location { row: 13, column: 8 }
+    );
+    assert_eq!(json.combined_query().unwrap().pattern_count(), 2);
+    assert_eq!(json.globals, file.globals);
+    assert!(file.referenced_globals().contains("root"));
+}
+
+#[test]
+fn cannot_parse_stanza_for_undefined_language() {
+    let source = r#"
+        language json
+        (pair) @_pair {
+        }
+    "#;
+    let err = match File::from_str(tree_sitter_python::language(), source) {
+        Ok(_) => panic!("Parse succeeded unexpectedly"),
+        Err(err) => err,
+    };
+    match err {
+        ParseError::UndefinedLanguage(language, location) => {
+            assert_eq!(language, "json");
+            assert_eq!(location, Location { row: 1, column: 17 });
+        }
+        _ => panic!("Unexpected error {}", err),
+    }
+}
@@ -19,3 +19,14 @@ fn can_create_nested_variables() {
     let w = f(&v);
     w.get(&"foo".into()).expect("Failed to get foo");
 }
+
+#[test]
+fn can_iterate_variables_in_order() {
+    let mut v = Variables::new();
+    v.add("foo".into(), 1.into()).expect("Failed to set foo");
+    v.add("bar".into(), 2.into()).expect("Failed to set bar");
+    let names = v.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["bar", "foo"]);
+    let values = v.iter().map(|(_, value)| value.clone()).collect::<Vec<_>>();
+    assert_eq!(values, vec![2.into(), 1.into()]);
+}